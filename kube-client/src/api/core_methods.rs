@@ -5,7 +5,12 @@ use std::fmt::Debug;
 
 use crate::{api::Api, Error, Result};
 use kube_core::{
-    metadata::PartialObjectMeta, object::ObjectList, params::*, response::Status, ErrorResponse, WatchEvent,
+    metadata::PartialObjectMeta,
+    object::ObjectList,
+    params::*,
+    response::Status,
+    table::{IncludeObject, Table},
+    ErrorResponse, WatchEvent,
 };
 
 /// PUSH/PUT/POST/GET abstractions
@@ -219,7 +224,7 @@ where
     pub async fn list(&self, lp: &ListParams) -> Result<ObjectList<K>> {
         let mut req = self.request.list(lp).map_err(Error::BuildRequest)?;
         req.extensions_mut().insert("list");
-        self.client.request::<ObjectList<K>>(req).await
+        self.client.request_list::<K>(req).await
     }
 
     /// Get a list of resources that contains only their metadata as
@@ -246,7 +251,58 @@ where
     pub async fn list_metadata(&self, lp: &ListParams) -> Result<ObjectList<PartialObjectMeta<K>>> {
         let mut req = self.request.list_metadata(lp).map_err(Error::BuildRequest)?;
         req.extensions_mut().insert("list_metadata");
-        self.client.request::<ObjectList<PartialObjectMeta<K>>>(req).await
+        self.client.request_list::<PartialObjectMeta<K>>(req).await
+    }
+
+    /// Get a list of resources rendered as a [`Table`] of printer columns
+    ///
+    /// This is what `kubectl get` displays; the columns come from the built-in printers
+    /// for core types and from `additionalPrinterColumns` for custom resources.
+    /// Rows carry the object metadata unless another [`IncludeObject`] is requested.
+    ///
+    /// ```no_run
+    /// use kube::api::{Api, IncludeObject, ListParams};
+    /// use k8s_openapi::api::core::v1::Pod;
+    ///
+    /// # async fn wrapper() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client: kube::Client = todo!();
+    /// let pods: Api<Pod> = Api::namespaced(client, "apps");
+    /// let table = pods.list_table(&ListParams::default(), IncludeObject::None).await?;
+    /// let columns: Vec<_> = table.columns(0).collect();
+    /// let names: Vec<_> = columns.iter().map(|(_, c)| c.name.as_str()).collect();
+    /// println!("{}", names.join("\t"));
+    /// for row in &table.rows {
+    ///     let cells: Vec<_> = columns
+    ///         .iter()
+    ///         .map(|(i, _)| {
+    ///             let cell = &row.cells[*i];
+    ///             cell.as_str().map_or_else(|| cell.to_string(), str::to_owned)
+    ///         })
+    ///         .collect();
+    ///     println!("{}", cells.join("\t"));
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn list_table(&self, lp: &ListParams, include: IncludeObject) -> Result<Table<K>> {
+        let mut req = self
+            .request
+            .list_table(lp, include)
+            .map_err(Error::BuildRequest)?;
+        req.extensions_mut().insert("list_table");
+        self.client.request::<Table<K>>(req).await
+    }
+
+    /// Get a named resource rendered as a single row [`Table`] of printer columns
+    ///
+    /// See [`Api::list_table`] for details on the returned table.
+    pub async fn get_table(&self, name: &str, include: IncludeObject) -> Result<Table<K>> {
+        let mut req = self
+            .request
+            .get_table(name, &GetParams::default(), include)
+            .map_err(Error::BuildRequest)?;
+        req.extensions_mut().insert("get_table");
+        self.client.request::<Table<K>>(req).await
     }
 
     /// Create a resource
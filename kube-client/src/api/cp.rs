@@ -0,0 +1,637 @@
+//! Copying files to and from containers by streaming tar archives over exec
+use std::{
+    fmt, fs,
+    io::{self, Read, Write},
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
+
+use k8s_openapi::api::core::v1::Pod;
+use thiserror::Error;
+use tokio_util::io::SyncIoBridge;
+
+use super::{
+    exec::{check_status, read_capped},
+    Api, AttachParams, ExecError, ExecOutput, DEFAULT_OUTPUT_LIMIT,
+};
+
+/// The size of the buffers between the archive and the connection
+const BUF_SIZE: usize = 64 * 1024;
+
+/// Errors from [`Api::upload`] and [`Api::download`]
+#[derive(Debug, Error)]
+pub enum CopyError {
+    /// Running `tar` in the container failed
+    #[error("failed to run tar in the container: {0}")]
+    Exec(#[source] ExecError),
+
+    /// Reading or writing local files or the archive failed
+    #[error("failed to copy files: {0}")]
+    Io(#[source] io::Error),
+
+    /// The path in the container does not name a file or directory
+    #[error("invalid path in the container: {0:?}")]
+    InvalidPath(String),
+
+    /// The archive has an entry outside of the copied path
+    #[error("archive entry {0:?} is outside of the copied path")]
+    UnsafePath(PathBuf),
+
+    /// The archive has a link pointing outside of the copied path
+    #[error("archive entry {path:?} links to {target:?} outside of the copied path")]
+    UnsafeLink {
+        /// The path of the link in the archive
+        path: PathBuf,
+        /// Where the link points to
+        target: PathBuf,
+    },
+}
+
+/// The progress of a copy, reported after every file, directory or link
+#[derive(Debug)]
+pub struct CopyProgress<'a> {
+    /// The local path of the entry that was copied
+    pub path: &'a Path,
+    /// The number of entries copied so far
+    pub entries: usize,
+    /// The number of bytes of file contents copied so far
+    pub bytes: u64,
+}
+
+type ProgressFn = dyn Fn(&CopyProgress<'_>) + Send + Sync;
+
+/// Parameters for [`Api::upload`] and [`Api::download`]
+#[derive(Clone, Default)]
+pub struct CopyParams {
+    /// The container to copy to or from, if the pod has more than one
+    pub container: Option<String>,
+    progress: Option<Arc<ProgressFn>>,
+}
+
+impl fmt::Debug for CopyParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CopyParams")
+            .field("container", &self.container)
+            .field("progress", &self.progress.is_some())
+            .finish()
+    }
+}
+
+impl CopyParams {
+    /// Copy to or from the given container
+    #[must_use]
+    pub fn container<T: Into<String>>(mut self, container: T) -> Self {
+        self.container = Some(container.into());
+        self
+    }
+
+    /// Call `progress` after every copied entry
+    ///
+    /// The callback runs on a blocking thread, so it should return quickly.
+    #[must_use]
+    pub fn progress(mut self, progress: impl Fn(&CopyProgress<'_>) + Send + Sync + 'static) -> Self {
+        self.progress = Some(Arc::new(progress));
+        self
+    }
+
+    fn attach_params(&self, stdin: bool) -> AttachParams {
+        let mut ap = AttachParams::default()
+            .stdin(stdin)
+            .stdout(!stdin)
+            .max_stdin_buf_size(BUF_SIZE)
+            .max_stdout_buf_size(BUF_SIZE);
+        if let Some(container) = &self.container {
+            ap = ap.container(container);
+        }
+        ap
+    }
+}
+
+/// Counts the copied entries for the progress callback
+struct Progress {
+    callback: Option<Arc<ProgressFn>>,
+    entries: usize,
+    bytes: u64,
+}
+
+impl Progress {
+    fn new(callback: Option<Arc<ProgressFn>>) -> Self {
+        Self {
+            callback,
+            entries: 0,
+            bytes: 0,
+        }
+    }
+
+    fn copied(&mut self, path: &Path, bytes: u64) {
+        self.entries += 1;
+        self.bytes += bytes;
+        if let Some(callback) = &self.callback {
+            callback(&CopyProgress {
+                path,
+                entries: self.entries,
+                bytes: self.bytes,
+            });
+        }
+    }
+}
+
+impl Api<Pod> {
+    /// Copy a local file or directory into a container, like `kubectl cp`
+    ///
+    /// The file or directory is created at `remote`, replacing files that already exist.
+    /// Permissions are preserved, and symbolic links are copied as links rather than followed.
+    /// The container needs a `tar` executable.
+    ///
+    /// Servers that do not support version 5 of the streaming protocol cannot report
+    /// whether `tar` succeeded, as the connection has to be closed to end its input.
+    ///
+    /// ```no_run
+    /// use kube::api::{Api, CopyParams};
+    /// use k8s_openapi::api::core::v1::Pod;
+    ///
+    /// # async fn wrapper() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client: kube::Client = todo!();
+    /// let pods: Api<Pod> = Api::default_namespaced(client);
+    /// let cp = CopyParams::default().progress(|p| println!("{} ({} bytes)", p.path.display(), p.bytes));
+    /// pods.upload("web", "./static", "/usr/share/nginx/html", &cp).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn upload(
+        &self,
+        name: &str,
+        local: impl AsRef<Path>,
+        remote: &str,
+        cp: &CopyParams,
+    ) -> Result<(), CopyError> {
+        let (dir, base) = split_remote(remote)?;
+        let local = local.as_ref().to_owned();
+        let command = ["tar", "-xmf", "-", "-C", dir];
+        let mut process = self
+            .exec(name, command, &cp.attach_params(true))
+            .await
+            .map_err(|err| CopyError::Exec(ExecError::Connect(err)))?;
+
+        let mut writer = SyncIoBridge::new(process.stdin().expect("stdin is attached"));
+        let mut progress = Progress::new(cp.progress.clone());
+        let base = base.to_owned();
+        let archive = tokio::task::spawn_blocking(move || {
+            let result = write_archive(&mut writer, &local, &base, &mut progress);
+            // Closing stdin lets tar finish
+            let _ = writer.shutdown();
+            result
+        });
+        let status = process.take_status().expect("status is available");
+        let (archived, stderr) = tokio::join!(archive, read_capped(process.stderr(), DEFAULT_OUTPUT_LIMIT));
+        let status = status.await;
+        let joined = process.join().await;
+        let (stderr, stderr_truncated) = stderr.map_err(|err| CopyError::Exec(ExecError::Output(err)))?;
+
+        let output = ExecOutput {
+            stderr,
+            stderr_truncated,
+            ..ExecOutput::default()
+        };
+        if let Some(status) = status {
+            check_status(status, output).map_err(CopyError::Exec)?;
+        }
+        archived
+            .map_err(|err| CopyError::Io(io::Error::other(err)))?
+            .map_err(CopyError::Io)?;
+        joined.map_err(|err| CopyError::Exec(ExecError::Stream(Box::new(err))))
+    }
+
+    /// Copy a file or directory out of a container, like `kubectl cp`
+    ///
+    /// The file or directory is created at `local`, replacing files that already exist.
+    /// Permissions are preserved. Entries outside of the copied path, links pointing outside of it,
+    /// and entries below a link fail the copy without being written. The container needs a `tar` executable.
+    ///
+    /// ```no_run
+    /// use kube::api::{Api, CopyParams};
+    /// use k8s_openapi::api::core::v1::Pod;
+    ///
+    /// # async fn wrapper() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client: kube::Client = todo!();
+    /// let pods: Api<Pod> = Api::default_namespaced(client);
+    /// pods.download("web", "/var/log/nginx", "./logs", &CopyParams::default().container("nginx")).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn download(
+        &self,
+        name: &str,
+        remote: &str,
+        local: impl AsRef<Path>,
+        cp: &CopyParams,
+    ) -> Result<(), CopyError> {
+        let (dir, base) = split_remote(remote)?;
+        let local = local.as_ref().to_owned();
+        let command = ["tar", "-cf", "-", "-C", dir, base];
+        let mut process = self
+            .exec(name, command, &cp.attach_params(false))
+            .await
+            .map_err(|err| CopyError::Exec(ExecError::Connect(err)))?;
+
+        let reader = SyncIoBridge::new(process.stdout().expect("stdout is attached"));
+        let mut progress = Progress::new(cp.progress.clone());
+        let base = base.to_owned();
+        let unpack =
+            tokio::task::spawn_blocking(move || unpack_archive(reader, &base, &local, &mut progress));
+        let status = process.take_status().expect("status is available");
+        let (unpacked, stderr) = tokio::join!(unpack, read_capped(process.stderr(), DEFAULT_OUTPUT_LIMIT));
+        let status = status.await;
+        let joined = process.join().await;
+        let (stderr, stderr_truncated) = stderr.map_err(|err| CopyError::Exec(ExecError::Output(err)))?;
+
+        let output = ExecOutput {
+            stderr,
+            stderr_truncated,
+            ..ExecOutput::default()
+        };
+        // tar failing explains an archive that ended early, so it takes precedence
+        let checked = status.map(|status| check_status(status, output));
+        if let Some(Err(err)) = checked {
+            return Err(CopyError::Exec(err));
+        }
+        unpacked.map_err(|err| CopyError::Io(io::Error::other(err)))??;
+        joined.map_err(|err| CopyError::Exec(ExecError::Stream(Box::new(err))))?;
+        checked
+            .ok_or(CopyError::Exec(ExecError::MissingStatus))?
+            .map(|_| ())
+            .map_err(CopyError::Exec)
+    }
+}
+
+/// Split a path in a container into its parent directory and file name
+fn split_remote(remote: &str) -> Result<(&str, &str), CopyError> {
+    let trimmed = remote.trim_end_matches('/');
+    let (dir, base) = match trimmed.rsplit_once('/') {
+        Some(("", base)) => ("/", base),
+        Some((dir, base)) => (dir, base),
+        None => (".", trimmed),
+    };
+    if matches!(base, "" | "." | "..") {
+        return Err(CopyError::InvalidPath(remote.to_owned()));
+    }
+    Ok((dir, base))
+}
+
+/// Write a file or directory to a tar archive, naming it `base`
+fn write_archive(writer: impl Write, local: &Path, base: &str, progress: &mut Progress) -> io::Result<()> {
+    let mut builder = tar::Builder::new(writer);
+    builder.follow_symlinks(false);
+    append(&mut builder, local, Path::new(base), progress)?;
+    builder.into_inner()?.flush()
+}
+
+fn append(
+    builder: &mut tar::Builder<impl Write>,
+    path: &Path,
+    name: &Path,
+    progress: &mut Progress,
+) -> io::Result<()> {
+    builder.append_path_with_name(path, name)?;
+    let metadata = path.symlink_metadata()?;
+    progress.copied(path, if metadata.is_file() { metadata.len() } else { 0 });
+    if metadata.is_dir() {
+        let mut children = fs::read_dir(path)?.collect::<io::Result<Vec<_>>>()?;
+        children.sort_by_key(fs::DirEntry::file_name);
+        for child in children {
+            append(builder, &child.path(), &name.join(child.file_name()), progress)?;
+        }
+    }
+    Ok(())
+}
+
+/// Unpack a tar archive of a file or directory named `base` to `local`
+///
+/// Every entry must be `base` or inside it, not below a link, and links must point inside it.
+fn unpack_archive(
+    reader: impl Read,
+    base: &str,
+    local: &Path,
+    progress: &mut Progress,
+) -> Result<(), CopyError> {
+    let mut archive = tar::Archive::new(reader);
+    let mut dirs = Vec::new();
+    for entry in archive.entries().map_err(CopyError::Io)? {
+        let mut entry = entry.map_err(CopyError::Io)?;
+        let path = entry.path().map_err(CopyError::Io)?.into_owned();
+        let relative = relative_path(&path, base).ok_or_else(|| CopyError::UnsafePath(path.clone()))?;
+        // Joining an empty path would add a trailing separator
+        let target = if relative.as_os_str().is_empty() {
+            local.to_owned()
+        } else {
+            local.join(&relative)
+        };
+        // Links unpacked by earlier entries could otherwise lead anywhere
+        if passes_through_link(local, &relative) {
+            return Err(CopyError::UnsafePath(path));
+        }
+        let entry_type = entry.header().entry_type();
+
+        if entry_type.is_dir() {
+            fs::create_dir_all(&target).map_err(CopyError::Io)?;
+            // Permissions are set last, so read-only directories can still be filled
+            dirs.push((target.clone(), entry.header().mode().ok()));
+            progress.copied(&target, 0);
+            continue;
+        }
+        if !(entry_type.is_file() || entry_type.is_symlink() || entry_type.is_hard_link()) {
+            tracing::debug!("skipping {path:?} of type {entry_type:?}");
+            continue;
+        }
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).map_err(CopyError::Io)?;
+        }
+        // Replace rather than write through existing files and links
+        if target.symlink_metadata().is_ok_and(|m| !m.is_dir()) {
+            fs::remove_file(&target).map_err(CopyError::Io)?;
+        }
+
+        let link = entry
+            .link_name()
+            .map_err(CopyError::Io)?
+            .map(|link| link.into_owned());
+        let unsafe_link = |target: PathBuf| CopyError::UnsafeLink {
+            path: path.clone(),
+            target,
+        };
+        if entry_type.is_hard_link() {
+            let link = link.unwrap_or_default();
+            let source = relative_path(&link, base).ok_or_else(|| unsafe_link(link.clone()))?;
+            let source_is_link = local
+                .join(&source)
+                .symlink_metadata()
+                .is_ok_and(|m| m.file_type().is_symlink());
+            // A symbolic link only stays inside where it was checked
+            if source_is_link || passes_through_link(local, &source) {
+                return Err(unsafe_link(link));
+            }
+            fs::hard_link(local.join(source), &target).map_err(CopyError::Io)?;
+        } else {
+            if entry_type.is_symlink() {
+                let link = link.unwrap_or_default();
+                if !link_stays_inside(&relative, &link) {
+                    return Err(unsafe_link(link));
+                }
+            }
+            entry.set_preserve_permissions(true);
+            entry.unpack(&target).map_err(CopyError::Io)?;
+        }
+        progress.copied(&target, if entry_type.is_file() { entry.size() } else { 0 });
+    }
+
+    // Drain the padding after the end of the archive, so tar can exit
+    io::copy(&mut archive.into_inner(), &mut io::sink()).map_err(CopyError::Io)?;
+    #[cfg(unix)]
+    for (dir, mode) in dirs.into_iter().rev() {
+        use std::os::unix::fs::PermissionsExt;
+        if let Some(mode) = mode {
+            fs::set_permissions(dir, fs::Permissions::from_mode(mode)).map_err(CopyError::Io)?;
+        }
+    }
+    #[cfg(not(unix))]
+    drop(dirs);
+    Ok(())
+}
+
+/// The path of an archive entry relative to `base`, if it is `base` or inside it
+fn relative_path(path: &Path, base: &str) -> Option<PathBuf> {
+    let mut components = path.components().skip_while(|c| *c == Component::CurDir);
+    if components.next()? != Component::Normal(base.as_ref()) {
+        return None;
+    }
+    let mut relative = PathBuf::new();
+    for component in components {
+        match component {
+            Component::Normal(name) => relative.push(name),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(relative)
+}
+
+/// Whether a parent directory of `relative` inside `local` is a symbolic link
+fn passes_through_link(local: &Path, relative: &Path) -> bool {
+    let mut dir = local.to_owned();
+    relative
+        .parent()
+        .into_iter()
+        .flat_map(Path::components)
+        .any(|component| {
+            dir.push(component);
+            dir.symlink_metadata().is_ok_and(|m| m.file_type().is_symlink())
+        })
+}
+
+/// Whether a symbolic link at `relative` to `link` resolves inside the copied directory
+fn link_stays_inside(relative: &Path, link: &Path) -> bool {
+    // The number of directories between the copied directory and the link
+    let mut depth = relative.components().count() as isize - 1;
+    if depth < 0 {
+        return false;
+    }
+    for component in link.components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => {}
+            Component::ParentDir => {
+                depth -= 1;
+                if depth < 0 {
+                    return false;
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => return false,
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    #[test]
+    fn remote_paths() {
+        assert_eq!(split_remote("/etc/nginx/").unwrap(), ("/etc", "nginx"));
+        assert_eq!(split_remote("/data").unwrap(), ("/", "data"));
+        assert_eq!(split_remote("logs").unwrap(), (".", "logs"));
+        for invalid in ["/", "", ".", "/tmp/.."] {
+            assert!(
+                matches!(split_remote(invalid), Err(CopyError::InvalidPath(_))),
+                "{invalid}"
+            );
+        }
+
+        let relative = |path: &str| relative_path(Path::new(path), "app");
+        assert_eq!(relative("app"), Some(PathBuf::new()));
+        assert_eq!(relative("./app/a/./b"), Some(PathBuf::from("a/b")));
+        assert_eq!(relative("other/a"), None);
+        assert_eq!(relative("app/../etc/passwd"), None);
+        assert_eq!(relative("/app/a"), None);
+
+        let inside = |at: &str, link: &str| link_stays_inside(Path::new(at), Path::new(link));
+        assert!(inside("a/link", "b"));
+        assert!(inside("a/link", "../b"));
+        assert!(inside("a/b/link", "../../c/./d"));
+        assert!(!inside("a/link", "../../b"));
+        assert!(!inside("link", "../b"));
+        assert!(!inside("a/link", "/etc/passwd"));
+        // A copied link has nothing to point to
+        assert!(!inside("", "target"));
+    }
+
+    #[test]
+    fn archive_roundtrip() {
+        let src = tempfile::tempdir().unwrap();
+        let root = src.path().join("site");
+        fs::create_dir_all(root.join("css")).unwrap();
+        fs::write(root.join("index.html"), "<html>").unwrap();
+        fs::write(root.join("css/main.css"), "body {}").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::{symlink, PermissionsExt};
+            fs::write(root.join("run.sh"), "#!/bin/sh").unwrap();
+            fs::set_permissions(root.join("run.sh"), fs::Permissions::from_mode(0o751)).unwrap();
+            symlink("css/main.css", root.join("style.css")).unwrap();
+        }
+
+        let mut archive = Vec::new();
+        let uploaded = Arc::new(Mutex::new(Vec::new()));
+        let seen = uploaded.clone();
+        let callback: Arc<ProgressFn> = Arc::new(move |p: &CopyProgress<'_>| {
+            seen.lock().unwrap().push((p.entries, p.bytes));
+        });
+        let mut progress = Progress::new(Some(callback));
+        write_archive(&mut archive, &root, "www", &mut progress).unwrap();
+        let uploaded = uploaded.lock().unwrap().clone();
+        assert_eq!(uploaded.first(), Some(&(1, 0)));
+        assert_eq!(uploaded.last().unwrap().1, 6 + 7 + if cfg!(unix) { 9 } else { 0 });
+
+        let dst = tempfile::tempdir().unwrap();
+        let local = dst.path().join("copy");
+        let mut progress = Progress::new(None);
+        unpack_archive(archive.as_slice(), "www", &local, &mut progress).unwrap();
+        assert_eq!(progress.entries, uploaded.len());
+        assert_eq!(fs::read_to_string(local.join("index.html")).unwrap(), "<html>");
+        assert_eq!(fs::read_to_string(local.join("css/main.css")).unwrap(), "body {}");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(local.join("run.sh")).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o751);
+            let link = fs::read_link(local.join("style.css")).unwrap();
+            assert_eq!(link, Path::new("css/main.css"));
+        }
+
+        // A single file is copied to the given path
+        let mut archive = Vec::new();
+        let file = root.join("index.html");
+        write_archive(&mut archive, &file, "index.html", &mut Progress::new(None)).unwrap();
+        let target = dst.path().join("home.html");
+        unpack_archive(
+            archive.as_slice(),
+            "index.html",
+            &target,
+            &mut Progress::new(None),
+        )
+        .unwrap();
+        assert_eq!(fs::read_to_string(target).unwrap(), "<html>");
+    }
+
+    #[test]
+    fn unsafe_archives_are_rejected() {
+        fn archive_of(entries: &[(&str, tar::EntryType, Option<&str>)]) -> Vec<u8> {
+            let mut builder = tar::Builder::new(Vec::new());
+            for (name, entry_type, link) in entries {
+                let mut header = tar::Header::new_gnu();
+                // Set the raw name, as the builder refuses to write `..`
+                let raw = &mut header.as_old_mut().name;
+                raw[..name.len()].copy_from_slice(name.as_bytes());
+                header.set_entry_type(*entry_type);
+                header.set_size(0);
+                header.set_mode(0o644);
+                if let Some(link) = link {
+                    header.set_link_name(link).unwrap();
+                }
+                header.set_cksum();
+                builder.append(&header, io::empty()).unwrap();
+            }
+            builder.into_inner().unwrap()
+        }
+        fn archive(name: &str, entry_type: tar::EntryType, link: Option<&str>) -> Vec<u8> {
+            archive_of(&[(name, entry_type, link)])
+        }
+
+        let dst = tempfile::tempdir().unwrap();
+        let local = dst.path().join("app");
+        let unpack =
+            |archive: Vec<u8>| unpack_archive(archive.as_slice(), "app", &local, &mut Progress::new(None));
+        let regular = tar::EntryType::Regular;
+        assert!(matches!(
+            unpack(archive("app/../../evil", regular, None)),
+            Err(CopyError::UnsafePath(_))
+        ));
+        assert!(matches!(
+            unpack(archive("/etc/passwd", regular, None)),
+            Err(CopyError::UnsafePath(_))
+        ));
+        assert!(matches!(
+            unpack(archive("other", regular, None)),
+            Err(CopyError::UnsafePath(_))
+        ));
+        assert!(matches!(
+            unpack(archive("app/link", tar::EntryType::Symlink, Some("../../etc"))),
+            Err(CopyError::UnsafeLink { .. })
+        ));
+        assert!(matches!(
+            unpack(archive("app/link", tar::EntryType::Symlink, Some("/etc"))),
+            Err(CopyError::UnsafeLink { .. })
+        ));
+        assert!(matches!(
+            unpack(archive("app/link", tar::EntryType::Link, Some("etc/passwd"))),
+            Err(CopyError::UnsafeLink { .. })
+        ));
+        // Each link stays inside on its own, but together they lead outside
+        #[cfg(unix)]
+        {
+            use tar::EntryType::{Link, Symlink};
+            let chained = [
+                ("app/d/l", Symlink, Some("..")),
+                ("app/l2", Symlink, Some("d/l/..")),
+            ];
+            assert!(matches!(
+                unpack(archive_of(&[
+                    chained[0],
+                    chained[1],
+                    ("app/l2/evil", regular, None)
+                ])),
+                Err(CopyError::UnsafePath(_))
+            ));
+            assert!(matches!(
+                unpack(archive_of(&[
+                    chained[0],
+                    chained[1],
+                    ("app/h", Link, Some("app/l2/evil"))
+                ])),
+                Err(CopyError::UnsafeLink { .. })
+            ));
+            assert!(matches!(
+                unpack(archive_of(&[
+                    chained[0],
+                    chained[1],
+                    ("app/h", Link, Some("app/l2"))
+                ])),
+                Err(CopyError::UnsafeLink { .. })
+            ));
+        }
+        assert!(!dst.path().join("evil").exists());
+        assert!(!local.join("link").exists());
+    }
+}
@@ -0,0 +1,554 @@
+//! API helpers for previewing server-side apply, like `kubectl diff`
+//!
+//! [`Api::diff`] is the primary entry point for this API.
+use std::{fmt::Debug, ops::Range};
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+use crate::{
+    api::{Patch, PatchParams},
+    Api, Error, Result,
+};
+use kube_core::Resource;
+
+/// Lines of unchanged context around each hunk of [`Diff::unified`]
+const CONTEXT: usize = 3;
+
+impl<K: Resource + Clone + DeserializeOwned + Serialize + Debug> Api<K> {
+    /// Preview what a server-side apply of `desired` would change, like `kubectl diff`
+    ///
+    /// The apply is performed with [`PatchParams::dry_run`], so nothing is persisted,
+    /// and the result is compared with the live object (if it exists).
+    /// Fields that change on every write or are not part of the desired state (`metadata.managedFields`,
+    /// `metadata.resourceVersion`, `metadata.generation` and `status`) are left out of the comparison.
+    ///
+    /// `pp` must set a field manager, as for any [`Patch::Apply`].
+    ///
+    /// ```no_run
+    /// use kube::api::{Api, PatchParams};
+    /// use k8s_openapi::api::apps::v1::Deployment;
+    /// # async fn wrapper() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client: kube::Client = todo!();
+    /// # let desired: Deployment = todo!();
+    /// let deploys: Api<Deployment> = Api::namespaced(client, "apps");
+    /// let diff = deploys.diff("blog", &PatchParams::apply("cd-pipeline"), &desired).await?;
+    /// if !diff.is_empty() {
+    ///     print!("{}", diff.unified());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn diff(&self, name: &str, pp: &PatchParams, desired: &K) -> Result<Diff> {
+        let live = self.get_opt(name).await?;
+        let merged = self
+            .patch(name, &pp.clone().dry_run(), &Patch::Apply(desired))
+            .await?;
+        let live = live
+            .map(|live| serde_json::to_value(live).map_err(Error::SerdeError))
+            .transpose()?;
+        let merged = serde_json::to_value(merged).map_err(Error::SerdeError)?;
+        Ok(Diff::new(name, live, merged))
+    }
+}
+
+/// The difference between a live object and the result of applying changes to it
+///
+/// Returned by [`Api::diff`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diff {
+    /// The name of the object
+    pub name: String,
+    /// The live object without noisy fields, or `None` if it does not exist yet
+    pub live: Option<Value>,
+    /// The object as it would be after applying, without noisy fields
+    pub merged: Value,
+    /// The changed fields, ordered by path
+    pub changes: Vec<Change>,
+}
+
+/// A field that differs between the live and the merged object
+#[derive(Clone, Debug, PartialEq)]
+pub struct Change {
+    /// The path of the field as a JSON pointer, like `/spec/replicas`
+    pub path: String,
+    /// Whether the field was added, removed or modified
+    pub kind: ChangeKind,
+    /// The live value of the field
+    pub old: Option<Value>,
+    /// The merged value of the field
+    pub new: Option<Value>,
+}
+
+/// What happened to a field in a [`Change`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChangeKind {
+    /// The field only exists in the merged object
+    Added,
+    /// The field only exists in the live object
+    Removed,
+    /// The field has a different value in the merged object
+    Modified,
+}
+
+impl Diff {
+    /// Compare two versions of an object, leaving out the fields ignored by [`Api::diff`]
+    pub fn new(name: impl Into<String>, mut live: Option<Value>, mut merged: Value) -> Self {
+        if let Some(live) = &mut live {
+            strip_noise(live);
+        }
+        strip_noise(&mut merged);
+        let mut changes = Vec::new();
+        compare(&mut String::new(), live.as_ref(), Some(&merged), &mut changes);
+        Self {
+            name: name.into(),
+            live,
+            merged,
+            changes,
+        }
+    }
+
+    /// Whether applying would leave the object unchanged
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Render the difference as a unified diff of the YAML of both versions
+    ///
+    /// The result is empty when nothing changed.
+    pub fn unified(&self) -> String {
+        let to_yaml = |value: &Value| serde_yaml::to_string(value).unwrap_or_default();
+        let live = self.live.as_ref().map(to_yaml).unwrap_or_default();
+        let merged = to_yaml(&self.merged);
+        let hunks = unified_hunks(
+            &live.lines().collect::<Vec<_>>(),
+            &merged.lines().collect::<Vec<_>>(),
+        );
+        if hunks.is_empty() {
+            return hunks;
+        }
+        format!("--- live/{0}\n+++ merged/{0}\n{hunks}", self.name)
+    }
+}
+
+fn strip_noise(object: &mut Value) {
+    let Some(object) = object.as_object_mut() else {
+        return;
+    };
+    object.remove("status");
+    if let Some(metadata) = object.get_mut("metadata").and_then(Value::as_object_mut) {
+        for field in ["managedFields", "resourceVersion", "generation"] {
+            metadata.remove(field);
+        }
+    }
+}
+
+fn compare(path: &mut String, old: Option<&Value>, new: Option<&Value>, changes: &mut Vec<Change>) {
+    let mut push = |kind| {
+        changes.push(Change {
+            path: path.clone(),
+            kind,
+            old: old.cloned(),
+            new: new.cloned(),
+        })
+    };
+    match (old, new) {
+        (None, None) => {}
+        (None, Some(_)) => push(ChangeKind::Added),
+        (Some(_), None) => push(ChangeKind::Removed),
+        (Some(Value::Object(old)), Some(Value::Object(new))) => {
+            let mut keys = old.keys().chain(new.keys()).collect::<Vec<_>>();
+            keys.sort_unstable();
+            keys.dedup();
+            for key in keys {
+                let len = path.len();
+                path.push('/');
+                path.push_str(&key.replace('~', "~0").replace('/', "~1"));
+                compare(path, old.get(key), new.get(key), changes);
+                path.truncate(len);
+            }
+        }
+        (Some(Value::Array(old)), Some(Value::Array(new))) => {
+            for i in 0..old.len().max(new.len()) {
+                let len = path.len();
+                path.push_str(&format!("/{i}"));
+                compare(path, old.get(i), new.get(i), changes);
+                path.truncate(len);
+            }
+        }
+        (Some(old_value), Some(new_value)) => {
+            if old_value != new_value {
+                push(ChangeKind::Modified);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Op {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// Diff lines with Myers' algorithm, in linear space
+///
+/// Sections that would take more than [`MAX_EDIT_COST`] edits to diff are replaced as a whole.
+fn diff_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<(Op, &'a str)> {
+    let mut myers = Myers {
+        old,
+        new,
+        forward: Diagonals::new(old.len() + new.len()),
+        backward: Diagonals::new(old.len() + new.len()),
+        ops: Vec::with_capacity(old.len().max(new.len())),
+    };
+    myers.conquer(0..old.len(), 0..new.len());
+    myers.ops
+}
+
+/// The maximum number of edits to search for when splitting a section of a diff
+///
+/// This bounds the time taken to diff large objects that have little in common.
+const MAX_EDIT_COST: usize = 4096;
+
+struct Myers<'a, 'b> {
+    old: &'b [&'a str],
+    new: &'b [&'a str],
+    forward: Diagonals,
+    backward: Diagonals,
+    ops: Vec<(Op, &'a str)>,
+}
+
+impl<'a> Myers<'a, '_> {
+    fn conquer(&mut self, mut old: Range<usize>, mut new: Range<usize>) {
+        let (old_lines, new_lines) = (self.old, self.new);
+        while !old.is_empty() && !new.is_empty() && old_lines[old.start] == new_lines[new.start] {
+            self.ops.push((Op::Equal, old_lines[old.start]));
+            old.start += 1;
+            new.start += 1;
+        }
+        let mut suffix = 0;
+        while !old.is_empty() && !new.is_empty() && old_lines[old.end - 1] == new_lines[new.end - 1] {
+            old.end -= 1;
+            new.end -= 1;
+            suffix += 1;
+        }
+
+        match self.middle_snake(old.clone(), new.clone()) {
+            Some((x, y)) => {
+                self.conquer(old.start..x, new.start..y);
+                self.conquer(x..old.end, y..new.end);
+            }
+            None => {
+                self.ops
+                    .extend(old_lines[old.clone()].iter().map(|l| (Op::Delete, *l)));
+                self.ops
+                    .extend(new_lines[new.clone()].iter().map(|l| (Op::Insert, *l)));
+            }
+        }
+        self.ops.extend(
+            old_lines[old.end..old.end + suffix]
+                .iter()
+                .map(|l| (Op::Equal, *l)),
+        );
+    }
+
+    /// A point on an optimal edit path that splits the sections into two smaller ones
+    ///
+    /// Returns `None` when either section is empty, or the edit cost exceeds [`MAX_EDIT_COST`].
+    fn middle_snake(&mut self, old: Range<usize>, new: Range<usize>) -> Option<(usize, usize)> {
+        if old.is_empty() || new.is_empty() {
+            return None;
+        }
+        let (n, m) = (old.len(), new.len());
+        let delta = n as isize - m as isize;
+        let odd = delta % 2 != 0;
+        let common = |x: usize, y: usize| self.old[old.start + x] == self.new[new.start + y];
+        let (forward, backward) = (&mut self.forward, &mut self.backward);
+        forward.set(1, 0);
+        backward.set(1, 0);
+
+        let max_cost = (n + m).div_ceil(2).min(MAX_EDIT_COST) as isize;
+        for d in 0..=max_cost {
+            for k in (-d..=d).step_by(2) {
+                let mut x = if k == -d || (k != d && forward.get(k - 1) < forward.get(k + 1)) {
+                    forward.get(k + 1)
+                } else {
+                    forward.get(k - 1) + 1
+                };
+                let (x0, y0) = (x, (x as isize - k) as usize);
+                let mut y = y0;
+                while x < n && y < m && common(x, y) {
+                    x += 1;
+                    y += 1;
+                }
+                forward.set(k, x);
+                if odd && (k - delta).abs() < d && x + backward.get(delta - k) >= n {
+                    return Some((old.start + x0, new.start + y0));
+                }
+            }
+            // The backward search runs on the reversed sections, where its diagonal `k` is `delta - k` forwards
+            for k in (-d..=d).step_by(2) {
+                let mut x = if k == -d || (k != d && backward.get(k - 1) < backward.get(k + 1)) {
+                    backward.get(k + 1)
+                } else {
+                    backward.get(k - 1) + 1
+                };
+                let mut y = (x as isize - k) as usize;
+                while x < n && y < m && common(n - x - 1, m - y - 1) {
+                    x += 1;
+                    y += 1;
+                }
+                backward.set(k, x);
+                if !odd && (k - delta).abs() <= d && x + forward.get(delta - k) >= n {
+                    return Some((old.start + n - x, new.start + m - y));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// The furthest reaching x on each diagonal `k` of the edit graph, for `k` in `-max..=max`
+struct Diagonals {
+    offset: isize,
+    x: Vec<usize>,
+}
+
+impl Diagonals {
+    fn new(lines: usize) -> Self {
+        let max = lines.div_ceil(2).min(MAX_EDIT_COST) + 1;
+        Self {
+            offset: max as isize,
+            x: vec![0; 2 * max + 1],
+        }
+    }
+
+    fn get(&self, k: isize) -> usize {
+        self.x[(k + self.offset) as usize]
+    }
+
+    fn set(&mut self, k: isize, x: usize) {
+        self.x[(k + self.offset) as usize] = x;
+    }
+}
+
+fn unified_hunks(old: &[&str], new: &[&str]) -> String {
+    let ops = diff_lines(old, new);
+    // The number of old and new lines before each op
+    let mut positions = vec![(0, 0)];
+    for (op, _) in &ops {
+        let (o, n) = positions[positions.len() - 1];
+        positions.push(match op {
+            Op::Equal => (o + 1, n + 1),
+            Op::Delete => (o + 1, n),
+            Op::Insert => (o, n + 1),
+        });
+    }
+    let next_change = |from: usize| (from..ops.len()).find(|&k| ops[k].0 != Op::Equal);
+
+    let mut out = String::new();
+    let mut done = 0;
+    while let Some(first) = next_change(done) {
+        let start = first.saturating_sub(CONTEXT).max(done);
+        // Extend the hunk over changes separated by little enough context to overlap
+        let mut end = first;
+        loop {
+            while end < ops.len() && ops[end].0 != Op::Equal {
+                end += 1;
+            }
+            match next_change(end) {
+                Some(next) if next - end <= 2 * CONTEXT => end = next,
+                _ => break,
+            }
+        }
+        let stop = (end + CONTEXT).min(ops.len());
+        let range = |from: usize, to: usize| {
+            let len = to - from;
+            // Empty ranges refer to the line before them
+            format!("{},{len}", if len == 0 { from } else { from + 1 })
+        };
+        out.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            range(positions[start].0, positions[stop].0),
+            range(positions[start].1, positions[stop].1)
+        ));
+        for (op, line) in &ops[start..stop] {
+            let prefix = match op {
+                Op::Equal => ' ',
+                Op::Delete => '-',
+                Op::Insert => '+',
+            };
+            out.push(prefix);
+            out.push_str(line);
+            out.push('\n');
+        }
+        done = stop;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::pin::pin;
+
+    use http::{Request, Response};
+    use k8s_openapi::api::core::v1::ConfigMap;
+    use serde_json::json;
+    use tower_test::mock;
+
+    use super::*;
+    use crate::{client::Body, Client};
+
+    fn configmap(data: Value, resource_version: &str) -> Value {
+        json!({
+            "apiVersion": "v1",
+            "kind": "ConfigMap",
+            "metadata": {
+                "name": "cm",
+                "namespace": "default",
+                "resourceVersion": resource_version,
+                "managedFields": [{ "manager": "kubectl", "operation": "Apply" }],
+            },
+            "data": data,
+        })
+    }
+
+    #[test]
+    fn diff_changes_and_unified() {
+        let live = configmap(json!({ "a": "1", "b": "2", "c/d": "3" }), "1");
+        let merged = configmap(json!({ "a": "1", "b": "20", "e": "5" }), "2");
+        let diff = Diff::new("cm", Some(live), merged);
+        let changes = diff
+            .changes
+            .iter()
+            .map(|c| (c.path.as_str(), c.kind))
+            .collect::<Vec<_>>();
+        assert_eq!(changes, [
+            ("/data/b", ChangeKind::Modified),
+            ("/data/c~1d", ChangeKind::Removed),
+            ("/data/e", ChangeKind::Added),
+        ]);
+        assert_eq!(diff.changes[0].old, Some(json!("2")));
+        assert_eq!(diff.changes[0].new, Some(json!("20")));
+        assert_eq!(
+            diff.unified(),
+            [
+                "--- live/cm",
+                "+++ merged/cm",
+                "@@ -1,8 +1,8 @@",
+                " apiVersion: v1",
+                " data:",
+                "   a: '1'",
+                "-  b: '2'",
+                "-  c/d: '3'",
+                "+  b: '20'",
+                "+  e: '5'",
+                " kind: ConfigMap",
+                " metadata:",
+                "   name: cm",
+                "",
+            ]
+            .join("\n")
+        );
+
+        let unchanged = Diff::new("cm", Some(configmap(json!({}), "1")), configmap(json!({}), "2"));
+        assert!(unchanged.is_empty());
+        assert_eq!(unchanged.unified(), "");
+
+        let created = Diff::new("cm", None, configmap(json!({ "a": "1" }), "1"));
+        assert_eq!(created.changes.len(), 1);
+        assert_eq!(created.changes[0].path, "");
+        assert!(created.unified().contains("@@ -0,0 +1,7 @@\n+apiVersion: v1\n"));
+    }
+
+    #[test]
+    fn unified_hunks_merge_close_changes() {
+        let old = (1..=20).map(|i| i.to_string()).collect::<Vec<_>>();
+        let mut new = old.clone();
+        new[1] = "two".into();
+        new[17] = "eighteen".into();
+        new.remove(11);
+        let old = old.iter().map(String::as_str).collect::<Vec<_>>();
+        let new = new.iter().map(String::as_str).collect::<Vec<_>>();
+        assert_eq!(
+            unified_hunks(&old, &new),
+            [
+                "@@ -1,5 +1,5 @@",
+                " 1",
+                "-2",
+                "+two",
+                " 3",
+                " 4",
+                " 5",
+                "@@ -9,12 +9,11 @@",
+                " 9",
+                " 10",
+                " 11",
+                "-12",
+                " 13",
+                " 14",
+                " 15",
+                " 16",
+                " 17",
+                "-18",
+                "+eighteen",
+                " 19",
+                " 20",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn diff_lines_of_large_objects() {
+        fn check(old: &[String], new: &[String]) -> usize {
+            let old = old.iter().map(String::as_str).collect::<Vec<_>>();
+            let new = new.iter().map(String::as_str).collect::<Vec<_>>();
+            let ops = diff_lines(&old, &new);
+            let from = |skip| ops.iter().filter(move |(op, _)| *op != skip).map(|(_, l)| *l);
+            assert!(from(Op::Insert).eq(old.iter().copied()));
+            assert!(from(Op::Delete).eq(new.iter().copied()));
+            ops.iter().filter(|(op, _)| *op != Op::Equal).count()
+        }
+
+        let old = (0..50_000).map(|i| i.to_string()).collect::<Vec<_>>();
+        let mut new = old.clone();
+        for i in (0..new.len()).step_by(100) {
+            new[i] = format!("changed {i}");
+        }
+        // Including one of the changed lines
+        new.drain(1000..1010);
+        assert_eq!(check(&old, &new), 499 + 500 + 9);
+
+        // Nothing in common, too costly to diff line by line
+        let new = (0..50_000).map(|i| format!("new {i}")).collect::<Vec<_>>();
+        assert_eq!(check(&old, &new), 100_000);
+    }
+
+    #[tokio::test]
+    async fn diff_dry_runs_apply() {
+        let (mock_service, handle) = mock::pair::<Request<Body>, Response<Body>>();
+        let spawned = tokio::spawn(async move {
+            let mut handle = pin!(handle);
+            let (request, send) = handle.next_request().await.expect("service not called");
+            assert_eq!(request.method(), http::Method::GET);
+            let live = configmap(json!({ "a": "1" }), "1");
+            send.send_response(Response::new(Body::from(live.to_string().into_bytes())));
+
+            let (request, send) = handle.next_request().await.expect("service not called");
+            assert_eq!(request.method(), http::Method::PATCH);
+            assert_eq!(request.uri().query(), Some("&dryRun=All&fieldManager=cd"));
+            let merged = configmap(json!({ "a": "2" }), "2");
+            send.send_response(Response::new(Body::from(merged.to_string().into_bytes())));
+        });
+
+        let cms: Api<ConfigMap> = Api::default_namespaced(Client::new(mock_service, "default"));
+        let desired = serde_json::from_value(configmap(json!({ "a": "2" }), "")).unwrap();
+        let diff = cms.diff("cm", &PatchParams::apply("cd"), &desired).await.unwrap();
+        assert_eq!(diff.changes.len(), 1);
+        assert_eq!(diff.changes[0].path, "/data/a");
+        spawned.await.unwrap();
+    }
+}
@@ -0,0 +1,447 @@
+//! Running commands to completion and collecting their output
+use std::{fmt::Debug, time::Duration};
+
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::Status;
+use serde::de::DeserializeOwned;
+use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+
+use super::{remote_command, Api, AttachParams, Execute};
+
+/// The number of bytes of stdout or stderr kept by default
+pub const DEFAULT_OUTPUT_LIMIT: usize = 1024 * 1024;
+
+/// Parameters for [`Api::exec_output`]
+#[derive(Clone, Debug)]
+pub struct ExecParams {
+    /// The container to run the command in, if the pod has more than one
+    pub container: Option<String>,
+    /// Data written to the stdin of the command, which is closed afterwards
+    pub stdin: Option<Vec<u8>>,
+    /// The maximum number of bytes of stdout to keep
+    pub max_stdout: usize,
+    /// The maximum number of bytes of stderr to keep
+    pub max_stderr: usize,
+    /// How long the command may run before it is abandoned
+    pub timeout: Option<Duration>,
+}
+
+impl Default for ExecParams {
+    fn default() -> Self {
+        Self {
+            container: None,
+            stdin: None,
+            max_stdout: DEFAULT_OUTPUT_LIMIT,
+            max_stderr: DEFAULT_OUTPUT_LIMIT,
+            timeout: None,
+        }
+    }
+}
+
+impl ExecParams {
+    /// Run the command in the given container
+    #[must_use]
+    pub fn container<T: Into<String>>(mut self, container: T) -> Self {
+        self.container = Some(container.into());
+        self
+    }
+
+    /// Write data to the stdin of the command
+    ///
+    /// The servers that support version 5 of the streaming protocol close stdin once the data is written.
+    /// Older servers have the connection closed instead, which can lose output written after that.
+    #[must_use]
+    pub fn stdin<T: Into<Vec<u8>>>(mut self, stdin: T) -> Self {
+        self.stdin = Some(stdin.into());
+        self
+    }
+
+    /// Keep at most `max` bytes of stdout, discarding the rest
+    #[must_use]
+    pub fn max_stdout(mut self, max: usize) -> Self {
+        self.max_stdout = max;
+        self
+    }
+
+    /// Keep at most `max` bytes of stderr, discarding the rest
+    #[must_use]
+    pub fn max_stderr(mut self, max: usize) -> Self {
+        self.max_stderr = max;
+        self
+    }
+
+    /// Abandon the command if it has not completed within `timeout`
+    ///
+    /// Only the connection is closed; the command may keep running in the container.
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+
+/// The output of a command run by [`Api::exec_output`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ExecOutput {
+    /// The stdout of the command, up to [`ExecParams::max_stdout`] bytes
+    pub stdout: Vec<u8>,
+    /// The stderr of the command, up to [`ExecParams::max_stderr`] bytes
+    pub stderr: Vec<u8>,
+    /// The exit code of the command
+    pub exit_code: i32,
+    /// Whether stdout was longer than [`ExecParams::max_stdout`]
+    pub stdout_truncated: bool,
+    /// Whether stderr was longer than [`ExecParams::max_stderr`]
+    pub stderr_truncated: bool,
+}
+
+impl ExecOutput {
+    /// The stdout of the command as a string, replacing invalid UTF-8
+    pub fn stdout_lossy(&self) -> std::borrow::Cow<'_, str> {
+        String::from_utf8_lossy(&self.stdout)
+    }
+
+    /// The stderr of the command as a string, replacing invalid UTF-8
+    pub fn stderr_lossy(&self) -> std::borrow::Cow<'_, str> {
+        String::from_utf8_lossy(&self.stderr)
+    }
+}
+
+/// Errors from [`Api::exec_output`]
+#[derive(Debug, Error)]
+pub enum ExecError {
+    /// The command ran and exited with a non-zero exit code
+    #[error("command exited with code {}", .0.exit_code)]
+    NonZeroExit(Box<ExecOutput>),
+
+    /// The command could not be run, for example because the executable was not found
+    #[error("command failed: {}", .0.message.as_deref().unwrap_or_default())]
+    Failed(Box<Status>),
+
+    /// The command did not complete within [`ExecParams::timeout`]
+    #[error("command did not complete within {0:?}")]
+    Timeout(Duration),
+
+    /// Failed to connect to the pod
+    #[error("failed to connect: {0}")]
+    Connect(#[source] crate::Error),
+
+    /// The connection failed while the command was running
+    #[error("connection failed: {0}")]
+    Stream(#[source] Box<remote_command::Error>),
+
+    /// Reading stdout or stderr of the command failed partway
+    #[error("failed to read output: {0}")]
+    Output(#[source] std::io::Error),
+
+    /// The connection closed without reporting how the command exited
+    #[error("connection closed without an exit status")]
+    MissingStatus,
+}
+
+impl<K> Api<K>
+where
+    K: Clone + DeserializeOwned + Execute,
+{
+    /// Run a command in a pod to completion, collecting its output and exit code
+    ///
+    /// A command that exits with a non-zero exit code fails with [`ExecError::NonZeroExit`], which
+    /// still carries its output. Output beyond the limits of the [`ExecParams`] is discarded rather than buffered.
+    ///
+    /// ```no_run
+    /// use kube::api::{Api, ExecParams};
+    /// use k8s_openapi::api::core::v1::Pod;
+    /// use std::time::Duration;
+    ///
+    /// # async fn wrapper() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client: kube::Client = todo!();
+    /// let pods: Api<Pod> = Api::default_namespaced(client);
+    /// let ep = ExecParams::default()
+    ///     .stdin("hello")
+    ///     .timeout(Duration::from_secs(10));
+    /// let output = pods.exec_output("web", vec!["wc", "-c"], &ep).await?;
+    /// assert_eq!(output.stdout_lossy().trim(), "5");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn exec_output<I, T>(
+        &self,
+        name: &str,
+        command: I,
+        ep: &ExecParams,
+    ) -> Result<ExecOutput, ExecError>
+    where
+        I: IntoIterator<Item = T> + Debug,
+        T: Into<String>,
+    {
+        let mut ap = AttachParams::default().stdin(ep.stdin.is_some());
+        if let Some(container) = &ep.container {
+            ap = ap.container(container);
+        }
+        let mut attached = self.exec(name, command, &ap).await.map_err(ExecError::Connect)?;
+
+        let stdin = attached.stdin();
+        let stdout = attached.stdout();
+        let stderr = attached.stderr();
+        let status = attached.take_status();
+        let run = async {
+            let write = async {
+                if let (Some(mut writer), Some(data)) = (stdin, &ep.stdin) {
+                    // The command may exit without reading all of its input
+                    if let Err(err) = writer.write_all(data).await {
+                        tracing::debug!("failed to write stdin: {err}");
+                    }
+                    let _ = writer.shutdown().await;
+                }
+            };
+            let ((), stdout, stderr) = tokio::join!(
+                write,
+                read_capped(stdout, ep.max_stdout),
+                read_capped(stderr, ep.max_stderr)
+            );
+            let status = match status {
+                Some(status) => status.await,
+                None => None,
+            };
+            (stdout, stderr, status)
+        };
+        let (stdout, stderr, status) = match ep.timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, run).await {
+                Ok(result) => result,
+                Err(_) => {
+                    attached.abort();
+                    return Err(ExecError::Timeout(timeout));
+                }
+            },
+            None => run.await,
+        };
+        attached
+            .join()
+            .await
+            .map_err(|err| ExecError::Stream(Box::new(err)))?;
+        let (stdout, stdout_truncated) = stdout.map_err(ExecError::Output)?;
+        let (stderr, stderr_truncated) = stderr.map_err(ExecError::Output)?;
+
+        let output = ExecOutput {
+            stdout,
+            stderr,
+            exit_code: 0,
+            stdout_truncated,
+            stderr_truncated,
+        };
+        check_status(status.ok_or(ExecError::MissingStatus)?, output)
+    }
+}
+
+/// Complete the output of a command with the exit code of its status, failing unless it is zero
+pub(super) fn check_status(status: Status, mut output: ExecOutput) -> Result<ExecOutput, ExecError> {
+    output.exit_code = exit_code(&status).ok_or_else(|| ExecError::Failed(Box::new(status)))?;
+    if output.exit_code == 0 {
+        Ok(output)
+    } else {
+        Err(ExecError::NonZeroExit(Box::new(output)))
+    }
+}
+
+/// Read until the end, keeping the first `max` bytes and whether there were more
+pub(super) async fn read_capped(
+    reader: Option<impl AsyncRead + Unpin>,
+    max: usize,
+) -> std::io::Result<(Vec<u8>, bool)> {
+    let (mut kept, mut truncated) = (Vec::new(), false);
+    let Some(mut reader) = reader else {
+        return Ok((kept, truncated));
+    };
+    let mut buf = vec![0; 8 * 1024];
+    loop {
+        let read = reader.read(&mut buf).await?;
+        if read == 0 {
+            break;
+        }
+        let keep = read.min(max - kept.len());
+        kept.extend_from_slice(&buf[..keep]);
+        truncated |= keep < read;
+    }
+    Ok((kept, truncated))
+}
+
+/// The exit code reported by the status of an exec, or `None` if the command did not run
+fn exit_code(status: &Status) -> Option<i32> {
+    if status.status.as_deref() == Some("Success") {
+        return Some(0);
+    }
+    if status.reason.as_deref() != Some("NonZeroExitCode") {
+        return None;
+    }
+    status
+        .details
+        .as_ref()?
+        .causes
+        .as_ref()?
+        .iter()
+        .find(|cause| cause.reason.as_deref() == Some("ExitCode"))?
+        .message
+        .as_ref()?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn exit_code_from_status() {
+        let status = |value| serde_json::from_value::<Status>(value).unwrap();
+        assert_eq!(
+            exit_code(&status(json!({ "metadata": {}, "status": "Success" }))),
+            Some(0)
+        );
+        assert_eq!(
+            exit_code(&status(json!({
+                "metadata": {},
+                "status": "Failure",
+                "message": "command terminated with non-zero exit code: error executing command [sh -c exit 3], exit code 3",
+                "reason": "NonZeroExitCode",
+                "details": { "causes": [{ "reason": "ExitCode", "message": "3" }] }
+            }))),
+            Some(3)
+        );
+        assert_eq!(
+            exit_code(&status(json!({
+                "metadata": {},
+                "status": "Failure",
+                "message": "exec: \"nope\": executable file not found in $PATH",
+                "reason": "InternalError",
+                "details": { "causes": [{ "message": "exec: \"nope\": executable file not found in $PATH" }] }
+            }))),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn read_capped_output() {
+        let reader = tokio_test::io::Builder::new()
+            .read(b"hello ")
+            .read(b"world")
+            .build();
+        assert_eq!(
+            read_capped(Some(reader), 8).await.unwrap(),
+            (b"hello wo".to_vec(), true)
+        );
+
+        let reader = tokio_test::io::Builder::new()
+            .read(b"partial")
+            .read_error(std::io::Error::other("connection reset"))
+            .build();
+        let err = read_capped(Some(reader), 1024).await.unwrap_err();
+        assert_eq!(err.to_string(), "connection reset");
+    }
+
+    #[cfg(feature = "cassette")]
+    mod replay {
+        use k8s_openapi::api::core::v1::Pod;
+
+        use super::*;
+        use crate::{
+            client::cassette::{
+                Cassette, Data, Interaction, Message, Opcode, Peer, RecordedRequest, RecordedResponse, Replay,
+            },
+            Client,
+        };
+
+        fn pods(uri: &str, messages: &[(Peer, &str)]) -> Api<Pod> {
+            let headers = [
+                ("connection", "Upgrade"),
+                ("upgrade", "websocket"),
+                ("sec-websocket-protocol", "v4.channel.k8s.io"),
+            ]
+            .into_iter()
+            .map(|(k, v)| (k.to_owned(), vec![v.to_owned()]))
+            .collect();
+            let messages = messages
+                .iter()
+                .map(|&(from, data)| Message {
+                    from,
+                    opcode: Opcode::Binary,
+                    fin: true,
+                    data: Data::Text(data.into()),
+                })
+                .collect();
+            let cassette = Cassette {
+                interactions: vec![Interaction {
+                    request: RecordedRequest {
+                        method: "GET".into(),
+                        uri: uri.into(),
+                        headers: Default::default(),
+                        body: None,
+                    },
+                    response: RecordedResponse {
+                        status: 101,
+                        headers,
+                        body: vec![],
+                    },
+                    messages,
+                }],
+            };
+            Api::default_namespaced(Client::new(Replay::new(cassette), "default"))
+        }
+
+        #[tokio::test]
+        async fn exec_output_collects_output() {
+            let failure = json!({
+                "metadata": {},
+                "status": "Failure",
+                "reason": "NonZeroExitCode",
+                "details": { "causes": [{ "reason": "ExitCode", "message": "2" }] }
+            });
+            let pods = pods(
+                "/api/v1/namespaces/default/pods/web/exec?stdout=true&stderr=true&container=app&command=ls",
+                &[
+                    (Peer::Server, "\u{1}"),
+                    (Peer::Server, "\u{1}hello "),
+                    (Peer::Server, "\u{2}oops"),
+                    (Peer::Server, "\u{1}world"),
+                    (Peer::Server, &format!("\u{3}{failure}")),
+                ],
+            );
+            let ep = ExecParams::default().container("app").max_stdout(8);
+            let err = pods.exec_output("web", ["ls"], &ep).await.unwrap_err();
+            let ExecError::NonZeroExit(output) = err else {
+                panic!("unexpected error {err:?}");
+            };
+            assert_eq!(*output, ExecOutput {
+                stdout: b"hello wo".to_vec(),
+                stderr: b"oops".to_vec(),
+                exit_code: 2,
+                stdout_truncated: true,
+                stderr_truncated: false,
+            });
+        }
+
+        #[tokio::test]
+        async fn exec_output_writes_stdin() {
+            let success = json!({ "metadata": {}, "status": "Success" });
+            let uri =
+                "/api/v1/namespaces/default/pods/web/exec?stdin=true&stdout=true&stderr=true&command=cat";
+            let pods = pods(uri, &[
+                (Peer::Client, "\u{0}hi"),
+                (Peer::Server, "\u{1}hi"),
+                (Peer::Server, &format!("\u{3}{success}")),
+            ]);
+            let ep = ExecParams::default().stdin("hi");
+            let output = pods.exec_output("web", ["cat"], &ep).await.unwrap();
+            assert_eq!(output.stdout_lossy(), "hi");
+            assert_eq!(output.exit_code, 0);
+
+            // Without a status the command never completes
+            let uri = "/api/v1/namespaces/default/pods/web/exec?stdout=true&stderr=true&command=sleep";
+            let pods = self::pods(uri, &[(Peer::Server, "\u{1}")]);
+            let ep = ExecParams::default().timeout(Duration::from_millis(50));
+            let err = pods.exec_output("web", ["sleep"], &ep).await.unwrap_err();
+            assert!(matches!(err, ExecError::Timeout(_)), "{err:?}");
+        }
+    }
+}
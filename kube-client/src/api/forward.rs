@@ -0,0 +1,573 @@
+//! Forwarding local ports to pods, and to services and deployments through one of their pods
+use std::{
+    fmt,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+
+use k8s_openapi::{
+    api::{
+        apps::v1::Deployment,
+        core::v1::{Pod, Service},
+    },
+    apimachinery::pkg::util::intstr::IntOrString,
+};
+use kube_core::{params::ListParams, ParseExpressionError, Selector};
+use thiserror::Error;
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::Mutex,
+    task::JoinHandle,
+};
+
+use super::{portforward, Api};
+
+/// Errors from [`Api::forward_ports`]
+#[derive(Debug, Error)]
+pub enum ForwardError {
+    /// The target is not of the form `[pod/|service/|deployment/]name`
+    #[error("invalid port-forward target {0:?}")]
+    InvalidTarget(String),
+
+    /// Failed to bind a local port
+    #[error("failed to bind {addr}: {source}")]
+    Bind {
+        /// The address that could not be bound
+        addr: SocketAddr,
+        /// The error from binding it
+        #[source]
+        source: std::io::Error,
+    },
+
+    /// Failed to get the target or its pods
+    #[error("failed to resolve the target: {0}")]
+    Resolve(#[source] crate::Error),
+
+    /// The selector of a deployment is invalid
+    #[error("invalid selector: {0}")]
+    Selector(#[source] ParseExpressionError),
+
+    /// The target selects no pods
+    #[error("{0} has no selector")]
+    NoSelector(ForwardTarget),
+
+    /// None of the pods of the target are ready
+    #[error("{0} has no ready pods")]
+    NoReadyPod(ForwardTarget),
+
+    /// The port is not exposed by a service, or a named target port is not exposed by its pod
+    #[error("{target} does not expose port {port}")]
+    UnknownPort {
+        /// The target of the forward
+        target: ForwardTarget,
+        /// The port that is not exposed
+        port: String,
+    },
+
+    /// Failed to open the port-forward connection
+    #[error("failed to connect: {0}")]
+    Connect(#[source] crate::Error),
+
+    /// The port-forward connection failed
+    #[error("port-forward failed: {0}")]
+    Forward(#[source] Box<portforward::Error>),
+}
+
+/// What to forward ports to
+///
+/// Services and deployments are forwarded to one of their ready pods, like `kubectl port-forward`.
+/// They can be parsed from the same `svc/name` and `deploy/name` forms.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ForwardTarget {
+    /// A pod
+    Pod(String),
+    /// A ready pod selected by a service, with ports of the service mapped to its target ports
+    Service(String),
+    /// A ready pod selected by a deployment
+    Deployment(String),
+}
+
+impl ForwardTarget {
+    /// Forward to the pod with the given name
+    pub fn pod(name: impl Into<String>) -> Self {
+        Self::Pod(name.into())
+    }
+
+    /// Forward to a pod of the service with the given name
+    pub fn service(name: impl Into<String>) -> Self {
+        Self::Service(name.into())
+    }
+
+    /// Forward to a pod of the deployment with the given name
+    pub fn deployment(name: impl Into<String>) -> Self {
+        Self::Deployment(name.into())
+    }
+}
+
+impl fmt::Display for ForwardTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Pod(name) => write!(f, "pod/{name}"),
+            Self::Service(name) => write!(f, "service/{name}"),
+            Self::Deployment(name) => write!(f, "deployment/{name}"),
+        }
+    }
+}
+
+impl FromStr for ForwardTarget {
+    type Err = ForwardError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, name) = s.split_once('/').unwrap_or(("pod", s));
+        if name.is_empty() || name.contains('/') {
+            return Err(ForwardError::InvalidTarget(s.to_owned()));
+        }
+        match kind {
+            "po" | "pod" | "pods" => Ok(Self::pod(name)),
+            "svc" | "service" | "services" => Ok(Self::service(name)),
+            "deploy" | "deployment" | "deployments" => Ok(Self::deployment(name)),
+            _ => Err(ForwardError::InvalidTarget(s.to_owned())),
+        }
+    }
+}
+
+/// Parameters for [`Api::forward_ports`]
+#[derive(Clone, Debug)]
+pub struct ForwardParams {
+    /// The local address to listen on, which is `127.0.0.1` by default
+    pub address: IpAddr,
+}
+
+impl Default for ForwardParams {
+    fn default() -> Self {
+        Self {
+            address: Ipv4Addr::LOCALHOST.into(),
+        }
+    }
+}
+
+impl ForwardParams {
+    /// Listen on the given local address
+    #[must_use]
+    pub fn address(mut self, address: impl Into<IpAddr>) -> Self {
+        self.address = address.into();
+        self
+    }
+}
+
+/// Local ports forwarded by [`Api::forward_ports`]
+///
+/// The ports are closed when this is dropped. Connections that were already accepted are forwarded until they close.
+pub struct PortForwarding {
+    addrs: Vec<(SocketAddr, u16)>,
+    listeners: Vec<JoinHandle<()>>,
+}
+
+impl PortForwarding {
+    /// The local address forwarded to a port of the target
+    ///
+    /// This is how to find the port that was picked when binding local port `0`.
+    pub fn local_addr(&self, port: u16) -> Option<SocketAddr> {
+        self.addrs
+            .iter()
+            .find_map(|&(addr, remote)| (remote == port).then_some(addr))
+    }
+
+    /// The local addresses with the ports of the target they are forwarded to
+    pub fn local_addrs(&self) -> &[(SocketAddr, u16)] {
+        &self.addrs
+    }
+
+    /// Stop listening on the local ports
+    pub fn stop(self) {}
+}
+
+impl Drop for PortForwarding {
+    fn drop(&mut self) {
+        for listener in &self.listeners {
+            listener.abort();
+        }
+    }
+}
+
+impl Api<Pod> {
+    /// Listen on local ports and forward every connection to a pod, service or deployment
+    ///
+    /// `ports` maps local ports to ports of the target, where local port `0` picks a free port.
+    /// Every accepted connection gets its own port-forward connection. Services and deployments are
+    /// forwarded to one of their ready pods, which is looked up again when connecting to it fails,
+    /// so forwarding continues after the pod is replaced.
+    ///
+    /// ```no_run
+    /// use kube::api::{Api, ForwardParams, ForwardTarget};
+    /// use k8s_openapi::api::core::v1::Pod;
+    ///
+    /// # async fn wrapper() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client: kube::Client = todo!();
+    /// let pods: Api<Pod> = Api::default_namespaced(client);
+    /// // Like `kubectl port-forward svc/web 8080:80`
+    /// let target = "svc/web".parse::<ForwardTarget>()?;
+    /// let forwarding = pods.forward_ports(target, &[(8080, 80)], &ForwardParams::default()).await?;
+    /// println!("forwarding {:?}", forwarding.local_addr(80));
+    /// tokio::signal::ctrl_c().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn forward_ports(
+        &self,
+        target: ForwardTarget,
+        ports: &[(u16, u16)],
+        fp: &ForwardParams,
+    ) -> Result<PortForwarding, ForwardError> {
+        let resolver = Arc::new(Resolver::new(self.clone(), target));
+        // Fail early when the target cannot be forwarded to
+        let backend = resolver.resolve(false).await?;
+        for &(_, port) in ports {
+            backend.pod_port(&resolver.target, port)?;
+        }
+
+        let mut forwarding = PortForwarding {
+            addrs: Vec::with_capacity(ports.len()),
+            listeners: Vec::with_capacity(ports.len()),
+        };
+        for &(local, port) in ports {
+            let addr = SocketAddr::new(fp.address, local);
+            let bind_error = |source| ForwardError::Bind { addr, source };
+            let listener = TcpListener::bind(addr).await.map_err(bind_error)?;
+            let addr = listener.local_addr().map_err(bind_error)?;
+            tracing::debug!(%addr, "forwarding to port {port} of {}", resolver.target);
+            forwarding.addrs.push((addr, port));
+            forwarding
+                .listeners
+                .push(tokio::spawn(accept_loop(listener, port, resolver.clone())));
+        }
+        Ok(forwarding)
+    }
+}
+
+/// The pod that connections are forwarded to
+struct Backend {
+    pod: Pod,
+    service: Option<Service>,
+}
+
+impl Backend {
+    fn name(&self) -> &str {
+        self.pod.metadata.name.as_deref().unwrap_or_default()
+    }
+
+    /// The port of the pod that a port of the target maps to
+    fn pod_port(&self, target: &ForwardTarget, port: u16) -> Result<u16, ForwardError> {
+        let unknown = |port: String| ForwardError::UnknownPort {
+            target: target.clone(),
+            port,
+        };
+        let Some(service) = &self.service else {
+            return Ok(port);
+        };
+        let service_port = service
+            .spec
+            .iter()
+            .flat_map(|spec| spec.ports.iter().flatten())
+            .find(|p| p.port == i32::from(port))
+            .ok_or_else(|| unknown(port.to_string()))?;
+        match &service_port.target_port {
+            None => Ok(port),
+            Some(IntOrString::Int(target_port)) => {
+                u16::try_from(*target_port).map_err(|_| unknown(target_port.to_string()))
+            }
+            Some(IntOrString::String(name)) => self
+                .pod
+                .spec
+                .iter()
+                .flat_map(|spec| &spec.containers)
+                .flat_map(|container| container.ports.iter().flatten())
+                .find(|p| p.name.as_deref() == Some(name))
+                .and_then(|p| u16::try_from(p.container_port).ok())
+                .ok_or_else(|| unknown(name.clone())),
+        }
+    }
+}
+
+/// Resolves the target to a pod, caching it until connecting to it fails
+struct Resolver {
+    api: Api<Pod>,
+    target: ForwardTarget,
+    backend: Mutex<Option<Arc<Backend>>>,
+}
+
+impl Resolver {
+    fn new(api: Api<Pod>, target: ForwardTarget) -> Self {
+        Self {
+            api,
+            target,
+            backend: Mutex::new(None),
+        }
+    }
+
+    async fn resolve(&self, refresh: bool) -> Result<Arc<Backend>, ForwardError> {
+        let mut cached = self.backend.lock().await;
+        if let Some(backend) = cached.as_ref().filter(|_| !refresh) {
+            return Ok(backend.clone());
+        }
+        let backend = Arc::new(self.lookup().await?);
+        tracing::debug!("forwarding {} to pod {}", self.target, backend.name());
+        *cached = Some(backend.clone());
+        Ok(backend)
+    }
+
+    async fn lookup(&self) -> Result<Backend, ForwardError> {
+        let client = self.api.client.clone();
+        let ns = self
+            .api
+            .namespace()
+            .unwrap_or(client.default_namespace())
+            .to_owned();
+        let (selector, service) = match &self.target {
+            ForwardTarget::Pod(name) => {
+                let pod = self.api.get(name).await.map_err(ForwardError::Resolve)?;
+                return Ok(Backend { pod, service: None });
+            }
+            ForwardTarget::Service(name) => {
+                let services: Api<Service> = Api::namespaced(client, &ns);
+                let service = services.get(name).await.map_err(ForwardError::Resolve)?;
+                let selector = service
+                    .spec
+                    .as_ref()
+                    .and_then(|spec| spec.selector.clone())
+                    .unwrap_or_default()
+                    .into_iter()
+                    .collect::<Selector>();
+                (selector, Some(service))
+            }
+            ForwardTarget::Deployment(name) => {
+                let deployments: Api<Deployment> = Api::namespaced(client, &ns);
+                let deployment = deployments.get(name).await.map_err(ForwardError::Resolve)?;
+                let selector = deployment.spec.map(|spec| spec.selector).unwrap_or_default();
+                (selector.try_into().map_err(ForwardError::Selector)?, None)
+            }
+        };
+        if selector.selects_all() {
+            return Err(ForwardError::NoSelector(self.target.clone()));
+        }
+        let pods = Api::<Pod>::namespaced(self.api.client.clone(), &ns)
+            .list(&ListParams::default().labels_from(&selector))
+            .await
+            .map_err(ForwardError::Resolve)?;
+        let pod = select_pod(pods.items).ok_or_else(|| ForwardError::NoReadyPod(self.target.clone()))?;
+        Ok(Backend { pod, service })
+    }
+}
+
+/// Pick the newest of the ready pods that are not being deleted
+fn select_pod(pods: Vec<Pod>) -> Option<Pod> {
+    let ready = |pod: &Pod| {
+        let status = pod.status.as_ref();
+        pod.metadata.deletion_timestamp.is_none()
+            && status.and_then(|s| s.phase.as_deref()) == Some("Running")
+            && status
+                .and_then(|s| s.conditions.as_ref())
+                .into_iter()
+                .flatten()
+                .any(|c| c.type_ == "Ready" && c.status == "True")
+    };
+    pods.into_iter().filter(ready).max_by(|a, b| {
+        let key = |pod: &Pod| (pod.metadata.creation_timestamp.clone(), pod.metadata.name.clone());
+        key(a).cmp(&key(b))
+    })
+}
+
+async fn accept_loop(listener: TcpListener, port: u16, resolver: Arc<Resolver>) {
+    loop {
+        let (connection, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(err) => {
+                // Errors like running out of file descriptors are transient
+                tracing::warn!("failed to accept connection: {err}");
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                continue;
+            }
+        };
+        let resolver = resolver.clone();
+        tokio::spawn(async move {
+            tracing::debug!(%peer, "forwarding connection to port {port} of {}", resolver.target);
+            if let Err(err) = forward_connection(connection, port, &resolver).await {
+                tracing::warn!(%peer, "failed to forward connection to {}: {err}", resolver.target);
+            }
+        });
+    }
+}
+
+async fn forward_connection(
+    mut connection: TcpStream,
+    port: u16,
+    resolver: &Resolver,
+) -> Result<(), ForwardError> {
+    let mut refresh = false;
+    let (mut forwarder, pod, pod_port) = loop {
+        let backend = resolver.resolve(refresh).await?;
+        let pod_port = backend.pod_port(&resolver.target, port)?;
+        match resolver.api.portforward(backend.name(), &[pod_port]).await {
+            Ok(forwarder) => break (forwarder, backend.name().to_owned(), pod_port),
+            // The pod may have been replaced, so look it up again once
+            Err(err) if !refresh => {
+                tracing::debug!("failed to connect to pod {}, retrying: {err}", backend.name());
+                refresh = true;
+            }
+            Err(err) => return Err(ForwardError::Connect(err)),
+        }
+    };
+    let mut upstream = forwarder.take_stream(pod_port).expect("port is forwarded");
+    let error = forwarder.take_error(pod_port).expect("port is forwarded");
+    tokio::select! {
+        copied = tokio::io::copy_bidirectional(&mut connection, &mut upstream) => {
+            if let Err(err) = copied {
+                tracing::debug!("connection to pod {pod} closed: {err}");
+            }
+        }
+        Some(message) = error => {
+            tracing::warn!("failed to forward port {pod_port} of pod {pod}: {message}");
+        }
+    }
+    drop(upstream);
+    forwarder
+        .join()
+        .await
+        .map_err(|err| ForwardError::Forward(Box::new(err)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use http::{Request, Response, StatusCode};
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::{client::Body, Client};
+
+    fn pod(name: &str, created: &str, ready: bool) -> Value {
+        json!({
+            "metadata": { "name": name, "creationTimestamp": created },
+            "spec": { "containers": [{ "name": "app", "ports": [{ "name": "http", "containerPort": 8080 }] }] },
+            "status": {
+                "phase": "Running",
+                "conditions": [{ "type": "Ready", "status": if ready { "True" } else { "False" } }],
+            },
+        })
+    }
+
+    #[test]
+    fn targets() {
+        assert_eq!("web".parse::<ForwardTarget>().unwrap(), ForwardTarget::pod("web"));
+        assert_eq!(
+            "svc/web".parse::<ForwardTarget>().unwrap(),
+            ForwardTarget::service("web")
+        );
+        assert_eq!(
+            "deployments/web".parse::<ForwardTarget>().unwrap(),
+            ForwardTarget::deployment("web")
+        );
+        for invalid in ["job/web", "svc/", "svc/a/b"] {
+            assert!(invalid.parse::<ForwardTarget>().is_err(), "{invalid}");
+        }
+        assert_eq!(ForwardTarget::service("web").to_string(), "service/web");
+
+        let pods = [
+            pod("old", "2024-01-01T00:00:00Z", true),
+            pod("new", "2024-01-02T00:00:00Z", true),
+            pod("newest", "2024-01-03T00:00:00Z", false),
+        ]
+        .map(|pod| serde_json::from_value(pod).unwrap());
+        let selected = select_pod(pods.to_vec()).unwrap();
+        assert_eq!(selected.metadata.name.as_deref(), Some("new"));
+        assert!(select_pod(pods[2..].to_vec()).is_none());
+    }
+
+    async fn apiserver(request: Request<Body>) -> Result<Response<Body>, Infallible> {
+        let body = match (request.uri().path(), request.uri().query()) {
+            ("/api/v1/namespaces/default/services/web", _) => json!({
+                "metadata": { "name": "web" },
+                "spec": {
+                    "selector": { "app": "web" },
+                    "ports": [{ "port": 80, "targetPort": "http" }, { "port": 81, "targetPort": 9090 }],
+                },
+            }),
+            ("/api/v1/namespaces/default/services/headless", _) => json!({
+                "metadata": { "name": "headless" },
+                "spec": { "ports": [{ "port": 80 }] },
+            }),
+            ("/apis/apps/v1/namespaces/default/deployments/web", _) => json!({
+                "metadata": { "name": "web" },
+                "spec": { "selector": { "matchLabels": { "app": "web" } }, "template": {} },
+            }),
+            ("/api/v1/namespaces/default/pods", Some(query)) if query.contains("labelSelector=app%3Dweb") => {
+                json!({
+                    "metadata": {},
+                    "items": [
+                        pod("web-1", "2024-01-01T00:00:00Z", true),
+                        pod("web-2", "2024-01-02T00:00:00Z", false),
+                    ],
+                })
+            }
+            _ => {
+                let response = Response::builder()
+                    .status(StatusCode::NOT_FOUND)
+                    .body(Body::from(b"{}".to_vec()))
+                    .unwrap();
+                return Ok(response);
+            }
+        };
+        Ok(Response::new(Body::from(body.to_string().into_bytes())))
+    }
+
+    #[tokio::test]
+    async fn resolve_targets() {
+        let pods: Api<Pod> = Api::default_namespaced(Client::new(tower::service_fn(apiserver), "default"));
+
+        let target = ForwardTarget::service("web");
+        let backend = Resolver::new(pods.clone(), target.clone())
+            .resolve(false)
+            .await
+            .unwrap();
+        assert_eq!(backend.name(), "web-1");
+        assert_eq!(backend.pod_port(&target, 80).unwrap(), 8080);
+        assert_eq!(backend.pod_port(&target, 81).unwrap(), 9090);
+        assert!(matches!(
+            backend.pod_port(&target, 443),
+            Err(ForwardError::UnknownPort { .. })
+        ));
+
+        let target = ForwardTarget::deployment("web");
+        let backend = Resolver::new(pods.clone(), target.clone())
+            .resolve(false)
+            .await
+            .unwrap();
+        assert_eq!(backend.name(), "web-1");
+        assert_eq!(backend.pod_port(&target, 3000).unwrap(), 3000);
+
+        let headless = Resolver::new(pods.clone(), ForwardTarget::service("headless"));
+        assert!(matches!(
+            headless.resolve(false).await,
+            Err(ForwardError::NoSelector(_))
+        ));
+
+        // Ports are only bound once the target is resolved
+        let fp = ForwardParams::default();
+        let err = pods
+            .forward_ports(ForwardTarget::service("web"), &[(0, 443)], &fp)
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(err, ForwardError::UnknownPort { .. }), "{err}");
+        let forwarding = pods
+            .forward_ports(ForwardTarget::service("web"), &[(0, 80)], &fp)
+            .await
+            .unwrap();
+        let addr = forwarding.local_addr(80).unwrap();
+        assert!(addr.ip().is_loopback() && addr.port() != 0);
+        assert_eq!(forwarding.local_addr(81), None);
+    }
+}
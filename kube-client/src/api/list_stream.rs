@@ -0,0 +1,249 @@
+//! Streaming paginated lists
+use std::{
+    fmt::Debug,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use futures::{future::BoxFuture, Stream};
+use serde::de::DeserializeOwned;
+
+use crate::{api::Api, Error, Result};
+use kube_core::{object::ObjectList, params::ListParams};
+
+/// The page size used when [`ListParams::limit`] is not set
+const DEFAULT_PAGE_SIZE: u32 = 500;
+
+impl<K> Api<K>
+where
+    K: Clone + DeserializeOwned + Debug + Send + 'static,
+{
+    /// Stream the items of a list, fetching it page by page
+    ///
+    /// Pages are fetched with [`ListParams::limit`] (or 500 items) as the next item is needed.
+    /// Once the stream has ended, [`ListStream::resource_version`] can be used to start a watch
+    /// that is consistent with the listed items.
+    ///
+    /// Continue tokens expire after a few minutes, which fails the stream with a `410 Gone` unless
+    /// [`ListStream::on_expired`] is set to restart.
+    ///
+    /// ```no_run
+    /// use futures::TryStreamExt;
+    /// use kube::api::{Api, ListParams, ResourceExt, WatchParams};
+    /// use k8s_openapi::api::core::v1::Pod;
+    ///
+    /// # async fn wrapper() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client: kube::Client = todo!();
+    /// let pods: Api<Pod> = Api::namespaced(client, "apps");
+    /// let mut stream = pods.list_stream(&ListParams::default().limit(100));
+    /// while let Some(pod) = stream.try_next().await? {
+    ///     println!("Found Pod: {}", pod.name_any());
+    /// }
+    /// let rv = stream.resource_version().unwrap_or("0");
+    /// let events = pods.watch(&WatchParams::default(), rv).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn list_stream(&self, lp: &ListParams) -> ListStream<K> {
+        let mut lp = lp.clone();
+        lp.limit.get_or_insert(DEFAULT_PAGE_SIZE);
+        ListStream {
+            api: self.clone(),
+            initial_continue_token: lp.continue_token.clone(),
+            lp,
+            on_expired: OnExpired::default(),
+            items: Vec::new().into_iter(),
+            page: None,
+            resource_version: None,
+            done: false,
+        }
+    }
+}
+
+/// What a [`ListStream`] does when its continue token expires
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OnExpired {
+    /// Fail the stream with the `410 Gone` error
+    #[default]
+    Error,
+    /// Restart the list from the first page
+    ///
+    /// Items that were already streamed are streamed again, possibly in a newer version.
+    Restart,
+}
+
+/// A stream of the items of a paginated list, returned by [`Api::list_stream`]
+#[must_use = "streams do nothing unless polled"]
+pub struct ListStream<K: Clone> {
+    api: Api<K>,
+    lp: ListParams,
+    initial_continue_token: Option<String>,
+    on_expired: OnExpired,
+    items: std::vec::IntoIter<K>,
+    page: Option<BoxFuture<'static, Result<ObjectList<K>>>>,
+    resource_version: Option<String>,
+    done: bool,
+}
+
+// The stream is never pinned structurally
+impl<K: Clone> Unpin for ListStream<K> {}
+
+impl<K: Clone> ListStream<K> {
+    /// Set what happens when the continue token expires
+    pub fn on_expired(mut self, on_expired: OnExpired) -> Self {
+        self.on_expired = on_expired;
+        self
+    }
+
+    /// The resourceVersion of the listed pages
+    ///
+    /// This is `None` until the first page has been fetched, and only covers all items once the stream has ended.
+    pub fn resource_version(&self) -> Option<&str> {
+        self.resource_version.as_deref()
+    }
+}
+
+impl<K> Stream for ListStream<K>
+where
+    K: Clone + DeserializeOwned + Debug + Send + 'static,
+{
+    type Item = Result<K>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(item) = this.items.next() {
+                return Poll::Ready(Some(Ok(item)));
+            }
+            if this.done {
+                return Poll::Ready(None);
+            }
+            let page = this.page.get_or_insert_with(|| {
+                let (api, lp) = (this.api.clone(), this.lp.clone());
+                Box::pin(async move { api.list(&lp).await })
+            });
+            let result = ready!(page.as_mut().poll(cx));
+            this.page = None;
+            match result {
+                Ok(list) => {
+                    this.resource_version = list.metadata.resource_version;
+                    match list.metadata.continue_ {
+                        Some(token) if !token.is_empty() => this.lp.continue_token = Some(token),
+                        _ => this.done = true,
+                    }
+                    this.items = list.items.into_iter();
+                }
+                Err(Error::Api(err))
+                    if err.code == 410
+                        && this.on_expired == OnExpired::Restart
+                        && this.lp.continue_token != this.initial_continue_token =>
+                {
+                    tracing::debug!("continue token expired, restarting list: {err}");
+                    this.lp.continue_token = this.initial_continue_token.clone();
+                }
+                Err(err) => {
+                    this.done = true;
+                    return Poll::Ready(Some(Err(err)));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        convert::Infallible,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+    };
+
+    use futures::{StreamExt, TryStreamExt};
+    use http::{Request, Response, StatusCode};
+    use k8s_openapi::api::core::v1::Pod;
+    use kube_core::ResourceExt;
+    use serde_json::json;
+
+    use super::*;
+    use crate::{client::Body, Client};
+
+    /// Serves 5 pods in pages, expiring the first continue token once if `expire` is set
+    async fn apiserver(
+        request: Request<Body>,
+        expire: Arc<AtomicBool>,
+    ) -> Result<Response<Body>, Infallible> {
+        let query = request.uri().query().unwrap_or_default().to_owned();
+        assert!(query.contains("limit=2"), "{query}");
+        let (start, rv) = match query.split("continue=").nth(1) {
+            None => (0, if expire.load(Ordering::SeqCst) { "10" } else { "20" }),
+            Some(token) if token.starts_with("2") && expire.swap(false, Ordering::SeqCst) => {
+                let status = json!({
+                    "status": "Failure",
+                    "message": "The provided continue parameter is too old",
+                    "reason": "Expired",
+                    "code": 410,
+                });
+                let response = Response::builder()
+                    .status(StatusCode::GONE)
+                    .body(Body::from(status.to_string().into_bytes()))
+                    .unwrap();
+                return Ok(response);
+            }
+            Some(token) => (token[..1].parse().unwrap(), "20"),
+        };
+        let items = (start..(start + 2).min(5))
+            .map(|i| json!({ "metadata": { "name": format!("pod-{i}") } }))
+            .collect::<Vec<_>>();
+        let continue_ = if start + 2 < 5 {
+            format!("{}", start + 2)
+        } else {
+            String::new()
+        };
+        let list = json!({
+            "apiVersion": "v1",
+            "kind": "PodList",
+            "metadata": { "resourceVersion": rv, "continue": continue_ },
+            "items": items,
+        });
+        Ok(Response::new(Body::from(list.to_string().into_bytes())))
+    }
+
+    fn pods(expire: bool) -> Api<Pod> {
+        let expire = Arc::new(AtomicBool::new(expire));
+        let service = tower::service_fn(move |request| apiserver(request, expire.clone()));
+        Api::default_namespaced(Client::new(service, "default"))
+    }
+
+    #[tokio::test]
+    async fn list_stream_fetches_pages() {
+        let lp = ListParams::default().limit(2);
+        let mut stream = pods(false).list_stream(&lp);
+        assert_eq!(stream.resource_version(), None);
+        let mut names = Vec::new();
+        while let Some(pod) = stream.try_next().await.unwrap() {
+            names.push(pod.name_any());
+        }
+        assert_eq!(names, ["pod-0", "pod-1", "pod-2", "pod-3", "pod-4"]);
+        assert_eq!(stream.resource_version(), Some("20"));
+    }
+
+    #[tokio::test]
+    async fn list_stream_expired_continue() {
+        let lp = ListParams::default().limit(2);
+        let results = pods(true).list_stream(&lp).collect::<Vec<_>>().await;
+        assert_eq!(results.len(), 3);
+        assert!(matches!(&results[2], Err(Error::Api(err)) if err.code == 410));
+
+        let mut stream = pods(true).list_stream(&lp).on_expired(OnExpired::Restart);
+        let mut names = Vec::new();
+        while let Some(pod) = stream.try_next().await.unwrap() {
+            names.push(pod.name_any());
+        }
+        assert_eq!(names, [
+            "pod-0", "pod-1", "pod-0", "pod-1", "pod-2", "pod-3", "pod-4"
+        ]);
+        assert_eq!(stream.resource_version(), Some("20"));
+    }
+}
@@ -0,0 +1,400 @@
+//! Resumable streaming of timestamped logs
+use std::{pin::Pin, time::Duration};
+
+use chrono::{DateTime, Utc};
+use futures::{io::Lines, stream, AsyncBufRead, AsyncBufReadExt, Stream, StreamExt};
+use k8s_openapi::api::core::v1::Pod;
+use kube_core::{subresource::LogParams, ErrorResponse};
+
+use crate::{api::Api, Error, Result};
+
+/// The longest delay between reconnects that fail or return no new lines
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// A line of logs with the timestamp it was logged at
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LogEntry {
+    /// When the line was logged
+    pub timestamp: DateTime<Utc>,
+    /// The line, without its timestamp and line ending
+    pub message: String,
+}
+
+impl Api<Pod> {
+    /// Stream the logs of a container as timestamped lines, resuming after disconnects
+    ///
+    /// Log streams end when the API server times out the request or the kubelet restarts.
+    /// This reconnects with [`LogParams::since_time`] set to the timestamp of the last line,
+    /// and skips the lines that were already returned, until the pod has completed or is deleted.
+    /// When the pod has completed, the rest of its logs are read once more without following them.
+    ///
+    /// [`LogParams::timestamps`] is always enabled. `tail_lines`, `since_seconds` and `limit_bytes`
+    /// only apply to the first request. Errors from reconnecting are returned without ending the stream,
+    /// and reconnects that fail or return no new lines back off up to 30 seconds.
+    ///
+    /// ```no_run
+    /// use futures::TryStreamExt;
+    /// use k8s_openapi::api::core::v1::Pod;
+    /// use kube::api::{Api, LogParams};
+    ///
+    /// # async fn wrapper() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client: kube::Client = todo!();
+    /// let pods: Api<Pod> = Api::default_namespaced(client);
+    /// let lp = LogParams { follow: true, ..LogParams::default() };
+    /// let mut logs = std::pin::pin!(pods.log_entries("web", &lp));
+    /// while let Some(entry) = logs.try_next().await? {
+    ///     println!("{} {}", entry.timestamp, entry.message);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn log_entries(
+        &self,
+        name: &str,
+        lp: &LogParams,
+    ) -> impl Stream<Item = Result<LogEntry>> + Send + use<> {
+        let state = Follow {
+            api: self.clone(),
+            name: name.to_owned(),
+            lp: LogParams {
+                timestamps: true,
+                ..lp.clone()
+            },
+            lines: None,
+            connected: false,
+            last: None,
+            at_last: 0,
+            boundary: None,
+            fresh_lines: false,
+            backoff: Duration::ZERO,
+            done: false,
+        };
+        stream::unfold(state, |mut state| async move {
+            let item = state.next().await?;
+            Some((item, state))
+        })
+    }
+}
+
+type LogLines = Lines<Pin<Box<dyn AsyncBufRead + Send>>>;
+
+/// The state of a resumable log stream
+struct Follow {
+    api: Api<Pod>,
+    name: String,
+    lp: LogParams,
+    lines: Option<LogLines>,
+    /// Whether any request has succeeded
+    connected: bool,
+    /// The timestamp of the last returned line
+    last: Option<DateTime<Utc>>,
+    /// The number of returned lines with the last timestamp
+    at_last: usize,
+    /// The last timestamp and how many lines with it are left to skip after resuming
+    boundary: Option<(DateTime<Utc>, usize)>,
+    /// Whether the current request has returned new lines
+    fresh_lines: bool,
+    backoff: Duration,
+    done: bool,
+}
+
+impl Follow {
+    async fn next(&mut self) -> Option<Result<LogEntry>> {
+        loop {
+            if self.done {
+                return None;
+            }
+            let Some(lines) = self.lines.as_mut() else {
+                match self.connect().await {
+                    Ok(()) => continue,
+                    // The pod was deleted since the last request
+                    Err(Error::Api(ErrorResponse { reason, .. }))
+                        if self.connected && reason == "NotFound" =>
+                    {
+                        return None;
+                    }
+                    Err(err) => {
+                        // Failing to connect at all is not worth retrying
+                        self.done = !self.connected;
+                        return Some(Err(err));
+                    }
+                }
+            };
+            match lines.next().await {
+                Some(Ok(line)) => {
+                    if let Some(entry) = self.accept(&line) {
+                        return Some(Ok(entry));
+                    }
+                    continue;
+                }
+                Some(Err(err)) => {
+                    tracing::debug!("log stream of {} failed, resuming: {err}", self.name);
+                    self.lines = None;
+                }
+                None => {
+                    self.lines = None;
+                    if !self.lp.follow {
+                        return None;
+                    }
+                }
+            }
+            match self.should_resume().await {
+                Ok(true) => {}
+                Ok(false) => return None,
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+
+    /// Request the logs since the last returned line
+    async fn connect(&mut self) -> Result<()> {
+        if self.connected && !self.fresh_lines {
+            self.backoff = (self.backoff * 2).clamp(Duration::from_secs(1), MAX_BACKOFF);
+            tokio::time::sleep(self.backoff).await;
+        } else {
+            self.backoff = Duration::ZERO;
+        }
+        // Failing to connect counts as returning no new lines
+        self.fresh_lines = false;
+        let mut lp = self.lp.clone();
+        if let Some(last) = self.last {
+            lp.since_time = Some(last);
+            lp.since_seconds = None;
+            lp.tail_lines = None;
+            lp.limit_bytes = None;
+            self.boundary = Some((last, self.at_last));
+        }
+        let mut req = self
+            .api
+            .request
+            .logs(&self.name, &lp)
+            .map_err(Error::BuildRequest)?;
+        req.extensions_mut().insert("log_entries");
+        let reader = self.api.client.request_stream(req).await?;
+        let reader: Pin<Box<dyn AsyncBufRead + Send>> = Box::pin(reader);
+        self.lines = Some(reader.lines());
+        self.connected = true;
+        Ok(())
+    }
+
+    /// Whether a stream that ended or failed should be resumed, which is until the pod has completed or is deleted
+    async fn should_resume(&mut self) -> Result<bool> {
+        let Some(pod) = self.api.get_opt(&self.name).await? else {
+            return Ok(false);
+        };
+        let phase = pod.status.and_then(|status| status.phase);
+        if matches!(phase.as_deref(), Some("Succeeded" | "Failed")) {
+            // Read what was logged after the disconnect, without waiting for more
+            self.lp.follow = false;
+        }
+        Ok(true)
+    }
+
+    /// Parse a line, unless it was already returned before resuming
+    fn accept(&mut self, line: &str) -> Option<LogEntry> {
+        let Some((timestamp, message)) = parse_line(line) else {
+            tracing::debug!("log line of {} without a timestamp: {line:?}", self.name);
+            let timestamp = self.last.unwrap_or_else(Utc::now);
+            return Some(LogEntry {
+                timestamp,
+                message: line.to_owned(),
+            });
+        };
+        if let Some((last, skip)) = self.boundary.as_mut() {
+            if timestamp < *last {
+                return None;
+            }
+            if timestamp == *last && *skip > 0 {
+                *skip -= 1;
+                return None;
+            }
+            self.boundary = None;
+        }
+        if self.last == Some(timestamp) {
+            self.at_last += 1;
+        } else {
+            self.last = Some(timestamp);
+            self.at_last = 1;
+        }
+        self.fresh_lines = true;
+        Some(LogEntry {
+            timestamp,
+            message: message.to_owned(),
+        })
+    }
+}
+
+/// Split a line logged with `timestamps` into its timestamp and message
+fn parse_line(line: &str) -> Option<(DateTime<Utc>, &str)> {
+    let (timestamp, message) = line.split_once(' ').unwrap_or((line, ""));
+    let timestamp = DateTime::parse_from_rfc3339(timestamp).ok()?;
+    Some((timestamp.with_timezone(&Utc), message))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        convert::Infallible,
+        sync::{Arc, Mutex},
+    };
+
+    use futures::TryStreamExt;
+    use http::{Request, Response};
+    use serde_json::json;
+
+    use super::*;
+    use crate::{client::Body, Client};
+
+    #[test]
+    fn parse_log_lines() {
+        let (timestamp, message) = parse_line("2024-05-01T10:00:00.123456789Z hello world").unwrap();
+        assert_eq!(timestamp.to_rfc3339(), "2024-05-01T10:00:00.123456789+00:00");
+        assert_eq!(message, "hello world");
+        assert_eq!(parse_line("2024-05-01T10:00:00Z").unwrap().1, "");
+        assert!(parse_line("no timestamp").is_none());
+    }
+
+    fn status(code: u16, reason: &str) -> Response<Body> {
+        let status = json!({ "kind": "Status", "apiVersion": "v1", "status": "Failure", "reason": reason, "code": code });
+        Response::builder()
+            .status(code)
+            .body(Body::from(status.to_string().into_bytes()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn log_entries_resume_without_duplicates() {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let seen = requests.clone();
+        let service = tower::service_fn(move |request: Request<Body>| {
+            let uri = request.uri().to_string();
+            let mut seen = seen.lock().unwrap();
+            seen.push(uri.clone());
+            // The pod is running when the first stream ends, and has completed when the second does
+            let pod_gets = seen.iter().filter(|uri| uri.ends_with("/pods/web")).count();
+            let body = if uri.ends_with("/pods/web") {
+                let phase = if pod_gets == 1 { "Running" } else { "Succeeded" };
+                json!({ "metadata": { "name": "web" }, "status": { "phase": phase } }).to_string()
+            } else if !uri.contains("sinceTime") {
+                "2024-05-01T10:00:01.1Z a\n2024-05-01T10:00:01.2Z b\n2024-05-01T10:00:01.2Z c\n".to_owned()
+            } else {
+                // Resuming from the start of the second of the last line repeats it
+                concat!(
+                    "2024-05-01T10:00:01.1Z a\n2024-05-01T10:00:01.2Z b\n2024-05-01T10:00:01.2Z c\n",
+                    "2024-05-01T10:00:01.2Z d\n2024-05-01T10:00:02Z e\n",
+                )
+                .to_owned()
+            };
+            async move { Ok::<_, Infallible>(Response::new(Body::from(body.into_bytes()))) }
+        });
+
+        let pods: Api<Pod> = Api::default_namespaced(Client::new(service, "default"));
+        let lp = LogParams {
+            follow: true,
+            tail_lines: Some(10),
+            ..LogParams::default()
+        };
+        let entries = pods
+            .log_entries("web", &lp)
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let messages = entries.iter().map(|e| e.message.as_str()).collect::<Vec<_>>();
+        assert_eq!(messages, ["a", "b", "c", "d", "e"]);
+
+        let requests = requests.lock().unwrap().clone();
+        let logs = "/api/v1/namespaces/default/pods/web/log?";
+        assert_eq!(requests, [
+            format!("{logs}&follow=true&tailLines=10&timestamps=true"),
+            "/api/v1/namespaces/default/pods/web".to_owned(),
+            format!("{logs}&follow=true&sinceTime=2024-05-01T10%3A00%3A01Z&timestamps=true"),
+            "/api/v1/namespaces/default/pods/web".to_owned(),
+            format!("{logs}&sinceTime=2024-05-01T10%3A00%3A02Z&timestamps=true"),
+        ]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn log_entries_back_off_failed_reconnects_until_deleted() {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let seen = requests.clone();
+        let service = tower::service_fn(move |request: Request<Body>| {
+            let uri = request.uri().to_string();
+            let mut seen = seen.lock().unwrap();
+            seen.push((uri.clone(), tokio::time::Instant::now()));
+            let response = if uri.ends_with("/pods/web") {
+                let pod = json!({ "metadata": { "name": "web" }, "status": { "phase": "Running" } });
+                Response::new(Body::from(pod.to_string().into_bytes()))
+            } else {
+                match seen.len() {
+                    1 => Response::new(Body::from(b"2024-05-01T10:00:01Z a\n".to_vec())),
+                    3 | 4 => status(503, "ServiceUnavailable"),
+                    _ => status(404, "NotFound"),
+                }
+            };
+            async move { Ok::<_, Infallible>(response) }
+        });
+
+        let pods: Api<Pod> = Api::default_namespaced(Client::new(service, "default"));
+        let lp = LogParams {
+            follow: true,
+            ..LogParams::default()
+        };
+        let entries = pods.log_entries("web", &lp).collect::<Vec<_>>().await;
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].as_ref().unwrap().message, "a");
+        assert!(entries[1..].iter().all(Result::is_err));
+
+        // Reconnecting after new lines is immediate, and each failure doubles the delay
+        let requests = requests.lock().unwrap().clone();
+        assert_eq!(requests.len(), 5);
+        let delays = requests
+            .windows(2)
+            .map(|pair| pair[1].1 - pair[0].1)
+            .collect::<Vec<_>>();
+        assert_eq!(delays, [
+            Duration::ZERO,
+            Duration::ZERO,
+            Duration::from_secs(1),
+            Duration::from_secs(2),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn log_entries_end_when_pod_is_deleted_after_failing() {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let seen = requests.clone();
+        let service = tower::service_fn(move |request: Request<Body>| {
+            let uri = request.uri().to_string();
+            seen.lock().unwrap().push(uri.clone());
+            let response = if uri.ends_with("/pods/web") {
+                status(404, "NotFound")
+            } else {
+                let frames = futures::stream::iter([
+                    Ok(http_body::Frame::data(bytes::Bytes::from(
+                        "2024-05-01T10:00:01Z a\n",
+                    ))),
+                    Err(std::io::Error::other("connection reset")),
+                ]);
+                Response::new(Body::wrap_body(http_body_util::StreamBody::new(frames)))
+            };
+            async move { Ok::<_, Infallible>(response) }
+        });
+
+        let pods: Api<Pod> = Api::default_namespaced(Client::new(service, "default"));
+        let lp = LogParams {
+            follow: true,
+            ..LogParams::default()
+        };
+        let entries = pods
+            .log_entries("web", &lp)
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(entries.len(), 1);
+        let requests = requests.lock().unwrap().clone();
+        assert_eq!(requests, [
+            "/api/v1/namespaces/default/pods/web/log?&follow=true&timestamps=true",
+            "/api/v1/namespaces/default/pods/web",
+        ]);
+    }
+}
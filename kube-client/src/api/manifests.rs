@@ -0,0 +1,483 @@
+//! API helpers for applying multi-document YAML manifests, like `kubectl apply -f`
+//!
+//! [`apply_manifests`] is the primary entry point for this API.
+use std::{collections::HashMap, time::Duration};
+
+use kube_core::{
+    discovery::{ApiCapabilities, ApiResource, Scope},
+    gvk::GroupVersionKind,
+    params::{Patch, PatchParams},
+    ResourceExt,
+};
+use serde::Deserialize;
+
+use crate::{api::DynamicObject, discovery, Api, Client, Discovery, Error};
+
+/// How long [`apply_manifests`] waits for a `CustomResourceDefinition` to become established
+pub const CRD_ESTABLISHED_TIMEOUT: Duration = Duration::from_secs(60);
+
+const CRD_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Errors from parsing or applying manifests
+#[derive(Debug, thiserror::Error)]
+pub enum ManifestError {
+    /// A document is not valid YAML, or not a Kubernetes object
+    #[error("failed to parse manifest document {index}: {source}")]
+    Parse {
+        /// The index of the document in the manifest
+        index: usize,
+        /// The parse error
+        #[source]
+        source: serde_yaml::Error,
+    },
+    /// A document has no `apiVersion` or `kind`
+    #[error("manifest document {0} has no apiVersion or kind")]
+    MissingTypeMeta(usize),
+    /// A document has no `metadata.name`
+    #[error("manifest document {0} has no name")]
+    MissingName(usize),
+    /// The kind of an object is not served by the API server
+    #[error("failed to resolve resource: {0}")]
+    Resolve(#[source] Error),
+    /// Failed to apply an object
+    #[error("failed to apply object: {0}")]
+    Apply(#[source] Error),
+    /// A `CustomResourceDefinition` did not become established in time
+    #[error("CustomResourceDefinition {0} was not established within {CRD_ESTABLISHED_TIMEOUT:?}")]
+    NotEstablished(String),
+}
+
+/// The outcome of applying one object of a manifest
+#[derive(Debug)]
+pub struct Applied {
+    /// The kind of the object
+    pub gvk: GroupVersionKind,
+    /// The name of the object
+    pub name: String,
+    /// The namespace the object was applied to, if it is namespaced
+    pub namespace: Option<String>,
+    /// The object returned by the API server, or why it could not be applied
+    pub result: Result<DynamicObject, ManifestError>,
+}
+
+/// Parse the objects in a multi-document YAML manifest
+///
+/// Empty documents are skipped, and the items of `List` kinds (like `v1/List` or `ConfigMapList`) are flattened.
+pub fn parse_manifests(yaml: &str) -> Result<Vec<DynamicObject>, ManifestError> {
+    let mut objects = Vec::new();
+    for (index, document) in serde_yaml::Deserializer::from_str(yaml).enumerate() {
+        let value = serde_yaml::Value::deserialize(document)
+            .map_err(|source| ManifestError::Parse { index, source })?;
+        if value.is_null() {
+            continue;
+        }
+        let kind = value.get("kind").and_then(|k| k.as_str()).unwrap_or_default();
+        let items = value.get("items").and_then(|i| i.as_sequence());
+        match (kind.strip_suffix("List"), items) {
+            (Some(item_kind), Some(items)) => {
+                let api_version = value.get("apiVersion").cloned().unwrap_or_default();
+                for item in items {
+                    let mut item = item.clone();
+                    // Items of typed lists may leave out their type
+                    if let (Some(map), false) = (item.as_mapping_mut(), item_kind.is_empty()) {
+                        if !map.contains_key("kind") {
+                            map.insert("kind".into(), item_kind.into());
+                            map.insert("apiVersion".into(), api_version.clone());
+                        }
+                    }
+                    objects.push(parse_object(index, item)?);
+                }
+            }
+            _ => objects.push(parse_object(index, value)?),
+        }
+    }
+    Ok(objects)
+}
+
+fn parse_object(index: usize, value: serde_yaml::Value) -> Result<DynamicObject, ManifestError> {
+    let object: DynamicObject =
+        serde_yaml::from_value(value).map_err(|source| ManifestError::Parse { index, source })?;
+    if object.types.is_none() {
+        return Err(ManifestError::MissingTypeMeta(index));
+    }
+    if object.metadata.name.is_none() {
+        return Err(ManifestError::MissingName(index));
+    }
+    Ok(object)
+}
+
+/// The order objects are applied in, so that Namespaces and CustomResourceDefinitions exist before objects in them
+fn apply_order(gvk: &GroupVersionKind) -> u8 {
+    match (gvk.group.as_str(), gvk.kind.as_str()) {
+        ("", "Namespace") => 0,
+        ("apiextensions.k8s.io", "CustomResourceDefinition") => 1,
+        _ => 2,
+    }
+}
+
+/// Apply all objects of a multi-document YAML manifest with server-side apply
+///
+/// This is [`parse_manifests`] followed by [`apply_objects`].
+///
+/// ```no_run
+/// use kube::{api::{manifests::apply_manifests, PatchParams}, Client, Discovery};
+/// # async fn wrapper() -> Result<(), Box<dyn std::error::Error>> {
+/// let client = Client::try_default().await?;
+/// let discovery = Discovery::new(client.clone()).run().await?;
+/// let yaml = std::fs::read_to_string("manifests.yaml")?;
+/// let pp = PatchParams::apply("cd-pipeline").force();
+/// for applied in apply_manifests(&client, &discovery, &yaml, &pp).await? {
+///     match applied.result {
+///         Ok(_) => println!("applied {} {}", applied.gvk.kind, applied.name),
+///         Err(err) => println!("failed to apply {} {}: {err}", applied.gvk.kind, applied.name),
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub async fn apply_manifests(
+    client: &Client,
+    discovery: &Discovery,
+    yaml: &str,
+    pp: &PatchParams,
+) -> Result<Vec<Applied>, ManifestError> {
+    let objects = parse_manifests(yaml)?;
+    Ok(apply_objects(client, discovery, objects, pp).await)
+}
+
+/// Apply objects of any kind with server-side apply
+///
+/// Namespaces are applied first, followed by CustomResourceDefinitions, followed by everything else in the given order.
+/// Before applying the rest, this waits up to [`CRD_ESTABLISHED_TIMEOUT`] for the applied CustomResourceDefinitions
+/// to become established, unless `pp` is a dry run.
+///
+/// Kinds are resolved through `discovery`, falling back to querying the API server once for each kind it does not know,
+/// like those of the CustomResourceDefinitions that were just applied.
+/// Namespaced objects without a namespace are applied to the default namespace of the `client`.
+/// `pp` must set a field manager, as for any [`Patch::Apply`].
+///
+/// Objects that cannot be applied do not stop the others from being applied,
+/// so check the [`result`](Applied::result) of every object.
+pub async fn apply_objects(
+    client: &Client,
+    discovery: &Discovery,
+    mut objects: Vec<DynamicObject>,
+    pp: &PatchParams,
+) -> Vec<Applied> {
+    let gvk_of = |object: &DynamicObject| {
+        object
+            .types
+            .as_ref()
+            .and_then(|types| GroupVersionKind::try_from(types).ok())
+            .unwrap_or_else(|| GroupVersionKind::gvk("", "", ""))
+    };
+    objects.sort_by_key(|object| apply_order(&gvk_of(object)));
+
+    let mut resolver = Resolver {
+        client,
+        discovery,
+        resolved: HashMap::new(),
+    };
+    let mut results = Vec::with_capacity(objects.len());
+    let mut crds_established = false;
+    for object in objects {
+        let gvk = gvk_of(&object);
+        if apply_order(&gvk) > 1 && !crds_established {
+            crds_established = true;
+            if !pp.dry_run {
+                wait_for_crds(&mut resolver, &mut results).await;
+            }
+        }
+        results.push(apply_object(&mut resolver, object, gvk, pp).await);
+    }
+    results
+}
+
+async fn apply_object(
+    resolver: &mut Resolver<'_>,
+    object: DynamicObject,
+    gvk: GroupVersionKind,
+    pp: &PatchParams,
+) -> Applied {
+    let client = resolver.client;
+    let name = object.name_any();
+    let (ar, caps) = match resolver.resolve(&gvk).await {
+        Ok(resolved) => resolved,
+        Err(source) => {
+            return Applied {
+                gvk,
+                name,
+                namespace: object.namespace(),
+                result: Err(ManifestError::Resolve(source)),
+            };
+        }
+    };
+    let namespace = match caps.scope {
+        Scope::Cluster => None,
+        Scope::Namespaced => Some(
+            object
+                .namespace()
+                .unwrap_or_else(|| client.default_namespace().to_owned()),
+        ),
+    };
+    let api: Api<DynamicObject> = match &namespace {
+        Some(namespace) => Api::namespaced_with(client.clone(), namespace, &ar),
+        None => Api::all_with(client.clone(), &ar),
+    };
+    let result = api
+        .patch(&name, pp, &Patch::Apply(&object))
+        .await
+        .map_err(ManifestError::Apply);
+    Applied {
+        gvk,
+        name,
+        namespace,
+        result,
+    }
+}
+
+/// Resolves kinds through discovery, remembering the kinds queried from the API server
+struct Resolver<'a> {
+    client: &'a Client,
+    discovery: &'a Discovery,
+    resolved: HashMap<GroupVersionKind, (ApiResource, ApiCapabilities)>,
+}
+
+impl Resolver<'_> {
+    async fn resolve(&mut self, gvk: &GroupVersionKind) -> Result<(ApiResource, ApiCapabilities), Error> {
+        if let Some(resolved) = self.discovery.resolve_gvk(gvk) {
+            return Ok(resolved);
+        }
+        if let Some(resolved) = self.resolved.get(gvk) {
+            return Ok(resolved.clone());
+        }
+        let resolved = discovery::pinned_kind(self.client, gvk).await?;
+        self.resolved.insert(gvk.clone(), resolved.clone());
+        Ok(resolved)
+    }
+}
+
+/// Wait for the applied CustomResourceDefinitions to become established, failing the results of those that do not
+async fn wait_for_crds(resolver: &mut Resolver<'_>, results: &mut [Applied]) {
+    let client = resolver.client;
+    let deadline = tokio::time::Instant::now() + CRD_ESTABLISHED_TIMEOUT;
+    for applied in results.iter_mut() {
+        if apply_order(&applied.gvk) != 1 || applied.result.is_err() {
+            continue;
+        }
+        let api = match resolver.resolve(&applied.gvk).await {
+            Ok((ar, _)) => Api::<DynamicObject>::all_with(client.clone(), &ar),
+            Err(source) => {
+                applied.result = Err(ManifestError::Resolve(source));
+                continue;
+            }
+        };
+        let established = tokio::time::timeout_at(deadline, async {
+            loop {
+                match api.get(&applied.name).await {
+                    Ok(crd) if is_established(&crd) => return Ok(crd),
+                    Ok(_) => {}
+                    Err(Error::Api(err)) if err.code == 404 => {}
+                    Err(err) => return Err(err),
+                }
+                tokio::time::sleep(CRD_POLL_INTERVAL).await;
+            }
+        })
+        .await;
+        applied.result = match established {
+            Ok(Ok(crd)) => Ok(crd),
+            Ok(Err(err)) => Err(ManifestError::Apply(err)),
+            Err(_elapsed) => Err(ManifestError::NotEstablished(applied.name.clone())),
+        };
+    }
+}
+
+fn is_established(crd: &DynamicObject) -> bool {
+    crd.data["status"]["conditions"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .any(|condition| condition["type"] == "Established" && condition["status"] == "True")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        convert::Infallible,
+        sync::{Arc, Mutex},
+    };
+
+    use http::{Request, Response};
+    use serde_json::json;
+
+    use super::*;
+    use crate::client::Body;
+
+    const MANIFEST: &str = r#"
+apiVersion: v1
+kind: ConfigMap
+metadata:
+  name: settings
+data:
+  a: "1"
+---
+apiVersion: example.com/v1
+kind: Widget
+metadata:
+  name: gear
+  namespace: shop
+---
+# only a comment
+---
+apiVersion: apiextensions.k8s.io/v1
+kind: CustomResourceDefinition
+metadata:
+  name: widgets.example.com
+---
+apiVersion: v1
+kind: List
+items:
+- apiVersion: v1
+  kind: Namespace
+  metadata:
+    name: shop
+- apiVersion: v1
+  kind: ServiceAccount
+  metadata:
+    name: robot
+    namespace: shop
+"#;
+
+    #[test]
+    fn parse_manifests_flattens_lists() {
+        let objects = parse_manifests(MANIFEST).unwrap();
+        let kinds = objects
+            .iter()
+            .map(|o| (o.types.as_ref().unwrap().kind.as_str(), o.name_any()))
+            .collect::<Vec<_>>();
+        assert_eq!(kinds, [
+            ("ConfigMap", "settings".to_string()),
+            ("Widget", "gear".to_string()),
+            ("CustomResourceDefinition", "widgets.example.com".to_string()),
+            ("Namespace", "shop".to_string()),
+            ("ServiceAccount", "robot".to_string()),
+        ]);
+
+        let typed_list = "apiVersion: v1\nkind: ConfigMapList\nitems:\n- metadata:\n    name: a\n";
+        let objects = parse_manifests(typed_list).unwrap();
+        assert_eq!(objects[0].types.as_ref().unwrap().kind, "ConfigMap");
+
+        let namespace = "apiVersion: v1\nkind: Namespace\nmetadata:\n  name: a\n---\n";
+        assert!(matches!(
+            parse_manifests(&format!("{namespace}data: [")),
+            Err(ManifestError::Parse { index: 1, .. })
+        ));
+        assert!(matches!(
+            parse_manifests(&format!("{namespace}kind: ConfigMap\nmetadata:\n  name: a\n")),
+            Err(ManifestError::MissingTypeMeta(1))
+        ));
+        assert!(matches!(
+            parse_manifests(&format!("{namespace}apiVersion: v1\nkind: ConfigMap\n")),
+            Err(ManifestError::MissingName(1))
+        ));
+    }
+
+    /// A fake apiserver serving core resources, and widgets once their CRD has been applied and polled once
+    async fn apiserver(
+        request: Request<Body>,
+        requests: Arc<Mutex<Vec<String>>>,
+    ) -> Result<Response<Body>, Infallible> {
+        let (parts, body) = request.into_parts();
+        let body = body.collect_bytes().await.unwrap();
+        let line = format!("{} {}", parts.method, parts.uri.path());
+        let crd_polls = {
+            let mut requests = requests.lock().unwrap();
+            requests.push(line.clone());
+            requests
+                .iter()
+                .filter(|r| r.starts_with("GET /apis/apiextensions.k8s.io/v1/"))
+                .count()
+        };
+        let resource = |name: &str, kind: &str, namespaced: bool| json!({ "name": name, "singularName": "", "kind": kind, "namespaced": namespaced, "verbs": ["get", "patch"] });
+        let list = |group_version: &str, resources: Vec<serde_json::Value>| json!({ "kind": "APIResourceList", "groupVersion": group_version, "resources": resources });
+        let response = match line.as_str() {
+            "GET /api/v1" => list("v1", vec![
+                resource("configmaps", "ConfigMap", true),
+                resource("namespaces", "Namespace", false),
+                resource("serviceaccounts", "ServiceAccount", true),
+            ]),
+            "GET /apis/apiextensions.k8s.io/v1" => list("apiextensions.k8s.io/v1", vec![resource(
+                "customresourcedefinitions",
+                "CustomResourceDefinition",
+                false,
+            )]),
+            "GET /apis/example.com/v1" => list("example.com/v1", vec![resource("widgets", "Widget", true)]),
+            "GET /apis/apiextensions.k8s.io/v1/customresourcedefinitions/widgets.example.com" => {
+                let status = if crd_polls >= 2 { "True" } else { "False" };
+                json!({
+                    "apiVersion": "apiextensions.k8s.io/v1",
+                    "kind": "CustomResourceDefinition",
+                    "metadata": { "name": "widgets.example.com" },
+                    "status": { "conditions": [{ "type": "Established", "status": status }] },
+                })
+            }
+            _ if parts.method == http::Method::PATCH => serde_json::from_slice(&body).unwrap(),
+            _ => unreachable!("unexpected request {line}"),
+        };
+        Ok(Response::new(Body::from(response.to_string().into_bytes())))
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn apply_manifests_orders_and_waits_for_crds() {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let service = {
+            let requests = requests.clone();
+            tower::service_fn(move |request| apiserver(request, requests.clone()))
+        };
+        let client = Client::new(service, "default");
+        let discovery = Discovery::new(client.clone());
+        let pp = PatchParams::apply("test");
+        let manifest = format!(
+            "{MANIFEST}---\napiVersion: example.com/v1\nkind: Widget\nmetadata:\n  name: cog\n  namespace: shop\n"
+        );
+        let applied = apply_manifests(&client, &discovery, &manifest, &pp)
+            .await
+            .unwrap();
+        let summary = applied
+            .iter()
+            .map(|a| (a.gvk.kind.as_str(), a.namespace.as_deref(), a.result.is_ok()))
+            .collect::<Vec<_>>();
+        assert_eq!(summary, [
+            ("Namespace", None, true),
+            ("CustomResourceDefinition", None, true),
+            ("ConfigMap", Some("default"), true),
+            ("Widget", Some("shop"), true),
+            ("ServiceAccount", Some("shop"), true),
+            ("Widget", Some("shop"), true),
+        ]);
+
+        let patches = requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|r| r.starts_with("PATCH") || r.contains("/customresourcedefinitions/"))
+            .cloned()
+            .collect::<Vec<_>>();
+        assert_eq!(patches, [
+            "PATCH /api/v1/namespaces/shop",
+            "PATCH /apis/apiextensions.k8s.io/v1/customresourcedefinitions/widgets.example.com",
+            "GET /apis/apiextensions.k8s.io/v1/customresourcedefinitions/widgets.example.com",
+            "GET /apis/apiextensions.k8s.io/v1/customresourcedefinitions/widgets.example.com",
+            "PATCH /api/v1/namespaces/default/configmaps/settings",
+            "PATCH /apis/example.com/v1/namespaces/shop/widgets/gear",
+            "PATCH /api/v1/namespaces/shop/serviceaccounts/robot",
+            "PATCH /apis/example.com/v1/namespaces/shop/widgets/cog",
+        ]);
+
+        // Each kind is only looked up once
+        let lookups = |path: &str| requests.lock().unwrap().iter().filter(|r| *r == path).count();
+        assert_eq!(lookups("GET /apis/example.com/v1"), 1);
+        assert_eq!(lookups("GET /apis/apiextensions.k8s.io/v1"), 1);
+        assert_eq!(lookups("GET /api/v1"), 3);
+    }
+}
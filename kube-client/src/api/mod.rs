@@ -1,10 +1,28 @@
 //! API helpers for structured interaction with the Kubernetes API
 
 mod core_methods;
+mod list_stream;
+pub use list_stream::{ListStream, OnExpired};
+mod logs;
+pub use logs::LogEntry;
 #[cfg(feature = "ws")] mod remote_command;
 use std::fmt::Debug;
 
 #[cfg(feature = "ws")] pub use remote_command::{AttachedProcess, TerminalSize};
+#[cfg(feature = "ws")] mod exec;
+#[cfg(feature = "ws")]
+#[cfg_attr(docsrs, doc(cfg(feature = "ws")))]
+pub use exec::{ExecError, ExecOutput, ExecParams, DEFAULT_OUTPUT_LIMIT};
+#[cfg(feature = "cp")] mod cp;
+#[cfg(feature = "cp")]
+#[cfg_attr(docsrs, doc(cfg(feature = "cp")))]
+pub use cp::{CopyError, CopyParams, CopyProgress};
+#[cfg(feature = "ws")] mod forward;
+#[cfg(feature = "ws")]
+#[cfg_attr(docsrs, doc(cfg(feature = "ws")))]
+pub use forward::{ForwardError, ForwardParams, ForwardTarget, PortForwarding};
+mod proxy;
+pub use proxy::ProxyClient;
 #[cfg(feature = "ws")] mod portforward;
 #[cfg(feature = "ws")] pub use portforward::Portforwarder;
 
@@ -12,11 +30,13 @@ mod subresource;
 #[cfg(feature = "ws")]
 #[cfg_attr(docsrs, doc(cfg(feature = "ws")))]
 pub use subresource::{Attach, AttachParams, Ephemeral, Execute, Portforward};
-pub use subresource::{Evict, EvictParams, Log, LogParams, ScaleSpec, ScaleStatus};
+pub use subresource::{Evict, EvictParams, Log, LogParams, Proxy, ProxyParams, ScaleSpec, ScaleStatus};
 
 mod util;
 
+pub mod diff;
 pub mod entry;
+pub mod manifests;
 
 // Re-exports from kube-core
 #[cfg(feature = "admission")]
@@ -29,6 +49,7 @@ pub use kube_core::{
     metadata::{ListMeta, ObjectMeta, PartialObjectMeta, PartialObjectMetaExt, TypeMeta},
     object::{NotUsed, Object, ObjectList},
     request::Request,
+    table::{IncludeObject, Table},
     watch::WatchEvent,
     Resource, ResourceExt,
 };
@@ -38,7 +59,7 @@ pub use params::{
     ValidationDirective, VersionMatch, WatchParams,
 };
 
-use crate::Client;
+use crate::{client::WarningHandler, config::Impersonation, Client, Result};
 /// The generic Api abstraction
 ///
 /// This abstracts over a [`Request`] and a type `K` so that
@@ -126,6 +147,62 @@ impl<K: Resource> Api<K> {
     pub fn resource_url(&self) -> &str {
         &self.request.url_path
     }
+
+    /// Return an [`Api`] that impersonates the given user and groups for all its requests
+    ///
+    /// See [`Client::impersonate`] for setting the uid or extra fields.
+    ///
+    /// ```no_run
+    /// # use kube::{Api, Client};
+    /// # use k8s_openapi::api::core::v1::Pod;
+    /// # async fn wrapper() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client: Client = todo!();
+    /// let pods: Api<Pod> = Api::default_namespaced(client);
+    /// let pods_as_jane = pods.impersonate("jane", ["devs"])?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn impersonate<I, S>(&self, user: &str, groups: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let impersonation = Impersonation::user(user).groups(groups);
+        Ok(Self {
+            request: self.request.clone(),
+            client: self.client.impersonate(&impersonation)?,
+            namespace: self.namespace.clone(),
+            _phantom: std::iter::empty(),
+        })
+    }
+
+    /// Return an [`Api`] that passes the `Warning` headers of its responses to the given handler
+    ///
+    /// Use a [`CollectWarnings`](crate::client::CollectWarnings) to inspect the warnings of the calls made with the returned [`Api`]:
+    ///
+    /// ```no_run
+    /// # use kube::{api::{Patch, PatchParams}, client::CollectWarnings, Api, Client};
+    /// # use k8s_openapi::api::core::v1::Pod;
+    /// # async fn wrapper() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client: Client = todo!();
+    /// let warnings = CollectWarnings::default();
+    /// let pods: Api<Pod> = Api::default_namespaced(client).with_warning_handler(warnings.clone());
+    /// let patch = serde_json::json!({ "spec": { "unknownField": true } });
+    /// let pp = PatchParams::default().validation_warn();
+    /// pods.patch("blog", &pp, &Patch::Merge(&patch)).await?;
+    /// assert!(!warnings.take().is_empty());
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn with_warning_handler(&self, handler: impl WarningHandler + 'static) -> Self {
+        Self {
+            request: self.request.clone(),
+            client: self.client.clone().with_warning_handler(handler),
+            namespace: self.namespace.clone(),
+            _phantom: std::iter::empty(),
+        }
+    }
 }
 
 /// Api constructors for Resource implementors with Default DynamicTypes
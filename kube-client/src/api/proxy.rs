@@ -0,0 +1,152 @@
+//! HTTP client for the proxy subresource of pods, services and nodes
+use std::{
+    fmt,
+    task::{Context, Poll},
+};
+
+use bytes::Bytes;
+use futures::future::BoxFuture;
+use http::{Request, Response};
+use kube_core::subresource::ProxyParams;
+use tower::{BoxError, Service};
+
+use crate::{client::Body, Client, Error, Result};
+
+/// An HTTP client for a pod, service or node, through the proxy subresource of the apiserver
+///
+/// Requests are sent with their own method, headers and body,
+/// and their path and query are resolved relative to the proxy path of the object,
+/// e.g. `/healthz` becomes `/api/v1/namespaces/{ns}/services/{name}:{port}/proxy/healthz`.
+/// Responses are returned as they are, without checking their status, with a streaming body.
+///
+/// Besides [`ProxyClient::send`], this is a [`tower::Service`] for requests of any [`http_body::Body`].
+///
+/// Created with [`Api::proxy`](crate::Api::proxy).
+#[derive(Clone)]
+pub struct ProxyClient {
+    client: Client,
+    request: kube_core::Request,
+    name: String,
+    params: ProxyParams,
+}
+
+impl fmt::Debug for ProxyClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProxyClient")
+            .field("request", &self.request)
+            .field("name", &self.name)
+            .field("params", &self.params)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ProxyClient {
+    pub(crate) fn new(client: Client, request: kube_core::Request, name: &str, params: ProxyParams) -> Self {
+        Self {
+            client,
+            request,
+            name: name.to_owned(),
+            params,
+        }
+    }
+
+    /// Send a request to the proxied object
+    ///
+    /// ```no_run
+    /// use http_body_util::BodyExt;
+    /// use k8s_openapi::api::core::v1::Service;
+    /// use kube::api::{Api, ProxyParams};
+    ///
+    /// # async fn wrapper() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client: kube::Client = todo!();
+    /// let services: Api<Service> = Api::default_namespaced(client);
+    /// let web = services.proxy("web", &ProxyParams::default().port(8080));
+    /// let request = http::Request::post("/api/items").body(br#"{"name":"item"}"#.to_vec().into())?;
+    /// let response = web.send(request).await?;
+    /// println!("{}", response.status());
+    /// let body = response.into_body().collect().await?.to_bytes();
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn send(&self, request: Request<Body>) -> Result<Response<Body>> {
+        let mut request = self
+            .request
+            .proxy(&self.name, &self.params, request)
+            .map_err(Error::BuildRequest)?;
+        request.extensions_mut().insert("proxy");
+        self.client.send(request).await
+    }
+}
+
+impl<B> Service<Request<B>> for ProxyClient
+where
+    B: http_body::Body<Data = Bytes> + Send + 'static,
+    B::Error: Into<BoxError>,
+{
+    type Error = Error;
+    type Future = BoxFuture<'static, Result<Response<Body>>>;
+    type Response = Response<Body>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Request<B>) -> Self::Future {
+        let proxy = self.clone();
+        let request = request.map(Body::wrap_body);
+        Box::pin(async move { proxy.send(request).await })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use futures::{stream, StreamExt};
+    use http_body_util::{BodyExt, StreamBody};
+    use k8s_openapi::api::core::v1::Service as KubeService;
+    use tower::ServiceExt;
+
+    use crate::Api;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn proxies_requests_with_streaming_bodies() {
+        let apiserver = tower::service_fn(|request: Request<Body>| async move {
+            assert_eq!(request.method(), http::Method::PUT);
+            assert_eq!(
+                request.uri(),
+                "/api/v1/namespaces/default/services/web:http/proxy/items/1?dry=true"
+            );
+            assert_eq!(request.headers()["x-test"], "yes");
+            let received = request.into_body().collect_bytes().await.unwrap();
+            assert_eq!(received, "hello world");
+            let chunks = stream::iter(["a", "b", "c"])
+                .map(|chunk| Ok::<_, Infallible>(http_body::Frame::data(Bytes::from(chunk))));
+            Ok::<_, Infallible>(
+                Response::builder()
+                    .status(http::StatusCode::ACCEPTED)
+                    .body(Body::wrap_body(StreamBody::new(chunks)))
+                    .unwrap(),
+            )
+        });
+        let services: Api<KubeService> = Api::default_namespaced(Client::new(apiserver, "default"));
+        let web = services.proxy("web", &ProxyParams::default().port("http"));
+
+        let chunks = stream::iter(["hello", " ", "world"])
+            .map(|chunk| Ok::<_, Infallible>(http_body::Frame::data(Bytes::from(chunk))));
+        let request = Request::put("/items/1?dry=true")
+            .header("x-test", "yes")
+            .body(StreamBody::new(chunks))
+            .unwrap();
+        let response = web.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), http::StatusCode::ACCEPTED);
+        let mut body = response.into_body();
+        let mut frames = Vec::new();
+        while let Some(frame) = body.frame().await {
+            frames.push(frame.unwrap().into_data().unwrap());
+        }
+        assert_eq!(frames, ["a", "b", "c"]);
+    }
+}
@@ -3,12 +3,12 @@ use serde::{de::DeserializeOwned, Serialize};
 use std::fmt::Debug;
 
 use crate::{
-    api::{Api, Patch, PatchParams, PostParams},
+    api::{Api, Patch, PatchParams, PostParams, ProxyClient},
     Error, Result,
 };
 
 use kube_core::response::Status;
-pub use kube_core::subresource::{EvictParams, LogParams};
+pub use kube_core::subresource::{EvictParams, LogParams, ProxyParams};
 
 #[cfg(feature = "ws")]
 #[cfg_attr(docsrs, doc(cfg(feature = "ws")))]
@@ -468,6 +468,47 @@ where
     }
 }
 
+// ----------------------------------------------------------------------------
+// Proxy subresource
+// ----------------------------------------------------------------------------
+
+/// Marker trait for objects that can be proxied to
+///
+/// See [`Api::proxy`] for usage
+pub trait Proxy {}
+
+impl Proxy for k8s_openapi::api::core::v1::Pod {}
+impl Proxy for k8s_openapi::api::core::v1::Service {}
+impl Proxy for k8s_openapi::api::core::v1::Node {}
+
+impl<K> Api<K>
+where
+    K: Proxy,
+{
+    /// Create an HTTP client for an object, through the apiserver proxy
+    ///
+    /// This talks to pods, services and nodes without port-forwarding,
+    /// with requests authenticated and authorized by the apiserver.
+    /// See [`ProxyClient`] for how requests are sent.
+    ///
+    /// ```no_run
+    /// use k8s_openapi::api::core::v1::Node;
+    /// use kube::api::{Api, ProxyParams};
+    ///
+    /// # async fn wrapper() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client: kube::Client = todo!();
+    /// let nodes: Api<Node> = Api::all(client);
+    /// let kubelet = nodes.proxy("node1", &ProxyParams::default().https().port(10250));
+    /// let response = kubelet.send(http::Request::get("/healthz").body(vec![].into())?).await?;
+    /// assert!(response.status().is_success());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn proxy(&self, name: &str, pp: &ProxyParams) -> ProxyClient {
+        ProxyClient::new(self.client.clone(), self.request.clone(), name, pp.clone())
+    }
+}
+
 // ----------------------------------------------------------------------------
 // Attach subresource
 // ----------------------------------------------------------------------------
@@ -0,0 +1,309 @@
+use std::fmt;
+
+use futures::{stream, StreamExt, TryStreamExt};
+use k8s_openapi::api::{
+    authentication::v1::{SelfSubjectReview, UserInfo},
+    authorization::v1::{
+        ResourceAttributes, SelfSubjectAccessReview, SelfSubjectAccessReviewSpec, SelfSubjectRulesReview,
+        SelfSubjectRulesReviewSpec, SubjectAccessReviewStatus, SubjectRulesReviewStatus,
+    },
+};
+use kube_core::{params::PostParams, ApiResource};
+
+use crate::{Api, Client, Result};
+
+/// The number of access reviews [`Client::missing_permissions`] has in flight at once
+const CONCURRENT_REVIEWS: usize = 8;
+
+/// An action on a resource, to check with [`Client::can_i`] or [`Client::missing_permissions`]
+///
+/// Converts from a `(verb, &ApiResource, namespace)` tuple.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Permission {
+    /// The verb, such as `get`, `list`, `watch`, `create`, `patch` or `delete`
+    pub verb: String,
+    /// The API group of the resource, empty for the core group
+    pub group: String,
+    /// The plural name of the resource
+    pub resource: String,
+    /// The subresource, such as `status` or `log`
+    pub subresource: Option<String>,
+    /// The namespace, or `None` for all namespaces and cluster scoped resources
+    pub namespace: Option<String>,
+    /// The name of the object, or `None` for all objects
+    pub name: Option<String>,
+}
+
+impl Permission {
+    /// An action on all objects of a resource, in a namespace or in all namespaces
+    pub fn new(verb: &str, resource: &ApiResource, namespace: Option<&str>) -> Self {
+        Self {
+            verb: verb.to_owned(),
+            group: resource.group.clone(),
+            resource: resource.plural.clone(),
+            subresource: None,
+            namespace: namespace.map(str::to_owned),
+            name: None,
+        }
+    }
+
+    /// Act on a subresource
+    #[must_use]
+    pub fn subresource(mut self, subresource: &str) -> Self {
+        self.subresource = Some(subresource.to_owned());
+        self
+    }
+
+    /// Act on a single object
+    #[must_use]
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_owned());
+        self
+    }
+
+    fn attributes(&self) -> ResourceAttributes {
+        ResourceAttributes {
+            verb: Some(self.verb.clone()),
+            group: Some(self.group.clone()),
+            resource: Some(self.resource.clone()),
+            subresource: self.subresource.clone(),
+            namespace: self.namespace.clone(),
+            name: self.name.clone(),
+            ..ResourceAttributes::default()
+        }
+    }
+}
+
+impl From<(&str, &ApiResource, Option<&str>)> for Permission {
+    fn from((verb, resource, namespace): (&str, &ApiResource, Option<&str>)) -> Self {
+        Self::new(verb, resource, namespace)
+    }
+}
+
+/// Formatted like the arguments of `kubectl auth can-i`, e.g. `list deployments.apps -n default`
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.verb, self.resource)?;
+        if !self.group.is_empty() {
+            write!(f, ".{}", self.group)?;
+        }
+        if let Some(name) = &self.name {
+            write!(f, "/{name}")?;
+        }
+        if let Some(subresource) = &self.subresource {
+            write!(f, " --subresource={subresource}")?;
+        }
+        match &self.namespace {
+            Some(namespace) => write!(f, " -n {namespace}"),
+            None => write!(f, " --all-namespaces"),
+        }
+    }
+}
+
+/// A [`Permission`] that was denied, returned from [`Client::missing_permissions`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MissingPermission {
+    /// The denied permission
+    pub permission: Permission,
+    /// Why the permission was denied, if the authorizer said
+    pub reason: Option<String>,
+}
+
+impl fmt::Display for MissingPermission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cannot {}", self.permission)?;
+        if let Some(reason) = &self.reason {
+            write!(f, ": {reason}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Methods to check the permissions of the client
+///
+/// These are useful for failing fast with an actionable message when RBAC is insufficient,
+/// rather than on the first forbidden request.
+impl Client {
+    /// Check whether the client is allowed an action with a `SelfSubjectAccessReview`
+    ///
+    /// This is what `kubectl auth can-i` does.
+    ///
+    /// ```no_run
+    /// use k8s_openapi::api::apps::v1::Deployment;
+    /// use kube::{client::Permission, core::ApiResource, Client};
+    /// # async fn wrapper() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client: Client = todo!();
+    /// let deployments = ApiResource::erase::<Deployment>(&());
+    /// let permission = Permission::new("patch", &deployments, Some("default")).subresource("scale");
+    /// if !client.can_i(&permission).await? {
+    ///     println!("cannot scale deployments in default");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn can_i(&self, permission: &Permission) -> Result<bool> {
+        Ok(self.access_review(permission).await?.allowed)
+    }
+
+    /// Check a list of permissions, returning those that are denied
+    ///
+    /// ```no_run
+    /// use k8s_openapi::api::core::v1::{ConfigMap, Pod};
+    /// use kube::{core::ApiResource, Client};
+    /// # async fn wrapper() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client: Client = todo!();
+    /// let pods = ApiResource::erase::<Pod>(&());
+    /// let configmaps = ApiResource::erase::<ConfigMap>(&());
+    /// let missing = client
+    ///     .missing_permissions([
+    ///         ("list", &pods, None),
+    ///         ("watch", &pods, None),
+    ///         ("create", &configmaps, Some("default")),
+    ///     ])
+    ///     .await?;
+    /// for missing in &missing {
+    ///     eprintln!("{missing}");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn missing_permissions<P>(
+        &self,
+        permissions: impl IntoIterator<Item = P>,
+    ) -> Result<Vec<MissingPermission>>
+    where
+        P: Into<Permission>,
+    {
+        stream::iter(permissions.into_iter().map(Into::into))
+            .map(|permission| async move {
+                let status = self.access_review(&permission).await?;
+                Ok((!status.allowed).then(|| MissingPermission {
+                    reason: status.reason.or(status.evaluation_error),
+                    permission,
+                }))
+            })
+            .buffered(CONCURRENT_REVIEWS)
+            .try_filter_map(|missing| async move { Ok(missing) })
+            .try_collect()
+            .await
+    }
+
+    /// Review an action with a `SelfSubjectAccessReview`
+    pub async fn access_review(&self, permission: &Permission) -> Result<SubjectAccessReviewStatus> {
+        let review = SelfSubjectAccessReview {
+            spec: SelfSubjectAccessReviewSpec {
+                resource_attributes: Some(permission.attributes()),
+                ..SelfSubjectAccessReviewSpec::default()
+            },
+            ..SelfSubjectAccessReview::default()
+        };
+        let api: Api<SelfSubjectAccessReview> = Api::all(self.clone());
+        let review = api.create(&PostParams::default(), &review).await?;
+        Ok(review.status.unwrap_or_default())
+    }
+
+    /// List the actions the client is allowed in a namespace with a `SelfSubjectRulesReview`
+    ///
+    /// This is what `kubectl auth can-i --list` does.
+    /// The rules may be incomplete when the authorizer does not support listing them,
+    /// which is indicated by [`SubjectRulesReviewStatus::incomplete`].
+    pub async fn rules_review(&self, namespace: &str) -> Result<SubjectRulesReviewStatus> {
+        let review = SelfSubjectRulesReview {
+            spec: SelfSubjectRulesReviewSpec {
+                namespace: Some(namespace.to_owned()),
+            },
+            ..SelfSubjectRulesReview::default()
+        };
+        let api: Api<SelfSubjectRulesReview> = Api::all(self.clone());
+        let review = api.create(&PostParams::default(), &review).await?;
+        Ok(review.status.unwrap_or_default())
+    }
+
+    /// Get the user the client is authenticated as with a `SelfSubjectReview`
+    ///
+    /// This is what `kubectl auth whoami` does.
+    pub async fn whoami(&self) -> Result<UserInfo> {
+        let api: Api<SelfSubjectReview> = Api::all(self.clone());
+        let review = api
+            .create(&PostParams::default(), &SelfSubjectReview::default())
+            .await?;
+        Ok(review
+            .status
+            .and_then(|status| status.user_info)
+            .unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use http::{Request, Response};
+    use k8s_openapi::api::{apps::v1::Deployment, core::v1::Pod};
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::client::Body;
+
+    #[test]
+    fn display_permissions() {
+        let deployments = ApiResource::erase::<Deployment>(&());
+        let pods = ApiResource::erase::<Pod>(&());
+        let permission = Permission::new("patch", &deployments, Some("ns"))
+            .name("web")
+            .subresource("scale");
+        assert_eq!(
+            permission.to_string(),
+            "patch deployments.apps/web --subresource=scale -n ns"
+        );
+        let missing = MissingPermission {
+            permission: ("list", &pods, None).into(),
+            reason: None,
+        };
+        assert_eq!(missing.to_string(), "cannot list pods --all-namespaces");
+    }
+
+    #[tokio::test]
+    async fn reviews_permissions() {
+        let apiserver = tower::service_fn(|request: Request<Body>| async move {
+            assert_eq!(request.method(), http::Method::POST);
+            let uri = request.uri().path().to_owned();
+            let mut review: Value =
+                serde_json::from_slice(&request.into_body().collect_bytes().await.unwrap()).unwrap();
+            if uri == "/apis/authorization.k8s.io/v1/selfsubjectaccessreviews" {
+                let attributes = &review["spec"]["resourceAttributes"];
+                let allowed = attributes["verb"] != "delete";
+                review["status"] = json!({ "allowed": allowed, "reason": "no RBAC policy matched" });
+            } else {
+                assert_eq!(uri, "/apis/authentication.k8s.io/v1/selfsubjectreviews");
+                review["status"] = json!({ "userInfo": { "username": "system:serviceaccount:default:app" } });
+            }
+            Ok::<_, Infallible>(Response::new(Body::from(serde_json::to_vec(&review).unwrap())))
+        });
+        let client = Client::new(apiserver, "default");
+        let pods = ApiResource::erase::<Pod>(&());
+
+        assert!(client
+            .can_i(&Permission::new("get", &pods, Some("default")))
+            .await
+            .unwrap());
+        let missing = client
+            .missing_permissions([
+                ("list", &pods, None),
+                ("delete", &pods, Some("default")),
+                ("watch", &pods, None),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(missing, [MissingPermission {
+            permission: Permission::new("delete", &pods, Some("default")),
+            reason: Some("no RBAC policy matched".into()),
+        }]);
+
+        let user = client.whoami().await.unwrap();
+        assert_eq!(
+            user.username.as_deref(),
+            Some("system:serviceaccount:default:app")
+        );
+    }
+}
@@ -0,0 +1,633 @@
+//! Native EKS bearer tokens, generated without shelling out to `aws eks get-token`
+//!
+//! An EKS token is a presigned STS `GetCallerIdentity` URL, which the cluster's authenticator calls
+//! to find out who the bearer is. See <https://github.com/kubernetes-sigs/aws-iam-authenticator#api-authorization-from-outside-a-cluster>
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use http_body_util::BodyExt;
+use hyper_util::rt::TokioExecutor;
+use secrecy::{ExposeSecret, SecretString};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use super::SIXTY_SEC;
+use crate::{client::Body, config::ExecConfig};
+
+const TOKEN_PREFIX: &str = "k8s-aws-v1.";
+const CLUSTER_ID_HEADER: &str = "x-k8s-aws-id";
+const STS_VERSION: &str = "2011-06-15";
+const DEFAULT_REGION: &str = "us-east-1";
+// Hex encoded SHA-256 of an empty payload
+const EMPTY_PAYLOAD_SHA256: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+#[derive(Error, Debug)]
+/// Possible errors when generating EKS tokens
+pub enum Error {
+    /// Failed to read the shared credentials file
+    #[error("failed to read AWS credentials file '{1:?}': {0}")]
+    ReadCredentialsFile(#[source] std::io::Error, PathBuf),
+
+    /// The profile has no static credentials
+    #[error("AWS profile '{0}' has no aws_access_key_id and aws_secret_access_key")]
+    MissingProfileCredentials(String),
+
+    /// Failed to read the web identity token file
+    #[error("failed to read web identity token file '{1:?}': {0}")]
+    ReadWebIdentityToken(#[source] std::io::Error, PathBuf),
+
+    /// Failed to build a request
+    #[error("failed to build request: {0}")]
+    BuildRequest(#[source] http::Error),
+
+    /// Failed to request credentials from STS
+    #[error("failed to request credentials from STS: {0}")]
+    RequestCredentials(#[source] hyper_util::client::legacy::Error),
+
+    /// Failed to concatenate the buffers from response body
+    #[error("failed to concatenate the buffers from response body: {0}")]
+    ConcatBuffers(#[source] hyper::Error),
+
+    /// STS rejected the request
+    #[error("STS AssumeRoleWithWebIdentity failed with status {0}: {1}")]
+    AssumeRoleWithWebIdentity(http::StatusCode, String),
+
+    /// STS response is missing a field
+    #[error("STS response is missing {0}")]
+    MissingResponseField(&'static str),
+
+    /// Malformed credentials expiration date
+    #[error("malformed credentials expiration date: {0}")]
+    MalformedExpirationDate(#[source] chrono::ParseError),
+
+    /// No valid native root CA certificates found
+    #[error("No valid native root CA certificates found")]
+    NoValidNativeRootCA(#[source] std::io::Error),
+
+    /// Failed to create OpenSSL HTTPS connector
+    #[cfg(feature = "openssl-tls")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "openssl-tls")))]
+    #[error("failed to create OpenSSL HTTPS connector: {0}")]
+    CreateOpensslHttpsConnector(#[source] openssl::error::ErrorStack),
+}
+
+// AWS credentials used to sign the token
+#[derive(Clone, Debug)]
+struct Credentials {
+    access_key_id: String,
+    secret_access_key: SecretString,
+    session_token: Option<SecretString>,
+    expiration: Option<DateTime<Utc>>,
+}
+
+// Where credentials are loaded from, following the precedence of the AWS CLI
+#[derive(Clone, Debug)]
+enum CredentialSource {
+    // `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and `AWS_SESSION_TOKEN`
+    Environment(Credentials),
+    // `AWS_WEB_IDENTITY_TOKEN_FILE` and `AWS_ROLE_ARN`, e.g. IAM roles for service accounts
+    WebIdentity {
+        token_file: PathBuf,
+        role_arn: String,
+        session_name: String,
+    },
+    // `~/.aws/credentials` or `AWS_SHARED_CREDENTIALS_FILE`
+    Profile {
+        path: PathBuf,
+        name: String,
+    },
+}
+
+/// Generates EKS tokens for the cluster of an `aws eks get-token` or `aws-iam-authenticator token` exec config
+pub struct Eks {
+    cluster_id: String,
+    region: String,
+    source: CredentialSource,
+    credentials: Option<Credentials>,
+    token: Option<(SecretString, DateTime<Utc>)>,
+}
+
+impl std::fmt::Debug for Eks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Eks")
+            .field("cluster_id", &self.cluster_id)
+            .field("region", &self.region)
+            .field("source", &self.source)
+            .finish()
+    }
+}
+
+impl Eks {
+    /// Recognize exec configs that only get a token for a cluster
+    ///
+    /// Returns `None` for other commands, and for options that need the real CLI such as `--role-arn`,
+    /// so that the exec plugin is run as before.
+    pub(crate) fn from_exec(exec: &ExecConfig) -> Option<Self> {
+        let command = Path::new(exec.command.as_ref()?).file_stem()?.to_str()?;
+        let args = exec.args.clone().unwrap_or_default();
+        let arg = |names: &[&str]| -> Option<String> {
+            args.iter().enumerate().find_map(|(i, arg)| {
+                names.iter().find_map(|name| {
+                    if arg == name {
+                        args.get(i + 1).cloned()
+                    } else {
+                        arg.strip_prefix(&format!("{name}=")).map(str::to_owned)
+                    }
+                })
+            })
+        };
+        let (cluster_id, profile) = match command {
+            "aws" if is_eks_get_token(&args) => {
+                if arg(&["--role-arn"]).is_some() {
+                    return None;
+                }
+                (
+                    arg(&["--cluster-id"]).or_else(|| arg(&["--cluster-name"]))?,
+                    arg(&["--profile"]),
+                )
+            }
+            "aws-iam-authenticator" if args.first().is_some_and(|a| a == "token") => {
+                if arg(&["-r", "--role"]).is_some() {
+                    return None;
+                }
+                (arg(&["-i", "--cluster-id"])?, None)
+            }
+            _ => return None,
+        };
+
+        // The exec env is added on top of the process env
+        let overrides = exec
+            .env
+            .iter()
+            .flatten()
+            .filter_map(|env| Some((env.get("name")?.clone(), env.get("value")?.clone())))
+            .collect::<HashMap<_, _>>();
+        let var = |name: &str| {
+            overrides
+                .get(name)
+                .cloned()
+                .or_else(|| std::env::var(name).ok())
+                .filter(|v| !v.is_empty())
+        };
+
+        let region = arg(&["--region"])
+            .or_else(|| var("AWS_REGION"))
+            .or_else(|| var("AWS_DEFAULT_REGION"))
+            .unwrap_or_else(|| DEFAULT_REGION.to_owned());
+        let profile_path = || {
+            var("AWS_SHARED_CREDENTIALS_FILE")
+                .map(PathBuf::from)
+                .or_else(|| home::home_dir().map(|home| home.join(".aws").join("credentials")))
+        };
+        let source = if let Some(name) = profile {
+            let path = profile_path()?;
+            profile_credentials(&path, &name).ok()?;
+            CredentialSource::Profile { path, name }
+        } else if let (Some(access_key_id), Some(secret_access_key)) =
+            (var("AWS_ACCESS_KEY_ID"), var("AWS_SECRET_ACCESS_KEY"))
+        {
+            CredentialSource::Environment(Credentials {
+                access_key_id,
+                secret_access_key: secret_access_key.into(),
+                session_token: var("AWS_SESSION_TOKEN").map(SecretString::from),
+                expiration: None,
+            })
+        } else if let (Some(token_file), Some(role_arn)) =
+            (var("AWS_WEB_IDENTITY_TOKEN_FILE"), var("AWS_ROLE_ARN"))
+        {
+            CredentialSource::WebIdentity {
+                token_file: token_file.into(),
+                role_arn,
+                session_name: var("AWS_ROLE_SESSION_NAME")
+                    .unwrap_or_else(|| format!("kube-rs-{}", Utc::now().timestamp_millis())),
+            }
+        } else {
+            let path = profile_path()?;
+            let name = var("AWS_PROFILE").unwrap_or_else(|| "default".to_owned());
+            // Only take over when the profile has static keys, e.g. not for SSO profiles
+            profile_credentials(&path, &name).ok()?;
+            CredentialSource::Profile { path, name }
+        };
+        Some(Self {
+            cluster_id,
+            region,
+            source,
+            credentials: None,
+            token: None,
+        })
+    }
+
+    /// Get a token, generating a new one when the cached one is about to expire
+    pub async fn token(&mut self) -> Result<SecretString, Error> {
+        if let Some((token, expiration)) = &self.token {
+            if Utc::now() + SIXTY_SEC < *expiration {
+                return Ok(token.clone());
+            }
+        }
+        let credentials = self.credentials().await?;
+        let now = Utc::now();
+        let url = presigned_url(&credentials, &self.region, &self.cluster_id, now);
+        let token = SecretString::from(format!(
+            "{TOKEN_PREFIX}{}",
+            base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(url)
+        ));
+        // Tokens are valid for 15 minutes, refresh a minute early like `aws eks get-token`
+        let mut expiration = now + chrono::Duration::minutes(14);
+        if let Some(credentials_expiration) = credentials.expiration {
+            expiration = expiration.min(credentials_expiration);
+        }
+        self.token = Some((token.clone(), expiration));
+        Ok(token)
+    }
+
+    async fn credentials(&mut self) -> Result<Credentials, Error> {
+        if let Some(credentials) = &self.credentials {
+            let expiring = credentials
+                .expiration
+                .is_some_and(|expiration| Utc::now() + SIXTY_SEC >= expiration);
+            if !expiring {
+                return Ok(credentials.clone());
+            }
+        }
+        let credentials = match &self.source {
+            CredentialSource::Environment(credentials) => return Ok(credentials.clone()),
+            // Re-read so rotated keys are picked up
+            CredentialSource::Profile { path, name } => return profile_credentials(path, name),
+            CredentialSource::WebIdentity {
+                token_file,
+                role_arn,
+                session_name,
+            } => assume_role_with_web_identity(&self.region, token_file, role_arn, session_name).await?,
+        };
+        self.credentials = Some(credentials.clone());
+        Ok(credentials)
+    }
+}
+
+/// Global options of the AWS CLI that do not take a value
+const AWS_GLOBAL_FLAGS: &[&str] = &[
+    "--debug",
+    "--no-verify-ssl",
+    "--no-paginate",
+    "--no-sign-request",
+    "--no-cli-pager",
+    "--cli-auto-prompt",
+    "--no-cli-auto-prompt",
+];
+
+/// Whether the AWS CLI arguments run `eks get-token`, possibly after global options
+///
+/// `aws eks update-kubeconfig` writes `--region <region> eks get-token ...`.
+fn is_eks_get_token(args: &[String]) -> bool {
+    let mut i = 0;
+    while let Some(arg) = args.get(i) {
+        if !arg.starts_with("--") {
+            return args[i..].iter().take(2).eq(["eks", "get-token"].iter());
+        }
+        let takes_value = !arg.contains('=') && !AWS_GLOBAL_FLAGS.contains(&arg.as_str());
+        i += if takes_value { 2 } else { 1 };
+    }
+    false
+}
+
+/// Read static credentials of a profile from the INI formatted shared credentials file
+fn profile_credentials(path: &Path, profile: &str) -> Result<Credentials, Error> {
+    let data =
+        std::fs::read_to_string(path).map_err(|err| Error::ReadCredentialsFile(err, path.to_owned()))?;
+    let mut section = None;
+    let mut values = HashMap::new();
+    for line in data.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            section = Some(name.trim().to_owned());
+        } else if section.as_deref() == Some(profile) {
+            if let Some((key, value)) = line.split_once('=') {
+                values.insert(key.trim().to_owned(), value.trim().to_owned());
+            }
+        }
+    }
+    match (
+        values.remove("aws_access_key_id"),
+        values.remove("aws_secret_access_key"),
+    ) {
+        (Some(access_key_id), Some(secret_access_key)) => Ok(Credentials {
+            access_key_id,
+            secret_access_key: secret_access_key.into(),
+            session_token: values.remove("aws_session_token").map(SecretString::from),
+            expiration: None,
+        }),
+        _ => Err(Error::MissingProfileCredentials(profile.to_owned())),
+    }
+}
+
+// Exchange the web identity token for temporary credentials. This call does not need to be signed.
+async fn assume_role_with_web_identity(
+    region: &str,
+    token_file: &Path,
+    role_arn: &str,
+    session_name: &str,
+) -> Result<Credentials, Error> {
+    let web_identity_token = std::fs::read_to_string(token_file)
+        .map_err(|err| Error::ReadWebIdentityToken(err, token_file.to_owned()))?;
+    let body = form_urlencoded::Serializer::new(String::new())
+        .append_pair("Action", "AssumeRoleWithWebIdentity")
+        .append_pair("Version", STS_VERSION)
+        .append_pair("RoleArn", role_arn)
+        .append_pair("RoleSessionName", session_name)
+        .append_pair("WebIdentityToken", web_identity_token.trim())
+        .finish();
+    let request = http::Request::post(format!("https://{}/", sts_host(region)))
+        .header(http::header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body(Body::from(body.into_bytes()))
+        .map_err(Error::BuildRequest)?;
+
+    #[cfg(not(any(feature = "rustls-tls", feature = "openssl-tls")))]
+    compile_error!(
+        "At least one of rustls-tls or openssl-tls feature must be enabled to use aws-eks feature"
+    );
+    // Current TLS feature precedence when more than one are set:
+    // 1. rustls-tls
+    // 2. openssl-tls
+    #[cfg(all(feature = "rustls-tls", not(feature = "webpki-roots")))]
+    let https = hyper_rustls::HttpsConnectorBuilder::new()
+        .with_native_roots()
+        .map_err(Error::NoValidNativeRootCA)?
+        .https_only()
+        .enable_http1()
+        .build();
+    #[cfg(all(feature = "rustls-tls", feature = "webpki-roots"))]
+    let https = hyper_rustls::HttpsConnectorBuilder::new()
+        .with_webpki_roots()
+        .https_only()
+        .enable_http1()
+        .build();
+    #[cfg(all(not(feature = "rustls-tls"), feature = "openssl-tls"))]
+    let https =
+        hyper_openssl::client::legacy::HttpsConnector::new().map_err(Error::CreateOpensslHttpsConnector)?;
+
+    let client = hyper_util::client::legacy::Client::builder(TokioExecutor::new()).build(https);
+    let res = client.request(request).await.map_err(Error::RequestCredentials)?;
+    let status = res.status();
+    let bytes = res
+        .into_body()
+        .collect()
+        .await
+        .map_err(Error::ConcatBuffers)?
+        .to_bytes();
+    let text = String::from_utf8_lossy(&bytes);
+    if !status.is_success() {
+        return Err(Error::AssumeRoleWithWebIdentity(status, text.into_owned()));
+    }
+    parse_assume_role_response(&text)
+}
+
+fn parse_assume_role_response(xml: &str) -> Result<Credentials, Error> {
+    // The response is small and flat enough to not need an XML parser
+    let field = |name: &'static str| {
+        let start = xml.find(&format!("<{name}>"))? + name.len() + 2;
+        let end = start + xml[start..].find(&format!("</{name}>"))?;
+        Some(xml[start..end].trim().to_owned())
+    };
+    let expiration = field("Expiration")
+        .ok_or(Error::MissingResponseField("Expiration"))?
+        .parse()
+        .map_err(Error::MalformedExpirationDate)?;
+    Ok(Credentials {
+        access_key_id: field("AccessKeyId").ok_or(Error::MissingResponseField("AccessKeyId"))?,
+        secret_access_key: field("SecretAccessKey")
+            .ok_or(Error::MissingResponseField("SecretAccessKey"))?
+            .into(),
+        session_token: Some(
+            field("SessionToken")
+                .ok_or(Error::MissingResponseField("SessionToken"))?
+                .into(),
+        ),
+        expiration: Some(expiration),
+    })
+}
+
+fn sts_host(region: &str) -> String {
+    if region.starts_with("cn-") {
+        format!("sts.{region}.amazonaws.com.cn")
+    } else {
+        format!("sts.{region}.amazonaws.com")
+    }
+}
+
+// Presign `GetCallerIdentity` with SigV4, signing the cluster id header so the token is only valid for that cluster.
+// See <https://docs.aws.amazon.com/IAM/latest/UserGuide/create-signed-request.html>
+fn presigned_url(credentials: &Credentials, region: &str, cluster_id: &str, now: DateTime<Utc>) -> String {
+    let host = sts_host(region);
+    let date = now.format("%Y%m%d").to_string();
+    let timestamp = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let scope = format!("{date}/{region}/sts/aws4_request");
+    let signed_headers = format!("host;{CLUSTER_ID_HEADER}");
+
+    let mut query = vec![
+        ("Action", "GetCallerIdentity".to_owned()),
+        ("Version", STS_VERSION.to_owned()),
+        ("X-Amz-Algorithm", "AWS4-HMAC-SHA256".to_owned()),
+        (
+            "X-Amz-Credential",
+            format!("{}/{scope}", credentials.access_key_id),
+        ),
+        ("X-Amz-Date", timestamp.clone()),
+        ("X-Amz-Expires", "60".to_owned()),
+        ("X-Amz-SignedHeaders", signed_headers.clone()),
+    ];
+    if let Some(session_token) = &credentials.session_token {
+        query.push(("X-Amz-Security-Token", session_token.expose_secret().to_owned()));
+    }
+    query.sort();
+    let query = query
+        .iter()
+        .map(|(key, value)| format!("{}={}", uri_encode(key), uri_encode(value)))
+        .collect::<Vec<_>>()
+        .join("&");
+
+    let canonical_request = format!(
+        "GET\n/\n{query}\nhost:{host}\n{CLUSTER_ID_HEADER}:{cluster_id}\n\n{signed_headers}\n{EMPTY_PAYLOAD_SHA256}"
+    );
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{timestamp}\n{scope}\n{}",
+        hex(&Sha256::digest(canonical_request))
+    );
+    let key = signing_key(
+        credentials.secret_access_key.expose_secret(),
+        &date,
+        region,
+        "sts",
+    );
+    let signature = hex(&hmac(&key, string_to_sign.as_bytes()));
+    format!("https://{host}/?{query}&X-Amz-Signature={signature}")
+}
+
+fn signing_key(secret_access_key: &str, date: &str, region: &str, service: &str) -> Vec<u8> {
+    let key = hmac(format!("AWS4{secret_access_key}").as_bytes(), date.as_bytes());
+    let key = hmac(&key, region.as_bytes());
+    let key = hmac(&key, service.as_bytes());
+    hmac(&key, b"aws4_request")
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut out, byte| {
+        let _ = write!(out, "{byte:02x}");
+        out
+    })
+}
+
+// URI encoding as specified by SigV4, everything but unreserved characters is percent-encoded
+fn uri_encode(value: &str) -> String {
+    value.bytes().fold(String::new(), |mut out, byte| {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.' | b'~') {
+            out.push(char::from(byte));
+        } else {
+            let _ = write!(out, "%{byte:02X}");
+        }
+        out
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exec(yaml: &str) -> ExecConfig {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn signing_key_matches_aws_example() {
+        // From the AWS SigV4 documentation
+        let key = signing_key(
+            "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+            "20150830",
+            "us-east-1",
+            "iam",
+        );
+        assert_eq!(
+            hex(&key),
+            "c4afb1cc5771d871763a393e44b703571b55cc28424d1a5e86da6ed3c154a4b9"
+        );
+    }
+
+    #[test]
+    fn presigned_url_signs_cluster_header() {
+        let credentials = Credentials {
+            access_key_id: "AKIDEXAMPLE".into(),
+            secret_access_key: "secret".to_owned().into(),
+            session_token: Some("session/token".to_owned().into()),
+            expiration: None,
+        };
+        let now = "2024-01-02T03:04:05Z".parse().unwrap();
+        let url = presigned_url(&credentials, "eu-west-1", "prod", now);
+        let (base, query) = url.split_once('?').unwrap();
+        assert_eq!(base, "https://sts.eu-west-1.amazonaws.com/");
+        let params = form_urlencoded::parse(query.as_bytes())
+            .into_owned()
+            .collect::<HashMap<_, _>>();
+        assert_eq!(params["Action"], "GetCallerIdentity");
+        assert_eq!(
+            params["X-Amz-Credential"],
+            "AKIDEXAMPLE/20240102/eu-west-1/sts/aws4_request"
+        );
+        assert_eq!(params["X-Amz-Date"], "20240102T030405Z");
+        assert_eq!(params["X-Amz-SignedHeaders"], "host;x-k8s-aws-id");
+        assert_eq!(params["X-Amz-Security-Token"], "session/token");
+        assert_eq!(params["X-Amz-Signature"].len(), 64);
+        // Signature covers the cluster
+        assert_ne!(url, presigned_url(&credentials, "eu-west-1", "staging", now));
+    }
+
+    #[test]
+    fn recognizes_token_commands() {
+        let eks = |yaml| {
+            let mut exec = exec(yaml);
+            // Don't depend on the environment of the test process
+            exec.env = Some(vec![
+                HashMap::from([
+                    ("name".to_owned(), "AWS_ACCESS_KEY_ID".to_owned()),
+                    ("value".to_owned(), "AKID".to_owned()),
+                ]),
+                HashMap::from([
+                    ("name".to_owned(), "AWS_SECRET_ACCESS_KEY".to_owned()),
+                    ("value".to_owned(), "secret".to_owned()),
+                ]),
+            ]);
+            Eks::from_exec(&exec).map(|eks| (eks.cluster_id, eks.region))
+        };
+        assert_eq!(
+            eks("command: aws\nargs: [eks, get-token, --cluster-name, prod, --region, eu-west-1]"),
+            Some(("prod".into(), "eu-west-1".into()))
+        );
+        assert_eq!(
+            eks("command: /usr/local/bin/aws-iam-authenticator\nargs: [token, -i, prod, --region=us-west-2]"),
+            Some(("prod".into(), "us-west-2".into()))
+        );
+        // As written by `aws eks update-kubeconfig`
+        assert_eq!(
+            eks("command: aws\nargs: [--region, eu-west-1, eks, get-token, --cluster-name, prod, --output, json]"),
+            Some(("prod".into(), "eu-west-1".into()))
+        );
+        assert_eq!(
+            eks("command: aws\nargs: [--debug, --region=us-west-2, eks, get-token, --cluster-name, prod]"),
+            Some(("prod".into(), "us-west-2".into()))
+        );
+        assert!(eks("command: aws\nargs: [eks, get-token, --cluster-name, prod, --role-arn, arn]").is_none());
+        assert!(
+            eks("command: aws\nargs: [--region, eu-west-1, eks, describe-cluster, --name, prod]").is_none()
+        );
+        assert!(eks("command: kubelogin\nargs: [get-token]").is_none());
+    }
+
+    #[test]
+    fn reads_profile_credentials() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(
+            file.path(),
+            "[default]\naws_access_key_id = A\naws_secret_access_key = B\n\n[ci]\naws_access_key_id=C\naws_secret_access_key=D\naws_session_token=E\n",
+        )
+        .unwrap();
+        let ci = profile_credentials(file.path(), "ci").unwrap();
+        assert_eq!(ci.access_key_id, "C");
+        assert_eq!(ci.session_token.unwrap().expose_secret(), "E");
+        assert!(matches!(
+            profile_credentials(file.path(), "sso"),
+            Err(Error::MissingProfileCredentials(_))
+        ));
+    }
+
+    #[test]
+    fn parses_assume_role_response() {
+        let xml = r#"<AssumeRoleWithWebIdentityResponse xmlns="https://sts.amazonaws.com/doc/2011-06-15/">
+          <AssumeRoleWithWebIdentityResult>
+            <Credentials>
+              <AccessKeyId>ASIA</AccessKeyId>
+              <SecretAccessKey>secret</SecretAccessKey>
+              <SessionToken>token</SessionToken>
+              <Expiration>2024-01-02T04:04:05Z</Expiration>
+            </Credentials>
+          </AssumeRoleWithWebIdentityResult>
+        </AssumeRoleWithWebIdentityResponse>"#;
+        let credentials = parse_assume_role_response(xml).unwrap();
+        assert_eq!(credentials.access_key_id, "ASIA");
+        assert_eq!(
+            credentials.expiration.unwrap().to_rfc3339(),
+            "2024-01-02T04:04:05+00:00"
+        );
+    }
+}
@@ -0,0 +1,215 @@
+//! Persisting exec plugin credentials between processes
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+
+use super::{ExecCredential, SIXTY_SEC};
+use crate::config::ExecConfig;
+
+/// Load unexpired credentials persisted by a previous invocation of the plugin
+pub(super) fn load(auth: &ExecConfig) -> Option<ExecCredential> {
+    let path = cache_file(auth.credential_cache.as_ref()?, auth);
+    let data = fs::read(&path).ok()?;
+    let creds: ExecCredential = match serde_json::from_slice(&data) {
+        Ok(creds) => creds,
+        Err(err) => {
+            tracing::debug!(
+                ?path,
+                error = &err as &dyn std::error::Error,
+                "ignoring invalid cached credentials"
+            );
+            return None;
+        }
+    };
+    // Leave the same wiggle room as the in-process refresh, so cached credentials are never refreshed right away
+    let expiration = expiration(&creds)?;
+    (expiration > Utc::now() + SIXTY_SEC).then_some(creds)
+}
+
+/// Persist credentials returned by the plugin, if they expire
+pub(super) fn store(auth: &ExecConfig, creds: &ExecCredential) {
+    let Some(dir) = &auth.credential_cache else {
+        return;
+    };
+    // Credentials without expiry are only valid for the lifetime of the process
+    if expiration(creds).is_none() {
+        return;
+    }
+    let path = cache_file(dir, auth);
+    if let Err(err) = write(dir, &path, creds) {
+        tracing::warn!(
+            ?path,
+            error = &err as &dyn std::error::Error,
+            "failed to cache exec credentials"
+        );
+    }
+}
+
+/// Drop cached credentials with a token the apiserver rejected
+///
+/// Credentials another process already replaced them with are kept.
+pub(super) fn remove(auth: &ExecConfig, token: &str) {
+    let Some(dir) = &auth.credential_cache else {
+        return;
+    };
+    let path = cache_file(dir, auth);
+    let cached = fs::read(&path)
+        .ok()
+        .and_then(|data| serde_json::from_slice::<ExecCredential>(&data).ok());
+    if cached
+        .as_ref()
+        .and_then(|creds| creds.status.as_ref()?.token.as_deref())
+        != Some(token)
+    {
+        return;
+    }
+    if let Err(err) = fs::remove_file(&path) {
+        tracing::warn!(
+            ?path,
+            error = &err as &dyn std::error::Error,
+            "failed to remove rejected exec credentials"
+        );
+    }
+}
+
+fn expiration(creds: &ExecCredential) -> Option<DateTime<Utc>> {
+    creds.status.as_ref()?.expiration_timestamp.as_ref()?.parse().ok()
+}
+
+fn write(dir: &Path, path: &Path, creds: &ExecCredential) -> std::io::Result<()> {
+    // The credentials are stored in plaintext, keep them away from other users
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    builder.create(dir)?;
+    // Write to a temporary file and rename, so concurrent processes never read a partial file
+    let tmp = path.with_extension(format!("tmp{}", std::process::id()));
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(&tmp)?;
+    file.write_all(&serde_json::to_vec(creds)?)?;
+    drop(file);
+    fs::rename(&tmp, path)
+}
+
+/// The cache file for a plugin, keyed on everything that affects its output
+fn cache_file(dir: &Path, auth: &ExecConfig) -> PathBuf {
+    let env = auth
+        .env
+        .iter()
+        .flatten()
+        .map(|env| (env.get("name"), env.get("value")))
+        .collect::<Vec<_>>();
+    let server = auth.cluster.as_ref().and_then(|cluster| cluster.server.as_ref());
+    let key = serde_json::to_vec(&(&auth.api_version, &auth.command, &auth.args, env, server))
+        .expect("serializing strings cannot fail");
+    dir.join(format!("{:016x}.json", fnv1a(&key)))
+}
+
+// A hash that is stable across processes and toolchains, unlike `DefaultHasher`
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::auth::ExecCredentialStatus;
+
+    fn exec_config(dir: &Path, args: &[&str]) -> ExecConfig {
+        serde_yaml::from_str::<ExecConfig>(&format!(
+            "command: kubelogin\nargs: {args:?}\napiVersion: client.authentication.k8s.io/v1"
+        ))
+        .map(|exec| ExecConfig {
+            credential_cache: Some(dir.to_owned()),
+            ..exec
+        })
+        .unwrap()
+    }
+
+    fn credential(token: &str, expiration: Option<DateTime<Utc>>) -> ExecCredential {
+        ExecCredential {
+            kind: Some("ExecCredential".into()),
+            api_version: Some("client.authentication.k8s.io/v1".into()),
+            spec: None,
+            status: Some(ExecCredentialStatus {
+                expiration_timestamp: expiration.map(|e| e.to_rfc3339()),
+                token: Some(token.into()),
+                client_certificate_data: None,
+                client_key_data: None,
+            }),
+        }
+    }
+
+    #[test]
+    fn exec_credentials_roundtrip() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("cache").join("exec");
+        let auth = exec_config(&dir, &["get-token"]);
+        assert!(load(&auth).is_none());
+
+        store(
+            &auth,
+            &credential("abc", Some(Utc::now() + chrono::Duration::hours(1))),
+        );
+        let cached = load(&auth).unwrap();
+        assert_eq!(cached.status.unwrap().token.as_deref(), Some("abc"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&dir).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o700);
+        }
+
+        // Different arguments may return different credentials
+        assert!(load(&exec_config(&dir, &["get-token", "--tenant", "other"])).is_none());
+    }
+
+    #[test]
+    fn exec_credentials_skip_expiring() {
+        let dir = tempfile::tempdir().unwrap();
+        let auth = exec_config(dir.path(), &[]);
+
+        store(&auth, &credential("no-expiry", None));
+        assert!(load(&auth).is_none());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+
+        store(
+            &auth,
+            &credential("expiring", Some(Utc::now() + chrono::Duration::seconds(30))),
+        );
+        assert!(load(&auth).is_none());
+    }
+
+    #[test]
+    fn exec_credentials_remove_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let auth = exec_config(dir.path(), &[]);
+        store(
+            &auth,
+            &credential("abc", Some(Utc::now() + chrono::Duration::hours(1))),
+        );
+
+        // Another process already replaced the rejected token
+        remove(&auth, "old");
+        assert!(load(&auth).is_some());
+
+        remove(&auth, "abc");
+        assert!(load(&auth).is_none());
+    }
+}
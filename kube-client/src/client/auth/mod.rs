@@ -1,4 +1,5 @@
 use std::{
+    io::IsTerminal,
     path::{Path, PathBuf},
     process::Command,
     sync::Arc,
@@ -19,6 +20,9 @@ use tower::{filter::AsyncPredicate, BoxError};
 
 use crate::config::{AuthInfo, AuthProviderConfig, ExecAuthCluster, ExecConfig, ExecInteractiveMode};
 
+#[cfg(feature = "aws-eks")] mod eks;
+#[cfg(feature = "aws-eks")] pub use eks::Error as EksError;
+mod exec_cache;
 #[cfg(feature = "oauth")] mod oauth;
 #[cfg(feature = "oauth")] pub use oauth::Error as OAuthError;
 #[cfg(feature = "oidc")] mod oidc;
@@ -99,6 +103,16 @@ pub enum Error {
     #[error("failed OIDC: {0}")]
     Oidc(#[source] oidc_errors::Error),
 
+    /// Exec plugin requires interactive mode, but stdin is not a terminal
+    #[error("exec plugin requires interactive mode, but stdin is not a terminal")]
+    ExecInteractiveUnavailable,
+
+    /// EKS token error
+    #[cfg(feature = "aws-eks")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "aws-eks")))]
+    #[error("failed EKS token generation: {0}")]
+    Eks(#[source] EksError),
+
     /// cluster spec missing while `provideClusterInfo` is true
     #[error("Cluster spec must be populated when `provideClusterInfo` is true")]
     ExecMissingClusterInfo,
@@ -196,6 +210,8 @@ pub enum RefreshableToken {
     GcpOauth(Arc<Mutex<oauth::Gcp>>),
     #[cfg(feature = "oidc")]
     Oidc(Arc<Mutex<oidc::Oidc>>),
+    #[cfg(feature = "aws-eks")]
+    Eks(Arc<Mutex<eks::Eks>>),
 }
 
 // For use with `AsyncFilterLayer` to add `Authorization` header with a refreshed token.
@@ -246,6 +262,8 @@ impl RefreshableToken {
                         Auth::RefreshableToken(RefreshableToken::GcpOauth(_)) => unreachable!(),
                         #[cfg(feature = "oidc")]
                         Auth::RefreshableToken(RefreshableToken::Oidc(_)) => unreachable!(),
+                        #[cfg(feature = "aws-eks")]
+                        Auth::RefreshableToken(RefreshableToken::Eks(_)) => unreachable!(),
                     }
                 }
 
@@ -276,6 +294,28 @@ impl RefreshableToken {
                 let token = oidc.lock().await.id_token().await.map_err(Error::Oidc)?;
                 bearer_header(&token)
             }
+
+            #[cfg(feature = "aws-eks")]
+            RefreshableToken::Eks(eks) => {
+                let token = eks.lock().await.token().await.map_err(Error::Eks)?;
+                bearer_header(token.expose_secret())
+            }
+        }
+    }
+
+    /// Drop an exec token the apiserver rejected, so the plugin runs again for the next request
+    ///
+    /// Requests sent before a refresh can be rejected after it, the refreshed token is kept then.
+    pub(crate) async fn invalidate(&self, rejected: &HeaderValue) {
+        if let RefreshableToken::Exec(data) = self {
+            let mut locked_data = data.lock().await;
+            if bearer_header(locked_data.0.expose_secret()).ok().as_ref() != Some(rejected) {
+                return;
+            }
+            if let Some(exec) = &locked_data.2.exec {
+                exec_cache::remove(exec, locked_data.0.expose_secret());
+            }
+            locked_data.1 = Utc::now();
         }
     }
 }
@@ -348,6 +388,14 @@ impl TryFrom<&AuthInfo> for Auth {
         }
 
         if let Some(exec) = &auth_info.exec {
+            // Generate EKS tokens natively rather than running the AWS CLI
+            #[cfg(feature = "aws-eks")]
+            if let Some(eks) = eks::Eks::from_exec(exec) {
+                return Ok(Self::RefreshableToken(RefreshableToken::Eks(Arc::new(
+                    Mutex::new(eks),
+                ))));
+            }
+
             let creds = auth_exec(exec)?;
             let status = creds.status.ok_or(Error::ExecPluginFailed)?;
             let expiration = status
@@ -567,6 +615,15 @@ pub struct ExecCredentialStatus {
 }
 
 fn auth_exec(auth: &ExecConfig) -> Result<ExecCredential, Error> {
+    if let Some(creds) = exec_cache::load(auth) {
+        return Ok(creds);
+    }
+    let creds = run_exec(auth)?;
+    exec_cache::store(auth, &creds);
+    Ok(creds)
+}
+
+fn run_exec(auth: &ExecConfig) -> Result<ExecCredential, Error> {
     let mut cmd = match &auth.command {
         Some(cmd) => Command::new(cmd),
         None => return Err(Error::MissingCommand),
@@ -585,9 +642,19 @@ fn auth_exec(auth: &ExecConfig) -> Result<ExecCredential, Error> {
         cmd.envs(envs);
     }
 
-    let interactive = auth.interactive_mode != Some(ExecInteractiveMode::Never);
+    // Same as client-go, plugins are only interactive when there is a terminal to interact with
+    let interactive = match auth.interactive_mode {
+        Some(ExecInteractiveMode::Never) => false,
+        Some(ExecInteractiveMode::Always) if !std::io::stdin().is_terminal() => {
+            return Err(Error::ExecInteractiveUnavailable);
+        }
+        Some(ExecInteractiveMode::Always) => true,
+        Some(ExecInteractiveMode::IfAvailable) | None => std::io::stdin().is_terminal(),
+    };
     if interactive {
+        // Prompts are written to stderr, so the user needs to see it
         cmd.stdin(std::process::Stdio::inherit());
+        cmd.stderr(std::process::Stdio::inherit());
     } else {
         cmd.stdin(std::process::Stdio::piped());
     }
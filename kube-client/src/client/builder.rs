@@ -105,7 +105,12 @@ impl TryFrom<Config> for ClientBuilder<GenericService> {
             }
         }
 
-        match config.proxy_url.as_ref() {
+        if let Some(path) = config.unix_socket() {
+            return unix_socket_builder(path, config);
+        }
+
+        let proxy_url = config.proxy();
+        match proxy_url.as_ref() {
             Some(proxy_url) if proxy_url.scheme_str() == Some("socks5") => {
                 #[cfg(feature = "socks5")]
                 {
@@ -118,7 +123,7 @@ impl TryFrom<Config> for ClientBuilder<GenericService> {
                 }
 
                 #[cfg(not(feature = "socks5"))]
-                Err(Error::ProxyProtocolDisabled {
+                skip_env_proxy(connector, config, Error::ProxyProtocolDisabled {
                     proxy_url: proxy_url.clone(),
                     protocol_feature: "kube/socks5",
                 })
@@ -135,13 +140,13 @@ impl TryFrom<Config> for ClientBuilder<GenericService> {
                 }
 
                 #[cfg(not(feature = "http-proxy"))]
-                Err(Error::ProxyProtocolDisabled {
+                skip_env_proxy(connector, config, Error::ProxyProtocolDisabled {
                     proxy_url: proxy_url.clone(),
                     protocol_feature: "kube/http-proxy",
                 })
             }
 
-            Some(proxy_url) => Err(Error::ProxyProtocolUnsupported {
+            Some(proxy_url) => skip_env_proxy(connector, config, Error::ProxyProtocolUnsupported {
                 proxy_url: proxy_url.clone(),
             }),
 
@@ -150,6 +155,44 @@ impl TryFrom<Config> for ClientBuilder<GenericService> {
     }
 }
 
+/// Connect directly when a proxy from the environment cannot be used, since they apply to every program
+///
+/// Proxies configured explicitly through [`Config::proxy_url`] fail with `err` instead.
+fn skip_env_proxy(
+    connector: HttpConnector,
+    config: Config,
+    err: Error,
+) -> Result<ClientBuilder<GenericService>, Error> {
+    if config.proxy_url.is_some() {
+        return Err(err);
+    }
+    tracing::warn!(
+        error = &err as &dyn std::error::Error,
+        "ignoring proxy from the environment"
+    );
+    make_generic_builder(connector, config)
+}
+
+/// Connect to an apiserver listening on a Unix domain socket, which never uses TLS or proxies
+#[cfg(unix)]
+fn unix_socket_builder(
+    path: std::path::PathBuf,
+    mut config: Config,
+) -> Result<ClientBuilder<GenericService>> {
+    // The socket path is not a meaningful host, and `kubectl proxy` only accepts requests for localhost
+    config
+        .headers
+        .push((http::header::HOST, http::HeaderValue::from_static("localhost")));
+    build_with_connector(super::unix_socket::UnixConnector::new(path), config)
+}
+
+#[cfg(not(unix))]
+fn unix_socket_builder(path: std::path::PathBuf, _config: Config) -> Result<ClientBuilder<GenericService>> {
+    Err(Error::Service(
+        format!("cannot connect to {path:?}: Unix domain sockets are only supported on Unix").into(),
+    ))
+}
+
 /// Helper function for implementation of [`TryFrom<Config>`] for [`ClientBuilder`].
 /// Ignores [`Config::proxy_url`], which at this point is already handled.
 fn make_generic_builder<H>(connector: H, config: Config) -> Result<ClientBuilder<GenericService>, Error>
@@ -158,26 +201,37 @@ where
     H::Response: 'static + Connection + Read + Write + Send + Unpin,
     H::Future: 'static + Send,
     H::Error: 'static + Send + Sync + std::error::Error,
+{
+    // Current TLS feature precedence when more than one are set:
+    // 1. rustls-tls
+    // 2. openssl-tls
+    // Create a custom client to use something else.
+    // If TLS features are not enabled, http connector will be used.
+    #[cfg(feature = "rustls-tls")]
+    let connector = config.rustls_https_connector_with_connector(connector)?;
+    #[cfg(all(not(feature = "rustls-tls"), feature = "openssl-tls"))]
+    let connector = config.openssl_https_connector_with_connector(connector)?;
+    #[cfg(all(not(feature = "rustls-tls"), not(feature = "openssl-tls")))]
+    if config.cluster_url.scheme() == Some(&http::uri::Scheme::HTTPS) {
+        // no tls stack situation only works with http scheme
+        return Err(Error::TlsRequired);
+    }
+
+    build_with_connector(connector, config)
+}
+
+/// Build the default stack on top of a connector, which is responsible for any TLS
+fn build_with_connector<H>(connector: H, config: Config) -> Result<ClientBuilder<GenericService>, Error>
+where
+    H: 'static + Clone + Send + Sync + Service<http::Uri>,
+    H::Response: 'static + Connection + Read + Write + Send + Unpin,
+    H::Future: 'static + Send,
+    H::Error: Into<BoxError>,
 {
     let default_ns = config.default_namespace.clone();
     let auth_layer = config.auth_layer()?;
 
     let client: hyper_util::client::legacy::Client<_, Body> = {
-        // Current TLS feature precedence when more than one are set:
-        // 1. rustls-tls
-        // 2. openssl-tls
-        // Create a custom client to use something else.
-        // If TLS features are not enabled, http connector will be used.
-        #[cfg(feature = "rustls-tls")]
-        let connector = config.rustls_https_connector_with_connector(connector)?;
-        #[cfg(all(not(feature = "rustls-tls"), feature = "openssl-tls"))]
-        let connector = config.openssl_https_connector_with_connector(connector)?;
-        #[cfg(all(not(feature = "rustls-tls"), not(feature = "openssl-tls")))]
-        if config.cluster_url.scheme() == Some(&http::uri::Scheme::HTTPS) {
-            // no tls stack situation only works with http scheme
-            return Err(Error::TlsRequired);
-        }
-
         let mut connector = TimeoutConnector::new(connector);
 
         // Set the timeouts for the client
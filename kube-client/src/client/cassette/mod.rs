@@ -0,0 +1,394 @@
+//! Recording and replaying the HTTP traffic of a [`Client`](crate::Client)
+//!
+//! A [`Recorder`] is a tower [`Layer`](tower::Layer) that records every request and response against a real cluster,
+//! including watch streams and the messages of WebSocket upgrades for exec, attach and portforward.
+//! The resulting [`Cassette`] can be saved to a file, and served back by a [`Replay`] service to get deterministic tests
+//! that do not need a cluster.
+//!
+//! `Authorization` headers (and other [`Recorder::redact_header`]s) and the data of `Secret`s are redacted before anything is recorded.
+//!
+//! ```no_run
+//! use kube::{
+//!     client::{cassette::{Recorder, Replay}, ClientBuilder},
+//!     Api, Client, Config,
+//! };
+//! use k8s_openapi::api::core::v1::Pod;
+//!
+//! # async fn wrapper() -> Result<(), Box<dyn std::error::Error>> {
+//! // Record against a real cluster
+//! let recorder = Recorder::new();
+//! let client = ClientBuilder::try_from(Config::infer().await?)?
+//!     .with_layer(&recorder)
+//!     .build();
+//! Api::<Pod>::default_namespaced(client).list(&Default::default()).await?;
+//! recorder.save("tests/cassettes/list_pods.json")?;
+//!
+//! // Replay in tests
+//! let client = Client::new(Replay::load("tests/cassettes/list_pods.json")?, "default");
+//! Api::<Pod>::default_namespaced(client).list(&Default::default()).await?;
+//! # Ok(())
+//! # }
+//! ```
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use base64::Engine;
+use bytes::Bytes;
+use http::{HeaderMap, HeaderName};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+mod record;
+mod replay;
+#[cfg(feature = "ws")] mod websocket;
+
+pub use record::{Record, Recorder};
+pub use replay::{Matcher, Replay};
+
+/// Errors from recording or replaying cassettes
+#[derive(Debug, Error)]
+pub enum Error {
+    /// Failed to read a cassette
+    #[error("failed to read cassette from {1:?}: {0}")]
+    Read(#[source] std::io::Error, PathBuf),
+
+    /// Failed to write a cassette
+    #[error("failed to write cassette to {1:?}: {0}")]
+    Write(#[source] std::io::Error, PathBuf),
+
+    /// Failed to parse or serialize a cassette
+    #[error("invalid cassette: {0}")]
+    Serde(#[source] serde_json::Error),
+
+    /// No recorded interaction matches the request
+    #[error("no recorded interaction matches {method} {uri}")]
+    NoMatch {
+        /// The method of the request
+        method: String,
+        /// The path and query of the request
+        uri: String,
+    },
+
+    /// The recorded response is not a valid HTTP response
+    #[error("invalid recorded response: {0}")]
+    InvalidResponse(#[source] http::Error),
+
+    /// Failed to read the body of the request
+    #[error("failed to read request body: {0}")]
+    ReadBody(#[source] tower::BoxError),
+
+    /// Failed to set up the replayed WebSocket connection
+    #[cfg(feature = "ws")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "ws")))]
+    #[error("failed to replay upgrade: {0}")]
+    Upgrade(#[source] hyper::Error),
+}
+
+/// A recording of HTTP interactions
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Cassette {
+    /// The interactions, in the order the requests were made
+    pub interactions: Vec<Interaction>,
+}
+
+impl Cassette {
+    /// Read a cassette from a JSON file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let data = std::fs::read(path).map_err(|err| Error::Read(err, path.into()))?;
+        serde_json::from_slice(&data).map_err(Error::Serde)
+    }
+
+    /// Write the cassette to a JSON file
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let path = path.as_ref();
+        let data = serde_json::to_vec_pretty(self).map_err(Error::Serde)?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|err| Error::Write(err, path.into()))?;
+        }
+        std::fs::write(path, data).map_err(|err| Error::Write(err, path.into()))
+    }
+}
+
+/// A request and the response it got
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Interaction {
+    /// The recorded request
+    pub request: RecordedRequest,
+    /// The recorded response
+    pub response: RecordedResponse,
+    /// The WebSocket messages exchanged after an upgrade
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub messages: Vec<Message>,
+}
+
+/// A recorded HTTP request
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecordedRequest {
+    /// The request method
+    pub method: String,
+    /// The path and query of the request
+    pub uri: String,
+    /// The request headers
+    #[serde(default)]
+    pub headers: BTreeMap<String, Vec<String>>,
+    /// The request body, if not empty
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<Data>,
+}
+
+/// A recorded HTTP response
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    /// The status code
+    pub status: u16,
+    /// The response headers
+    #[serde(default)]
+    pub headers: BTreeMap<String, Vec<String>>,
+    /// The response body
+    ///
+    /// Streaming responses like watches and followed logs are recorded line by line, other bodies as a single chunk.
+    #[serde(default)]
+    pub body: Vec<Data>,
+}
+
+/// A WebSocket message after an upgrade
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Message {
+    /// The side that sent the message
+    pub from: Peer,
+    /// The type of the frame
+    pub opcode: Opcode,
+    /// Whether this is the final frame of the message
+    #[serde(default = "default_fin", skip_serializing_if = "is_fin")]
+    pub fin: bool,
+    /// The payload of the frame
+    pub data: Data,
+}
+
+fn default_fin() -> bool {
+    true
+}
+
+#[allow(clippy::trivially_copy_pass_by_ref)]
+fn is_fin(fin: &bool) -> bool {
+    *fin
+}
+
+/// A side of a WebSocket connection
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Peer {
+    /// The client
+    Client,
+    /// The apiserver
+    Server,
+}
+
+/// The type of a WebSocket frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Opcode {
+    /// Continues a fragmented message
+    Continuation,
+    /// A text frame
+    Text,
+    /// A binary frame
+    Binary,
+    /// A close frame
+    Close,
+    /// A ping frame
+    Ping,
+    /// A pong frame
+    Pong,
+}
+
+/// Recorded bytes, stored as text when they are valid UTF-8
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Data {
+    /// UTF-8 data
+    Text(String),
+    /// Other data
+    Binary {
+        /// The base64 encoded data
+        base64: String,
+    },
+}
+
+impl Data {
+    fn from_bytes(bytes: &[u8]) -> Self {
+        match std::str::from_utf8(bytes) {
+            Ok(text) => Self::Text(text.to_owned()),
+            Err(_) => Self::Binary {
+                base64: base64::engine::general_purpose::STANDARD.encode(bytes),
+            },
+        }
+    }
+
+    /// The recorded bytes
+    pub fn to_bytes(&self) -> Bytes {
+        match self {
+            Self::Text(text) => Bytes::from(text.clone()),
+            // Recorded data is always valid, so only hand edited cassettes can end up here
+            Self::Binary { base64 } => base64::engine::general_purpose::STANDARD
+                .decode(base64)
+                .map(Bytes::from)
+                .unwrap_or_default(),
+        }
+    }
+}
+
+const REDACTED: &str = "REDACTED";
+const LAST_APPLIED: &str = "kubectl.kubernetes.io/last-applied-configuration";
+
+/// Headers that are always redacted
+const SENSITIVE_HEADERS: [HeaderName; 4] = [
+    http::header::AUTHORIZATION,
+    http::header::PROXY_AUTHORIZATION,
+    http::header::COOKIE,
+    http::header::SET_COOKIE,
+];
+
+fn record_headers(headers: &HeaderMap, redact: &[HeaderName]) -> BTreeMap<String, Vec<String>> {
+    let mut recorded = BTreeMap::<String, Vec<String>>::new();
+    for (name, value) in headers {
+        let value = if SENSITIVE_HEADERS.contains(name) || redact.contains(name) {
+            REDACTED.to_owned()
+        } else {
+            String::from_utf8_lossy(value.as_bytes()).into_owned()
+        };
+        recorded.entry(name.to_string()).or_default().push(value);
+    }
+    recorded
+}
+
+/// Redact the data of any `Secret` in a JSON body, as well as of bodies sent to the `secrets` resource
+fn redact_body(path: &str, body: &[u8]) -> Data {
+    let Ok(mut value) = serde_json::from_slice::<Value>(body) else {
+        return Data::from_bytes(body);
+    };
+    if path.split('/').any(|segment| segment == "secrets") {
+        redact_secret(&mut value);
+    }
+    redact_secrets(&mut value);
+    let mut redacted = serde_json::to_vec(&value).expect("serializing json values cannot fail");
+    if body.ends_with(b"\n") {
+        redacted.push(b'\n');
+    }
+    Data::from_bytes(&redacted)
+}
+
+fn redact_secrets(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            match map.get("kind").and_then(Value::as_str) {
+                Some("Secret") => redact_secret_data(map),
+                Some("SecretList") => {
+                    for item in map
+                        .get_mut("items")
+                        .and_then(Value::as_array_mut)
+                        .into_iter()
+                        .flatten()
+                    {
+                        redact_secret(item);
+                    }
+                }
+                _ => {}
+            }
+            map.values_mut().for_each(redact_secrets);
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact_secrets),
+        _ => {}
+    }
+}
+
+fn redact_secret(secret: &mut Value) {
+    if let Value::Object(map) = secret {
+        redact_secret_data(map);
+    }
+}
+
+fn redact_secret_data(secret: &mut serde_json::Map<String, Value>) {
+    // Keep `data` valid base64, so the redacted secret can still be deserialized
+    let redacted_data = base64::engine::general_purpose::STANDARD.encode(REDACTED);
+    for (field, redacted) in [("data", &redacted_data), ("stringData", &REDACTED.to_owned())] {
+        for value in secret
+            .get_mut(field)
+            .and_then(Value::as_object_mut)
+            .into_iter()
+            .flat_map(|data| data.values_mut())
+        {
+            *value = Value::String(redacted.clone());
+        }
+    }
+    if let Some(last_applied) = secret
+        .get_mut("metadata")
+        .and_then(|metadata| metadata.get_mut("annotations"))
+        .and_then(|annotations| annotations.get_mut(LAST_APPLIED))
+    {
+        *last_applied = Value::String(REDACTED.to_owned());
+    }
+}
+
+/// Whether the response body is a stream of lines that should be recorded as they arrive
+fn is_streaming(uri: &http::Uri) -> bool {
+    uri.query()
+        .into_iter()
+        .flat_map(|query| query.split('&'))
+        .any(|pair| pair == "watch=true" || pair == "watch=1" || pair == "follow=true")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn cassette_redacts_secrets() {
+        let body = json!({
+            "kind": "SecretList",
+            "items": [{
+                "metadata": { "name": "a", "annotations": { LAST_APPLIED: "{\"data\":{\"password\":\"aHVudGVyMg==\"}}" } },
+                "data": { "password": "aHVudGVyMg==" },
+            }],
+        });
+        let Data::Text(redacted) = redact_body("/api/v1/secrets", body.to_string().as_bytes()) else {
+            panic!("json is text");
+        };
+        assert!(!redacted.contains("aHVudGVyMg=="), "{redacted}");
+        let redacted: k8s_openapi::List<k8s_openapi::api::core::v1::Secret> =
+            serde_json::from_str(&redacted).unwrap();
+        let data = redacted.items[0].data.as_ref().unwrap();
+        assert_eq!(data["password"].0, b"REDACTED");
+
+        // Watch events and request bodies without a kind
+        let event =
+            json!({ "type": "ADDED", "object": { "kind": "Secret", "stringData": { "token": "abc" } } });
+        let redacted = redact_body(
+            "/api/v1/namespaces/default/secrets?watch=true",
+            event.to_string().as_bytes(),
+        );
+        assert_eq!(
+            redacted.to_bytes(),
+            json!({ "type": "ADDED", "object": { "kind": "Secret", "stringData": { "token": "REDACTED" } } })
+                .to_string()
+        );
+        let patch = json!({ "data": { "token": "YWJj" } });
+        let redacted = redact_body(
+            "/api/v1/namespaces/default/secrets/token",
+            patch.to_string().as_bytes(),
+        );
+        assert!(!String::from_utf8_lossy(&redacted.to_bytes()).contains("YWJj"));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(http::header::AUTHORIZATION, "Bearer abc".parse().unwrap());
+        headers.insert(http::header::ACCEPT, "application/json".parse().unwrap());
+        let recorded = record_headers(&headers, &[]);
+        assert_eq!(recorded["authorization"], ["REDACTED"]);
+        assert_eq!(recorded["accept"], ["application/json"]);
+    }
+}
@@ -0,0 +1,286 @@
+use std::{
+    path::Path,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{ready, Context, Poll},
+};
+
+use bytes::{Bytes, BytesMut};
+use futures::future::BoxFuture;
+use http::{HeaderName, Request, Response};
+use http_body::{Body as HttpBody, Frame, SizeHint};
+use tower::{BoxError, Layer, Service};
+
+use super::{
+    is_streaming, record_headers, redact_body, Cassette, Data, Error, Interaction, Message, RecordedRequest,
+    RecordedResponse,
+};
+use crate::client::Body;
+
+/// A [`Layer`] recording all traffic of the wrapped service into a [`Cassette`]
+///
+/// The recorder is a cheap handle to the shared cassette: clone it before adding it to a client,
+/// and [`save`](Recorder::save) it after the client is done.
+///
+/// Responses are recorded as they are read, so only the part of a watch stream that was consumed ends up in the cassette.
+#[derive(Clone, Debug, Default)]
+pub struct Recorder {
+    cassette: Arc<Mutex<Cassette>>,
+    redact_headers: Arc<Vec<HeaderName>>,
+}
+
+impl Recorder {
+    /// Create a recorder with an empty cassette
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also redact the given header, in addition to `Authorization` and cookies
+    #[must_use]
+    pub fn redact_header(mut self, name: HeaderName) -> Self {
+        Arc::make_mut(&mut self.redact_headers).push(name);
+        self
+    }
+
+    /// A snapshot of what has been recorded so far
+    pub fn cassette(&self) -> Cassette {
+        self.lock().clone()
+    }
+
+    /// Write what has been recorded so far to a JSON file
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        self.cassette().save(path)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Cassette> {
+        // A panic while holding the lock cannot leave the cassette in an invalid state
+        self.cassette
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    fn push(&self, interaction: Interaction) -> usize {
+        let mut cassette = self.lock();
+        cassette.interactions.push(interaction);
+        cassette.interactions.len() - 1
+    }
+
+    fn push_body(&self, index: usize, data: Data) {
+        self.lock().interactions[index].response.body.push(data);
+    }
+
+    #[cfg_attr(not(feature = "ws"), allow(dead_code))]
+    pub(super) fn push_message(&self, index: usize, message: Message) {
+        self.lock().interactions[index].messages.push(message);
+    }
+}
+
+impl<S> Layer<S> for Recorder {
+    type Service = Record<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Record {
+            inner,
+            recorder: self.clone(),
+        }
+    }
+}
+
+/// A service recording all traffic of the inner service, created by a [`Recorder`]
+#[derive(Clone, Debug)]
+pub struct Record<S> {
+    inner: S,
+    recorder: Recorder,
+}
+
+impl<S, B> Service<Request<Body>> for Record<S>
+where
+    S: Service<Request<Body>, Response = Response<B>>,
+    S::Future: Send + 'static,
+    S::Error: Into<BoxError>,
+    B: HttpBody<Data = Bytes> + Send + 'static,
+    B::Error: Into<BoxError>,
+{
+    type Error = BoxError;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+    type Response = Response<Body>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        let (parts, body) = request.into_parts();
+        let uri = parts
+            .uri
+            .path_and_query()
+            .map_or("/", |pq| pq.as_str())
+            .to_owned();
+        let method = parts.method.to_string();
+        let headers = record_headers(&parts.headers, &self.recorder.redact_headers);
+        let streaming = is_streaming(&parts.uri);
+        #[cfg(feature = "ws")]
+        let request_headers = parts.headers.clone();
+
+        // Requests are streamed to the inner service as usual, and recorded once they have been sent
+        let sent = Arc::new(Mutex::new(BytesMut::new()));
+        let body = Body::wrap_body(Tee {
+            inner: body,
+            sent: sent.clone(),
+        });
+        let future = self.inner.call(Request::from_parts(parts, body));
+        let recorder = self.recorder.clone();
+
+        Box::pin(async move {
+            #[cfg_attr(not(feature = "ws"), allow(unused_mut))]
+            let mut response = future.await.map_err(Into::into)?;
+            let sent = std::mem::take(&mut *sent.lock().unwrap_or_else(std::sync::PoisonError::into_inner));
+            let request = RecordedRequest {
+                method,
+                body: (!sent.is_empty()).then(|| redact_body(&uri, &sent)),
+                uri: uri.clone(),
+                headers,
+            };
+            let index = recorder.push(Interaction {
+                request,
+                response: RecordedResponse {
+                    status: response.status().as_u16(),
+                    headers: record_headers(response.headers(), &recorder.redact_headers),
+                    body: Vec::new(),
+                },
+                messages: Vec::new(),
+            });
+
+            #[cfg(feature = "ws")]
+            if response.status() == http::StatusCode::SWITCHING_PROTOCOLS {
+                let on_upgrade = hyper::upgrade::on(&mut response);
+                return super::websocket::record(
+                    &request_headers,
+                    response.headers(),
+                    on_upgrade,
+                    recorder,
+                    index,
+                )
+                .await
+                .map_err(BoxError::from);
+            }
+
+            Ok(response.map(|body| {
+                Body::wrap_body(RecordBody {
+                    inner: Box::pin(body),
+                    recorder,
+                    index,
+                    uri,
+                    streaming,
+                    buf: BytesMut::new(),
+                })
+            }))
+        })
+    }
+}
+
+/// Copies the request body as it is sent
+struct Tee {
+    inner: Body,
+    sent: Arc<Mutex<BytesMut>>,
+}
+
+impl HttpBody for Tee {
+    type Data = Bytes;
+    type Error = crate::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let frame = ready!(Pin::new(&mut self.inner).poll_frame(cx));
+        if let Some(data) = frame
+            .as_ref()
+            .and_then(|f| f.as_ref().ok())
+            .and_then(Frame::data_ref)
+        {
+            self.sent
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner)
+                .extend_from_slice(data);
+        }
+        Poll::Ready(frame)
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+}
+
+/// Records the response body as it is read
+struct RecordBody<B> {
+    inner: Pin<Box<B>>,
+    recorder: Recorder,
+    index: usize,
+    uri: String,
+    streaming: bool,
+    buf: BytesMut,
+}
+
+impl<B> RecordBody<B> {
+    fn flush_lines(&mut self) {
+        while let Some(end) = self.buf.iter().position(|b| *b == b'\n') {
+            let line = self.buf.split_to(end + 1);
+            self.recorder.push_body(self.index, redact_body(&self.uri, &line));
+        }
+    }
+
+    fn flush(&mut self) {
+        if !self.buf.is_empty() {
+            let rest = self.buf.split();
+            self.recorder.push_body(self.index, redact_body(&self.uri, &rest));
+        }
+    }
+}
+
+impl<B> HttpBody for RecordBody<B>
+where
+    B: HttpBody<Data = Bytes>,
+    B::Error: Into<BoxError>,
+{
+    type Data = Bytes;
+    type Error = BoxError;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let frame = ready!(self.inner.as_mut().poll_frame(cx));
+        match &frame {
+            Some(Ok(frame)) => {
+                if let Some(data) = frame.data_ref() {
+                    self.buf.extend_from_slice(data);
+                    if self.streaming {
+                        self.flush_lines();
+                    }
+                }
+            }
+            Some(Err(_)) | None => self.flush(),
+        }
+        Poll::Ready(frame.map(|frame| frame.map_err(Into::into)))
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+}
+
+impl<B> Drop for RecordBody<B> {
+    fn drop(&mut self) {
+        // Keep what was read of bodies that are not read to the end, like watches that are stopped
+        self.flush();
+    }
+}
@@ -0,0 +1,349 @@
+use std::{
+    convert::Infallible,
+    path::Path,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+
+use futures::future::BoxFuture;
+use http::{Request, Response};
+use http_body::Frame;
+use http_body_util::{BodyExt, StreamBody};
+use serde_json::Value;
+use tower::Service;
+
+use super::{redact_body, Cassette, Data, Error, Interaction, RecordedRequest};
+use crate::client::Body;
+
+/// Which parts of a request must match a recorded request to replay its response
+///
+/// Everything is matched by default. The query is compared regardless of the order of its parameters,
+/// and JSON bodies are compared regardless of formatting. Requests are redacted like when recording before comparing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Matcher {
+    /// Match the request method
+    pub method: bool,
+    /// Match the path of the request
+    pub path: bool,
+    /// Match the query parameters of the request
+    pub query: bool,
+    /// Match the request body
+    pub body: bool,
+}
+
+impl Default for Matcher {
+    fn default() -> Self {
+        Self {
+            method: true,
+            path: true,
+            query: true,
+            body: true,
+        }
+    }
+}
+
+impl Matcher {
+    fn matches(&self, recorded: &RecordedRequest, method: &str, uri: &str, body: Option<&Data>) -> bool {
+        let (recorded_path, recorded_query) = split_uri(&recorded.uri);
+        let (path, query) = split_uri(uri);
+        (!self.method || recorded.method.eq_ignore_ascii_case(method))
+            && (!self.path || recorded_path == path)
+            && (!self.query || recorded_query == query)
+            && (!self.body || same_body(recorded.body.as_ref(), body))
+    }
+}
+
+fn split_uri(uri: &str) -> (&str, Vec<&str>) {
+    let (path, query) = uri.split_once('?').unwrap_or((uri, ""));
+    let mut params = query.split('&').filter(|p| !p.is_empty()).collect::<Vec<_>>();
+    params.sort_unstable();
+    (path, params)
+}
+
+fn same_body(recorded: Option<&Data>, body: Option<&Data>) -> bool {
+    match (recorded, body) {
+        (None, None) => true,
+        (Some(recorded), Some(body)) => {
+            let (recorded, body) = (recorded.to_bytes(), body.to_bytes());
+            match (
+                serde_json::from_slice::<Value>(&recorded),
+                serde_json::from_slice::<Value>(&body),
+            ) {
+                (Ok(recorded), Ok(body)) => recorded == body,
+                _ => recorded == body,
+            }
+        }
+        _ => false,
+    }
+}
+
+/// A [`Service`] answering requests with the responses of a [`Cassette`], for use with [`Client::new`](crate::Client::new)
+///
+/// Each request is answered by the first recorded interaction that matches it and has not been replayed yet.
+/// Requests without a match fail with [`Error::NoMatch`].
+#[derive(Clone, Debug)]
+pub struct Replay {
+    interactions: Arc<Mutex<Vec<(Interaction, bool)>>>,
+    matcher: Matcher,
+}
+
+impl Replay {
+    /// Replay the interactions of a cassette
+    pub fn new(cassette: Cassette) -> Self {
+        Self {
+            interactions: Arc::new(Mutex::new(
+                cassette.interactions.into_iter().map(|i| (i, false)).collect(),
+            )),
+            matcher: Matcher::default(),
+        }
+    }
+
+    /// Replay the interactions of a cassette file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Cassette::load(path).map(Self::new)
+    }
+
+    /// Change which parts of requests must match
+    #[must_use]
+    pub fn matcher(mut self, matcher: Matcher) -> Self {
+        self.matcher = matcher;
+        self
+    }
+
+    /// The interactions that have not been replayed yet
+    pub fn unused(&self) -> Vec<Interaction> {
+        self.interactions
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .iter()
+            .filter(|(_, used)| !used)
+            .map(|(interaction, _)| interaction.clone())
+            .collect()
+    }
+
+    fn take(&self, method: &str, uri: &str, body: Option<&Data>) -> Option<Interaction> {
+        let mut interactions = self
+            .interactions
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let (interaction, used) = interactions
+            .iter_mut()
+            .find(|(i, used)| !used && self.matcher.matches(&i.request, method, uri, body))?;
+        *used = true;
+        Some(interaction.clone())
+    }
+}
+
+impl Service<Request<Body>> for Replay {
+    type Error = Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+    type Response = Response<Body>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        let replay = self.clone();
+        Box::pin(async move {
+            let (parts, body) = request.into_parts();
+            let body = body
+                .collect_bytes()
+                .await
+                .map_err(|err| Error::ReadBody(err.into()))?;
+            let uri = parts.uri.path_and_query().map_or("/", |pq| pq.as_str());
+            let method = parts.method.as_str();
+            let body = (!body.is_empty()).then(|| redact_body(uri, &body));
+            let interaction = replay
+                .take(method, uri, body.as_ref())
+                .ok_or_else(|| Error::NoMatch {
+                    method: method.to_owned(),
+                    uri: uri.to_owned(),
+                })?;
+
+            #[cfg(feature = "ws")]
+            if interaction.response.status == http::StatusCode::SWITCHING_PROTOCOLS.as_u16() {
+                return super::websocket::replay(&parts.headers, interaction).await;
+            }
+
+            let mut response = Response::builder().status(interaction.response.status);
+            for (name, values) in &interaction.response.headers {
+                // Redaction may have changed the length of the body
+                if name == "content-length" || name == "transfer-encoding" {
+                    continue;
+                }
+                for value in values {
+                    response = response.header(name, value);
+                }
+            }
+            let chunks = interaction
+                .response
+                .body
+                .iter()
+                .map(|data| Ok::<_, Infallible>(Frame::data(data.to_bytes())))
+                .collect::<Vec<_>>();
+            let body = StreamBody::new(futures::stream::iter(chunks));
+            response
+                .body(Body::wrap_body(body.map_err(|never| match never {})))
+                .map_err(Error::InvalidResponse)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{StreamExt, TryStreamExt};
+    use http::Request;
+    use k8s_openapi::api::core::v1::{ConfigMap, Secret};
+    use serde_json::json;
+    use tower::ServiceBuilder;
+
+    use super::*;
+    use crate::{
+        api::{ListParams, PostParams, WatchEvent, WatchParams},
+        client::cassette::Recorder,
+        Api, Client,
+    };
+
+    /// A fake apiserver for the recorder
+    async fn apiserver(request: Request<Body>) -> Result<Response<Body>, Infallible> {
+        let (parts, body) = request.into_parts();
+        let body = body.collect_bytes().await.unwrap();
+        let response = match (parts.method.as_str(), parts.uri.path()) {
+            ("GET", "/api/v1/namespaces/default/secrets/token") => json!({
+                "apiVersion": "v1",
+                "kind": "Secret",
+                "metadata": { "name": "token", "namespace": "default" },
+                "data": { "token": "c2VjcmV0" },
+            })
+            .to_string(),
+            ("POST", "/api/v1/namespaces/default/configmaps") => String::from_utf8(body.to_vec()).unwrap(),
+            ("GET", "/api/v1/namespaces/default/configmaps") => [
+                json!({ "type": "ADDED", "object": { "metadata": { "name": "a", "resourceVersion": "1" } } }),
+                json!({ "type": "MODIFIED", "object": { "metadata": { "name": "a", "resourceVersion": "2" } } }),
+            ]
+            .map(|event| format!("{event}\n"))
+            .concat(),
+            _ => unreachable!("unexpected request {parts:?}"),
+        };
+        Ok(Response::new(Body::from(response.into_bytes())))
+    }
+
+    async fn exercise(client: Client) -> Result<(), crate::Error> {
+        let secrets = Api::<Secret>::namespaced(client.clone(), "default");
+        let secret = secrets.get("token").await?;
+        assert_eq!(secret.data.unwrap()["token"].0, b"REDACTED");
+
+        let configmaps = Api::<ConfigMap>::namespaced(client, "default");
+        let mut cm = ConfigMap::default();
+        cm.metadata.name = Some("a".into());
+        configmaps.create(&PostParams::default(), &cm).await?;
+
+        let events = configmaps
+            .watch(&WatchParams::default().timeout(10), "0")
+            .await?
+            .try_collect::<Vec<_>>()
+            .await?;
+        assert!(matches!(&events[..], [
+            WatchEvent::Added(_),
+            WatchEvent::Modified(_)
+        ]));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cassette_record_and_replay() {
+        let recorder = Recorder::new();
+        let service = ServiceBuilder::new()
+            .layer(&recorder)
+            .service(tower::service_fn(apiserver));
+        let client = Client::new(service, "default");
+        // Only the recording is redacted
+        let secret = Api::<Secret>::namespaced(client.clone(), "default")
+            .get("token")
+            .await
+            .unwrap();
+        assert_eq!(secret.data.unwrap()["token"].0, b"secret");
+        let configmaps = Api::<ConfigMap>::namespaced(client, "default");
+        let mut cm = ConfigMap::default();
+        cm.metadata.name = Some("a".into());
+        configmaps.create(&PostParams::default(), &cm).await.unwrap();
+        let events = configmaps
+            .watch(&WatchParams::default().timeout(10), "0")
+            .await
+            .unwrap()
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(events.len(), 2);
+
+        let cassette = recorder.cassette();
+        assert_eq!(cassette.interactions.len(), 3);
+        assert!(cassette.interactions[1].request.body.is_some());
+        assert_eq!(
+            cassette.interactions[2].response.body.len(),
+            2,
+            "watch events are recorded separately"
+        );
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cassette.json");
+        recorder.save(&path).unwrap();
+        let saved = std::fs::read_to_string(&path).unwrap();
+        assert!(!saved.contains("c2VjcmV0"), "{saved}");
+
+        let replay = Replay::load(&path).unwrap();
+        exercise(Client::new(replay.clone(), "default")).await.unwrap();
+        assert!(replay.unused().is_empty());
+
+        // Every interaction is replayed once, and other requests never had a match
+        let client = Client::new(replay, "default");
+        let err = Api::<Secret>::namespaced(client.clone(), "default")
+            .get("token")
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("no recorded interaction matches"),
+            "{err}"
+        );
+        let err = Api::<ConfigMap>::namespaced(client, "default")
+            .list(&ListParams::default())
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("no recorded interaction matches"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn cassette_matcher() {
+        let recorded = RecordedRequest {
+            method: "PATCH".into(),
+            uri: "/api/v1/pods?fieldManager=kube&force=true".into(),
+            headers: Default::default(),
+            body: Some(Data::Text(r#"{"a":1,"b":2}"#.into())),
+        };
+        let body = Data::Text("{ \"b\": 2, \"a\": 1 }".into());
+        let matcher = Matcher::default();
+        assert!(matcher.matches(
+            &recorded,
+            "PATCH",
+            "/api/v1/pods?force=true&fieldManager=kube",
+            Some(&body)
+        ));
+        assert!(!matcher.matches(&recorded, "PATCH", "/api/v1/pods?force=true", Some(&body)));
+        assert!(!matcher.matches(
+            &recorded,
+            "PATCH",
+            "/api/v1/pods?force=true&fieldManager=kube",
+            None
+        ));
+
+        let loose = Matcher {
+            query: false,
+            body: false,
+            ..Matcher::default()
+        };
+        assert!(loose.matches(&recorded, "patch", "/api/v1/pods", None));
+        assert!(!loose.matches(&recorded, "GET", "/api/v1/pods", None));
+    }
+}
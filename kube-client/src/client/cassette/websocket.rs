@@ -0,0 +1,360 @@
+//! Recording and replaying the WebSocket connections of upgraded requests
+//!
+//! [`Client::connect`](crate::Client::connect) needs a response that hyper can upgrade, which only hyper can create.
+//! So both the recorder and the replay answer upgrade requests through an in-memory HTTP/1.1 connection
+//! with a minimal server on the other end, which hands the WebSocket frames to the real connection or the cassette.
+use bytes::Bytes;
+use http::{header, HeaderMap, Request, Response};
+use http_body_util::Empty;
+use hyper::upgrade::OnUpgrade;
+use hyper_util::rt::TokioIo;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, DuplexStream};
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+
+use super::{Data, Error, Interaction, Message, Opcode, Peer, Recorder};
+use crate::client::Body;
+
+/// Record the frames of a real upgraded connection, and give the client a connection passing them through
+pub(super) async fn record(
+    request_headers: &HeaderMap,
+    response_headers: &HeaderMap,
+    on_upgrade: OnUpgrade,
+    recorder: Recorder,
+    index: usize,
+) -> Result<Response<Body>, Error> {
+    let headers = response_headers
+        .iter()
+        .map(|(name, value)| {
+            (
+                name.as_str(),
+                String::from_utf8_lossy(value.as_bytes()).into_owned(),
+            )
+        })
+        .collect::<Vec<_>>();
+    let (response, io) = fake_upgrade(request_headers, headers).await?;
+    tokio::spawn(async move {
+        let upgraded = match on_upgrade.await {
+            Ok(upgraded) => TokioIo::new(upgraded),
+            Err(err) => {
+                tracing::warn!(error = &err as &dyn std::error::Error, "failed to record upgrade");
+                return;
+            }
+        };
+        let (real_read, real_write) = tokio::io::split(upgraded);
+        let (fake_read, fake_write) = tokio::io::split(io);
+        tokio::join!(
+            forward(fake_read, real_write, Peer::Client, &recorder, index),
+            forward(real_read, fake_write, Peer::Server, &recorder, index),
+        );
+    });
+    Ok(response)
+}
+
+/// Replay the recorded frames of an upgraded connection
+pub(super) async fn replay(
+    request_headers: &HeaderMap,
+    interaction: Interaction,
+) -> Result<Response<Body>, Error> {
+    let headers = interaction
+        .response
+        .headers
+        .iter()
+        .flat_map(|(name, values)| values.iter().map(move |value| (name.as_str(), value.clone())))
+        .collect::<Vec<_>>();
+    let (response, io) = fake_upgrade(request_headers, headers).await?;
+    tokio::spawn(play(io, interaction.messages));
+    Ok(response)
+}
+
+/// Upgrade a request over an in-memory connection, returning the server side of the upgraded connection
+async fn fake_upgrade(
+    request_headers: &HeaderMap,
+    response_headers: Vec<(&str, String)>,
+) -> Result<(Response<Body>, DuplexStream), Error> {
+    let (client_io, mut server_io) = tokio::io::duplex(64 * 1024);
+    let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(client_io))
+        .await
+        .map_err(Error::Upgrade)?;
+    tokio::spawn(connection.with_upgrades());
+
+    let mut request = Request::new(Empty::<Bytes>::new());
+    *request.headers_mut() = request_headers.clone();
+    // The accept key depends on the key of this request, not the recorded one
+    let accept = request_headers
+        .get(header::SEC_WEBSOCKET_KEY)
+        .map(|key| derive_accept_key(key.as_bytes()))
+        .unwrap_or_default();
+    let mut head = String::from("HTTP/1.1 101 Switching Protocols\r\n");
+    for (name, value) in response_headers {
+        if name != header::SEC_WEBSOCKET_ACCEPT {
+            head.push_str(&format!("{name}: {value}\r\n"));
+        }
+    }
+    head.push_str(&format!("{}: {accept}\r\n\r\n", header::SEC_WEBSOCKET_ACCEPT));
+
+    let server = async {
+        let mut request_head = Vec::new();
+        while !request_head.ends_with(b"\r\n\r\n") {
+            request_head.push(server_io.read_u8().await?);
+        }
+        server_io.write_all(head.as_bytes()).await
+    };
+    let (response, server) = tokio::join!(sender.send_request(request), server);
+    let response = response.map_err(Error::Upgrade)?;
+    if let Err(err) = server {
+        tracing::warn!(error = &err as &dyn std::error::Error, "failed to answer upgrade");
+    }
+    Ok((response.map(Body::wrap_body), server_io))
+}
+
+/// Pass bytes through, recording the frames they contain
+async fn forward(
+    mut from: impl AsyncRead + Unpin,
+    mut to: impl AsyncWrite + Unpin,
+    peer: Peer,
+    recorder: &Recorder,
+    index: usize,
+) {
+    let mut decoder = Decoder::default();
+    let mut buf = vec![0; 16 * 1024];
+    loop {
+        let read = match from.read(&mut buf).await {
+            Ok(0) | Err(_) => break,
+            Ok(read) => read,
+        };
+        if to.write_all(&buf[..read]).await.is_err() {
+            break;
+        }
+        decoder.buf.extend_from_slice(&buf[..read]);
+        while let Some(frame) = decoder.next_frame() {
+            if let Some(message) = frame.into_message(peer) {
+                recorder.push_message(index, message);
+            }
+        }
+    }
+    let _ = to.shutdown().await;
+}
+
+/// Send the recorded server frames, waiting for a frame from the client wherever the client sent one
+async fn play(mut io: DuplexStream, messages: Vec<Message>) {
+    let mut decoder = Decoder::default();
+    let mut client_closed = false;
+    for message in messages {
+        match message.from {
+            Peer::Server => {
+                if io.write_all(&encode(&message)).await.is_err() {
+                    return;
+                }
+            }
+            Peer::Client => match read_frame(&mut io, &mut decoder).await {
+                Some(frame) => client_closed |= frame.opcode == OPCODE_CLOSE,
+                None => return,
+            },
+        }
+    }
+    // Wait for the client to finish, so it gets to read everything
+    while !client_closed {
+        match read_frame(&mut io, &mut decoder).await {
+            Some(frame) => client_closed = frame.opcode == OPCODE_CLOSE,
+            None => return,
+        }
+    }
+}
+
+async fn read_frame(io: &mut DuplexStream, decoder: &mut Decoder) -> Option<Frame> {
+    loop {
+        if let Some(frame) = decoder.next_frame() {
+            return Some(frame);
+        }
+        let mut buf = [0; 4096];
+        match io.read(&mut buf).await {
+            Ok(0) | Err(_) => return None,
+            Ok(read) => decoder.buf.extend_from_slice(&buf[..read]),
+        }
+    }
+}
+
+const OPCODE_CLOSE: u8 = 0x8;
+
+struct Frame {
+    fin: bool,
+    opcode: u8,
+    payload: Vec<u8>,
+}
+
+impl Frame {
+    fn into_message(self, from: Peer) -> Option<Message> {
+        let opcode = match self.opcode {
+            0x0 => Opcode::Continuation,
+            0x1 => Opcode::Text,
+            0x2 => Opcode::Binary,
+            OPCODE_CLOSE => Opcode::Close,
+            0x9 => Opcode::Ping,
+            0xa => Opcode::Pong,
+            _ => return None,
+        };
+        Some(Message {
+            from,
+            opcode,
+            fin: self.fin,
+            data: Data::from_bytes(&self.payload),
+        })
+    }
+}
+
+/// Decodes frames as defined in RFC 6455, unmasking frames sent by the client
+#[derive(Default)]
+struct Decoder {
+    buf: Vec<u8>,
+}
+
+impl Decoder {
+    fn next_frame(&mut self) -> Option<Frame> {
+        let buf = &self.buf;
+        let [first, second, ..] = buf[..] else {
+            return None;
+        };
+        let (len, mut offset) = match second & 0x7f {
+            126 => (u64::from(u16::from_be_bytes(buf.get(2..4)?.try_into().ok()?)), 4),
+            127 => (u64::from_be_bytes(buf.get(2..10)?.try_into().ok()?), 10),
+            len => (u64::from(len), 2),
+        };
+        let mask = if second & 0x80 == 0 {
+            None
+        } else {
+            let mask: [u8; 4] = buf.get(offset..offset + 4)?.try_into().ok()?;
+            offset += 4;
+            Some(mask)
+        };
+        let end = offset.checked_add(usize::try_from(len).ok()?)?;
+        let mut payload = buf.get(offset..end)?.to_vec();
+        if let Some(mask) = mask {
+            for (i, byte) in payload.iter_mut().enumerate() {
+                *byte ^= mask[i % 4];
+            }
+        }
+        self.buf.drain(..end);
+        Some(Frame {
+            fin: first & 0x80 != 0,
+            opcode: first & 0x0f,
+            payload,
+        })
+    }
+}
+
+/// Encodes an unmasked frame, as sent by a server
+fn encode(message: &Message) -> Vec<u8> {
+    let opcode = match message.opcode {
+        Opcode::Continuation => 0x0,
+        Opcode::Text => 0x1,
+        Opcode::Binary => 0x2,
+        Opcode::Close => OPCODE_CLOSE,
+        Opcode::Ping => 0x9,
+        Opcode::Pong => 0xa,
+    };
+    let payload = message.data.to_bytes();
+    let mut frame = vec![if message.fin { 0x80 } else { 0 } | opcode];
+    match payload.len() {
+        len @ 0..=125 => frame.push(len as u8),
+        len @ 126..=0xffff => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(&payload);
+    frame
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{SinkExt, StreamExt};
+    use http::Request;
+    use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+    use super::*;
+    use crate::{
+        client::cassette::{Cassette, RecordedRequest, RecordedResponse, Replay},
+        Client,
+    };
+
+    fn cassette() -> Cassette {
+        let headers = [
+            ("connection", "Upgrade"),
+            ("upgrade", "websocket"),
+            ("sec-websocket-protocol", "v4.channel.k8s.io"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_owned(), vec![v.to_owned()]))
+        .collect();
+        let message = |from, opcode, data: &str| Message {
+            from,
+            opcode,
+            fin: true,
+            data: Data::Text(data.into()),
+        };
+        Cassette {
+            interactions: vec![Interaction {
+                request: RecordedRequest {
+                    method: "GET".into(),
+                    uri: "/api/v1/namespaces/default/pods/web/exec?command=cat&stdin=true&stdout=true".into(),
+                    headers: Default::default(),
+                    body: None,
+                },
+                response: RecordedResponse {
+                    status: 101,
+                    headers,
+                    body: vec![],
+                },
+                messages: vec![
+                    message(Peer::Server, Opcode::Binary, "\u{1}ready"),
+                    message(Peer::Client, Opcode::Binary, "\u{0}hello"),
+                    message(Peer::Server, Opcode::Binary, "\u{1}hello"),
+                ],
+            }],
+        }
+    }
+
+    async fn exec(client: Client) -> Vec<String> {
+        let request =
+            Request::get("/api/v1/namespaces/default/pods/web/exec?command=cat&stdin=true&stdout=true")
+                .body(vec![])
+                .unwrap();
+        let mut stream = client.connect(request).await.unwrap().into_stream();
+        let mut received = Vec::new();
+        while let Some(Ok(message)) = stream.next().await {
+            if let WsMessage::Binary(data) = message {
+                received.push(String::from_utf8(data.to_vec()).unwrap());
+                if received.len() == 1 {
+                    stream.send(WsMessage::binary(&b"\x00hello"[..])).await.unwrap();
+                } else {
+                    stream.close(None).await.unwrap();
+                }
+            }
+        }
+        received
+    }
+
+    #[tokio::test]
+    async fn cassette_websocket_record_and_replay() {
+        // Record the replay of a hand written cassette, which must capture the same frames
+        let recorder = Recorder::new();
+        let service = tower::ServiceBuilder::new()
+            .layer(&recorder)
+            .service(Replay::new(cassette()));
+        let received = exec(Client::new(service, "default")).await;
+        assert_eq!(received, ["\u{1}ready", "\u{1}hello"]);
+
+        let recorded = recorder.cassette();
+        let messages = &recorded.interactions[0].messages;
+        let expected = &cassette().interactions[0].messages;
+        assert_eq!(&messages[..3], &expected[..], "close frames follow the data");
+        assert_eq!(messages[3].opcode, Opcode::Close);
+
+        let received = exec(Client::new(Replay::new(recorded), "default")).await;
+        assert_eq!(received, ["\u{1}ready", "\u{1}hello"]);
+    }
+}
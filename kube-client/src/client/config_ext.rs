@@ -1,11 +1,10 @@
 use std::sync::Arc;
 
 use chrono::{DateTime, Utc};
-use http::{header::HeaderName, HeaderValue};
 #[cfg(feature = "openssl-tls")] use hyper::rt::{Read, Write};
 use hyper_util::client::legacy::connect::HttpConnector;
 use secrecy::ExposeSecret;
-use tower::{filter::AsyncFilterLayer, util::Either};
+use tower::util::Either;
 
 #[cfg(any(feature = "rustls-tls", feature = "openssl-tls"))] use super::tls;
 use super::{
@@ -155,6 +154,19 @@ pub trait ConfigExt: private::Sealed {
     fn openssl_ssl_connector_builder(&self) -> Result<openssl::ssl::SslConnectorBuilder>;
 }
 
+impl Config {
+    // Files to re-read TLS material from, the client identity only when it's not provided by an exec plugin
+    #[cfg(any(feature = "rustls-tls", feature = "openssl-tls"))]
+    fn tls_files(&self, identity_from_files: bool) -> tls::TlsFiles {
+        tls::TlsFiles {
+            identity: identity_from_files
+                .then(|| self.auth_info.identity_files())
+                .flatten(),
+            root_cert: self.root_cert_file.clone(),
+        }
+    }
+}
+
 mod private {
     pub trait Sealed {}
     impl Sealed for super::Config {}
@@ -174,32 +186,15 @@ impl ConfigExt for Config {
             Auth::Bearer(token) => Some(AuthLayer(Either::Left(
                 AddAuthorizationLayer::bearer(token.expose_secret()).as_sensitive(true),
             ))),
-            Auth::RefreshableToken(refreshable) => {
-                Some(AuthLayer(Either::Right(AsyncFilterLayer::new(refreshable))))
-            }
+            Auth::RefreshableToken(refreshable) => Some(AuthLayer(Either::Right(refreshable))),
             Auth::Certificate(_client_certificate_data, _client_key_data, _) => None,
         })
     }
 
     fn extra_headers_layer(&self) -> Result<ExtraHeadersLayer> {
         let mut headers = self.headers.clone();
-        if let Some(impersonate_user) = &self.auth_info.impersonate {
-            headers.push((
-                HeaderName::from_static("impersonate-user"),
-                HeaderValue::from_str(impersonate_user)
-                    .map_err(http::Error::from)
-                    .map_err(Error::HttpError)?,
-            ));
-        }
-        if let Some(impersonate_groups) = &self.auth_info.impersonate_groups {
-            for group in impersonate_groups {
-                headers.push((
-                    HeaderName::from_static("impersonate-group"),
-                    HeaderValue::from_str(group)
-                        .map_err(http::Error::from)
-                        .map_err(Error::HttpError)?,
-                ));
-            }
+        if let Some(impersonation) = self.impersonation() {
+            headers.extend(impersonation.headers().map_err(Error::HttpError)?);
         }
         Ok(ExtraHeadersLayer {
             headers: Arc::new(headers),
@@ -208,11 +203,15 @@ impl ConfigExt for Config {
 
     #[cfg(feature = "rustls-tls")]
     fn rustls_client_config(&self) -> Result<rustls::ClientConfig> {
-        let identity = self.exec_identity_pem().0.or_else(|| self.identity_pem());
+        let (identity, files) = match self.exec_identity_pem().0 {
+            Some(identity) => (Some(identity), self.tls_files(false)),
+            None => (self.identity_pem(), self.tls_files(true)),
+        };
         tls::rustls_tls::rustls_client_config(
             identity.as_deref(),
             self.root_cert.as_deref(),
             self.accept_invalid_certs,
+            &files,
         )
         .map_err(Error::RustlsTls)
     }
@@ -281,9 +280,26 @@ impl ConfigExt for Config {
             self.openssl_ssl_connector_builder()?,
         )
         .map_err(|e| Error::OpensslTls(tls::openssl_tls::Error::CreateHttpsConnector(e)))?;
-        if self.accept_invalid_certs {
-            https.set_callback(|ssl, _uri| {
-                ssl.set_verify(openssl::ssl::SslVerifyMode::NONE);
+        let accept_invalid_certs = self.accept_invalid_certs;
+        let files = self.tls_files(self.exec_identity_pem().0.is_none());
+        let identity = files
+            .identity
+            .map(|(cert, key)| tls::openssl_tls::ReloadingIdentity::new(cert, key));
+        let roots = files
+            .root_cert
+            .filter(|_| !accept_invalid_certs)
+            .map(tls::openssl_tls::ReloadingRoots::new);
+        if accept_invalid_certs || identity.is_some() || roots.is_some() {
+            https.set_callback(move |ssl, _uri| {
+                if accept_invalid_certs {
+                    ssl.set_verify(openssl::ssl::SslVerifyMode::NONE);
+                }
+                if let Some(identity) = &identity {
+                    identity.configure(ssl)?;
+                }
+                if let Some(roots) = &roots {
+                    roots.configure(ssl)?;
+                }
                 Ok(())
             });
         }
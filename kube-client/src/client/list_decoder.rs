@@ -0,0 +1,214 @@
+//! Incremental decoding of JSON list responses
+//!
+//! The items of a list are decoded as soon as their bytes have arrived, so only a single encoded item
+//! is buffered at a time rather than the whole response body.
+use kube_core::object::ObjectList;
+use serde::de::{DeserializeOwned, Error as _};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Mode {
+    /// Copying everything except the items into the envelope
+    Envelope,
+    /// Between the items of the `items` array, expecting what comes next
+    Items(Next),
+    /// Copying an item
+    Item,
+}
+
+/// What may come next between the items of the `items` array
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Next {
+    /// The first item or the end of the array
+    First,
+    /// A comma or the end of the array
+    Separator,
+    /// An item after a comma
+    Item,
+}
+
+/// Splits a JSON list into its items and an envelope of the list without them
+///
+/// Only the structure of the JSON and the separators between the items are checked here;
+/// the validity of the envelope and the items is checked when decoding them.
+pub(crate) struct ListDecoder<T> {
+    envelope: Vec<u8>,
+    item: Vec<u8>,
+    items: Vec<T>,
+    mode: Mode,
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+}
+
+impl<T> Default for ListDecoder<T> {
+    fn default() -> Self {
+        Self {
+            envelope: Vec::new(),
+            item: Vec::new(),
+            items: Vec::new(),
+            mode: Mode::Envelope,
+            depth: 0,
+            in_string: false,
+            escaped: false,
+        }
+    }
+}
+
+impl<T: Clone + DeserializeOwned> ListDecoder<T> {
+    /// Decode the items completed by the next chunk of the body
+    pub(crate) fn feed(&mut self, chunk: &[u8]) -> Result<(), serde_json::Error> {
+        for &byte in chunk {
+            if self.mode == Mode::Item && !self.in_string && self.depth == 2 && matches!(byte, b',' | b']') {
+                // The end of an item that is not an object or array
+                self.finish_item()?;
+            }
+            match self.mode {
+                Mode::Envelope => {
+                    if !self.in_string && byte == b'[' && self.depth == 1 && self.at_items_value() {
+                        self.mode = Mode::Items(Next::First);
+                    }
+                    self.envelope.push(byte);
+                    self.track(byte);
+                }
+                Mode::Items(next) => match (byte, next) {
+                    (b' ' | b'\t' | b'\n' | b'\r', _) => {}
+                    (b']', Next::First | Next::Separator) => {
+                        self.mode = Mode::Envelope;
+                        self.envelope.push(byte);
+                        self.track(byte);
+                    }
+                    (b',', Next::Separator) => self.mode = Mode::Items(Next::Item),
+                    (b']' | b',', _) => {
+                        return Err(serde_json::Error::custom(format!(
+                            "expected a list item, found `{}`",
+                            char::from(byte)
+                        )))
+                    }
+                    (_, Next::Separator) => {
+                        return Err(serde_json::Error::custom("expected `,` or `]` after a list item"))
+                    }
+                    _ => {
+                        self.mode = Mode::Item;
+                        self.item.push(byte);
+                        self.track(byte);
+                    }
+                },
+                Mode::Item => {
+                    self.item.push(byte);
+                    self.track(byte);
+                    if !self.in_string && self.depth == 2 && matches!(byte, b'}' | b']') {
+                        self.finish_item()?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Decode the rest of the list once the whole body has been fed
+    pub(crate) fn finish(mut self) -> Result<ObjectList<T>, serde_json::Error> {
+        let mut list: ObjectList<T> = serde_json::from_slice(&self.envelope).inspect_err(|e| {
+            tracing::warn!("{}, {:?}", String::from_utf8_lossy(&self.envelope), e);
+        })?;
+        list.items = std::mem::take(&mut self.items);
+        Ok(list)
+    }
+
+    /// Whether the envelope ends with the key of the `items` field
+    fn at_items_value(&self) -> bool {
+        let trimmed = self.envelope.trim_ascii_end();
+        trimmed
+            .strip_suffix(b":")
+            .is_some_and(|key| key.trim_ascii_end().ends_with(b"\"items\""))
+    }
+
+    fn track(&mut self, byte: u8) {
+        if self.in_string {
+            match byte {
+                _ if self.escaped => self.escaped = false,
+                b'\\' => self.escaped = true,
+                b'"' => self.in_string = false,
+                _ => {}
+            }
+            return;
+        }
+        match byte {
+            b'"' => self.in_string = true,
+            b'{' | b'[' => self.depth += 1,
+            b'}' | b']' => self.depth = self.depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+
+    fn finish_item(&mut self) -> Result<(), serde_json::Error> {
+        let item = serde_json::from_slice(&self.item).inspect_err(|e| {
+            tracing::warn!("{}, {:?}", String::from_utf8_lossy(&self.item), e);
+        })?;
+        self.items.push(item);
+        self.item.clear();
+        self.mode = Mode::Items(Next::Separator);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    fn decode(body: &[u8], chunk_size: usize) -> Result<ObjectList<Value>, serde_json::Error> {
+        let mut decoder = ListDecoder::default();
+        for chunk in body.chunks(chunk_size) {
+            decoder.feed(chunk)?;
+        }
+        decoder.finish()
+    }
+
+    #[test]
+    fn decodes_lists_in_any_chunks() {
+        let items = json!([
+            { "metadata": { "name": "a", "annotations": { "tricky": "\"items\": [\\\"]}" } } },
+            { "metadata": { "name": "b" }, "data": [[1], {}] },
+        ]);
+        // Fields of the envelope may come after the items
+        let body = format!(
+            r#"{{"kind": "PodList", "apiVersion" : "v1", "items" :
+                 [ {}, {} ] , "metadata":{{"resourceVersion":"12","continue":"next"}}}}"#,
+            items[0], items[1]
+        );
+        for chunk_size in [1, 2, 7, body.len()] {
+            let list = decode(body.as_bytes(), chunk_size).unwrap();
+            assert_eq!(list.types.kind, "PodList");
+            assert_eq!(list.metadata.resource_version.as_deref(), Some("12"));
+            assert_eq!(list.metadata.continue_.as_deref(), Some("next"));
+            assert_eq!(Value::Array(list.items), items);
+        }
+
+        let empty = decode(
+            br#"{"kind":"PodList","apiVersion":"v1","metadata":{},"items":[]}"#,
+            3,
+        )
+        .unwrap();
+        assert!(empty.items.is_empty());
+        let null = decode(
+            br#"{"kind":"PodList","apiVersion":"v1","metadata":{},"items":null}"#,
+            3,
+        )
+        .unwrap();
+        assert!(null.items.is_empty());
+        let scalars = decode(br#"{"items":[1, "a" ,true]}"#, 1).unwrap();
+        assert_eq!(scalars.items, [json!(1), json!("a"), json!(true)]);
+    }
+
+    #[test]
+    fn rejects_invalid_lists() {
+        assert!(decode(br#"{"items":[{"a":}]}"#, 4).is_err());
+        assert!(decode(br#"{"items":[{"a":1}"#, 4).is_err());
+        assert!(decode(br#"{"kind":"PodList","items":[{}]"#, 4).is_err());
+        for separators in ["[1 2]", "[{} {}]", "[,,{}]", "[{},,{}]", "[{},]", "[,]"] {
+            let body = format!(r#"{{"items":{separators}}}"#);
+            assert!(decode(body.as_bytes(), 1).is_err(), "{separators}");
+        }
+    }
+}
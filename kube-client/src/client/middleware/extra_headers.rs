@@ -3,6 +3,8 @@ use std::sync::Arc;
 use http::{header::HeaderName, request::Request, HeaderValue};
 use tower::{Layer, Service};
 
+use crate::config::Impersonation;
+
 #[derive(Clone)]
 /// Layer that adds a static set of extra headers to each request
 pub struct ExtraHeadersLayer {
@@ -40,7 +42,14 @@ where
     }
 
     fn call(&mut self, mut req: Request<ReqBody>) -> Self::Future {
-        req.headers_mut().extend(self.headers.iter().cloned());
+        // Impersonation set on the request (e.g. via `Client::impersonate`) wins over the config
+        let impersonating = req.headers().keys().any(Impersonation::is_user_header);
+        let headers = self
+            .headers
+            .iter()
+            .filter(|(name, _)| !(impersonating && Impersonation::is_impersonation_header(name)))
+            .cloned();
+        req.headers_mut().extend(headers);
         self.inner.call(req)
     }
 }
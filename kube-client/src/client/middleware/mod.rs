@@ -4,22 +4,33 @@ pub(crate) use tower_http::auth::AddAuthorizationLayer;
 
 mod base_uri;
 mod extra_headers;
+mod refresh_token;
 
 pub use base_uri::{BaseUri, BaseUriLayer};
 pub use extra_headers::{ExtraHeaders, ExtraHeadersLayer};
+pub use refresh_token::RefreshOnUnauthorized;
 
 use super::auth::RefreshableToken;
 /// Layer to set up `Authorization` header depending on the config.
-pub struct AuthLayer(pub(crate) Either<AddAuthorizationLayer, AsyncFilterLayer<RefreshableToken>>);
+pub struct AuthLayer(pub(crate) Either<AddAuthorizationLayer, RefreshableToken>);
 
 impl<S> Layer<S> for AuthLayer {
     type Service = Either<
         <AddAuthorizationLayer as Layer<S>>::Service,
-        <AsyncFilterLayer<RefreshableToken> as Layer<S>>::Service,
+        <AsyncFilterLayer<RefreshableToken> as Layer<RefreshOnUnauthorized<S>>>::Service,
     >;
 
     fn layer(&self, inner: S) -> Self::Service {
-        self.0.layer(inner)
+        match &self.0 {
+            Either::Left(layer) => Either::Left(layer.layer(inner)),
+            Either::Right(token) => {
+                let inner = RefreshOnUnauthorized {
+                    inner,
+                    token: token.clone(),
+                };
+                Either::Right(AsyncFilterLayer::new(token.clone()).layer(inner))
+            }
+        }
     }
 }
 
@@ -39,7 +50,7 @@ mod tests {
 
     use crate::{
         client::{AuthError, Body},
-        config::AuthInfo,
+        config::{AuthInfo, ExecConfig},
     };
 
     #[tokio::test(flavor = "current_thread")]
@@ -86,6 +97,58 @@ mod tests {
         ));
     }
 
+    #[cfg(unix)]
+    #[tokio::test(flavor = "current_thread")]
+    async fn rejected_exec_token_is_refreshed() {
+        let cache = tempfile::tempdir().unwrap();
+        let expiry = Utc::now() + Duration::try_seconds(60 * 60).unwrap();
+        let creds = serde_json::json!({
+            "apiVersion": "client.authentication.k8s.io/v1",
+            "kind": "ExecCredential",
+            "status": { "token": "new", "expirationTimestamp": expiry.to_rfc3339() },
+        });
+        let exec = serde_json::from_value::<ExecConfig>(serde_json::json!({
+            "apiVersion": "client.authentication.k8s.io/v1",
+            "command": "echo",
+            "args": [creds.to_string()],
+        }))
+        .unwrap();
+        let info = AuthInfo {
+            exec: Some(ExecConfig {
+                credential_cache: Some(cache.path().to_owned()),
+                ..exec
+            }),
+            ..Default::default()
+        };
+        let token = RefreshableToken::Exec(Arc::new(Mutex::new(("old".into(), expiry, info))));
+        let (mut service, handle): (_, Handle<Request<Body>, Response<Body>>) =
+            mock::spawn_layer(AuthLayer(Either::Right(token)));
+
+        let spawned = tokio::spawn(async move {
+            let mut handle = pin!(handle);
+            for (token, status) in [("old", 401), ("new", 200)] {
+                let (request, send) = handle.next_request().await.expect("service not called");
+                assert_eq!(
+                    request.headers().get(AUTHORIZATION).unwrap(),
+                    HeaderValue::try_from(format!("Bearer {token}")).unwrap()
+                );
+                send.send_response(Response::builder().status(status).body(Body::empty()).unwrap());
+            }
+        });
+
+        for status in [401, 200] {
+            assert_ready_ok!(service.poll_ready());
+            let res = service
+                .call(Request::builder().uri("/").body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(res.status(), status);
+        }
+        spawned.await.unwrap();
+        // The refreshed credentials are cached for other processes
+        assert_eq!(std::fs::read_dir(cache.path()).unwrap().count(), 1);
+    }
+
     fn test_token(token: String) -> RefreshableToken {
         let expiry = Utc::now() + Duration::try_seconds(60 * 60).unwrap();
         let secret_token = SecretString::from(token);
@@ -0,0 +1,44 @@
+use futures::{future::BoxFuture, FutureExt};
+use http::{header::AUTHORIZATION, Request, Response, StatusCode};
+use tower::Service;
+
+use crate::client::auth::RefreshableToken;
+
+#[derive(Clone)]
+/// Service that refreshes the token for the next request when the apiserver rejects it
+///
+/// Like client-go, the rejected request is not retried.
+pub struct RefreshOnUnauthorized<S> {
+    pub(super) inner: S,
+    pub(super) token: RefreshableToken,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for RefreshOnUnauthorized<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+    S::Error: Send + 'static,
+    ResBody: Send + 'static,
+{
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+    type Response = S::Response;
+
+    fn poll_ready(&mut self, cx: &mut std::task::Context<'_>) -> std::task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<ReqBody>) -> Self::Future {
+        let sent = req.headers().get(AUTHORIZATION).cloned();
+        let token = self.token.clone();
+        let res = self.inner.call(req);
+        async move {
+            let res = res.await?;
+            if let (StatusCode::UNAUTHORIZED, Some(sent)) = (res.status(), sent) {
+                token.invalidate(&sent).await;
+            }
+            Ok(res)
+        }
+        .boxed()
+    }
+}
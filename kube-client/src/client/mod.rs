@@ -7,13 +7,16 @@
 //!
 //! The [`Client`] can also be used with [`Discovery`](crate::Discovery) to dynamically
 //! retrieve the resources served by the kubernetes API.
+use std::sync::Arc;
+
 use chrono::{DateTime, Utc};
 use either::{Either, Left, Right};
 use futures::{future::BoxFuture, AsyncBufRead, StreamExt, TryStream, TryStreamExt};
-use http::{self, Request, Response};
+use http::{self, HeaderName, HeaderValue, Request, Response};
 use http_body_util::BodyExt;
 #[cfg(feature = "ws")] use hyper_util::rt::TokioIo;
 use k8s_openapi::apimachinery::pkg::apis::meta::v1 as k8s_meta_v1;
+use kube_core::object::ObjectList;
 pub use kube_core::response::Status;
 use serde::de::DeserializeOwned;
 use serde_json::{self, Value};
@@ -27,14 +30,21 @@ use tower::{buffer::Buffer, util::BoxService, BoxError, Layer, Service, ServiceE
 use tower_http::map_response_body::MapResponseBodyLayer;
 
 pub use self::body::Body;
-use crate::{api::WatchEvent, error::ErrorResponse, Config, Error, Result};
+use self::list_decoder::ListDecoder;
+use crate::{api::WatchEvent, config::Impersonation, error::ErrorResponse, Config, Error, Result};
 
+mod access;
+pub use access::{MissingPermission, Permission};
 mod auth;
 mod body;
 mod builder;
+#[cfg(feature = "cassette")]
+#[cfg_attr(docsrs, doc(cfg(feature = "cassette")))]
+pub mod cassette;
 #[cfg_attr(docsrs, doc(cfg(feature = "unstable-client")))]
 #[cfg(feature = "unstable-client")]
 mod client_ext;
+mod list_decoder;
 #[cfg_attr(docsrs, doc(cfg(feature = "unstable-client")))]
 #[cfg(feature = "unstable-client")]
 pub use client_ext::scope;
@@ -58,11 +68,18 @@ pub use auth::OAuthError;
 #[cfg_attr(docsrs, doc(cfg(feature = "oidc")))]
 pub use auth::oidc_errors;
 
+#[cfg(feature = "aws-eks")]
+#[cfg_attr(docsrs, doc(cfg(feature = "aws-eks")))]
+pub use auth::EksError;
+
 #[cfg(feature = "ws")] pub use upgrade::UpgradeConnectionError;
 
 #[cfg(feature = "kubelet-debug")]
 #[cfg_attr(docsrs, doc(cfg(feature = "kubelet-debug")))]
 mod kubelet_debug;
+#[cfg(unix)] mod unix_socket;
+mod warning;
+pub use warning::{CollectWarnings, DenyWarnings, LogWarnings, Warning, WarningHandler};
 
 pub use builder::{ClientBuilder, DynBody};
 
@@ -80,6 +97,8 @@ pub struct Client {
     inner: Buffer<Request<Body>, BoxFuture<'static, Result<Response<Body>, BoxError>>>,
     default_ns: String,
     valid_until: Option<DateTime<Utc>>,
+    impersonation: Option<Arc<Vec<(HeaderName, HeaderValue)>>>,
+    warning_handler: Arc<dyn WarningHandler>,
 }
 
 /// Represents a WebSocket connection.
@@ -157,6 +176,8 @@ impl Client {
             inner: Buffer::new(BoxService::new(service), 1024),
             default_ns: default_namespace.into(),
             valid_until: None,
+            impersonation: None,
+            warning_handler: Arc::new(LogWarnings),
         }
     }
 
@@ -170,6 +191,38 @@ impl Client {
         &self.valid_until
     }
 
+    /// Returns a [`Client`] that impersonates another identity for all its requests
+    ///
+    /// This takes precedence over any impersonation set on the [`Config`] the client was created from,
+    /// and shares the underlying connection with `self`.
+    ///
+    /// ```rust
+    /// # async fn doc() -> Result<(), Box<dyn std::error::Error>> {
+    /// # use kube::{config::Impersonation, Client};
+    /// let client = Client::try_default().await?;
+    /// let jane = client.impersonate(&Impersonation::user("jane").groups(["devs"]))?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn impersonate(&self, impersonation: &Impersonation) -> Result<Self> {
+        let headers = impersonation.headers().map_err(Error::HttpError)?;
+        Ok(Client {
+            impersonation: Some(Arc::new(headers)),
+            ..self.clone()
+        })
+    }
+
+    /// Sets how the `Warning` headers returned by the API server are handled.
+    ///
+    /// Warnings are logged by default, see [`WarningHandler`] for alternatives.
+    #[must_use]
+    pub fn with_warning_handler(self, handler: impl WarningHandler + 'static) -> Self {
+        Client {
+            warning_handler: Arc::new(handler),
+            ..self
+        }
+    }
+
     /// Create and initialize a [`Client`] using the inferred configuration.
     ///
     /// Will use [`Config::infer`] which attempts to load the local kubeconfig first,
@@ -203,7 +256,13 @@ impl Client {
     /// Perform a raw HTTP request against the API and return the raw response back.
     /// This method can be used to get raw access to the API which may be used to, for example,
     /// create a proxy server or application-level gateway between localhost and the API server.
-    pub async fn send(&self, request: Request<Body>) -> Result<Response<Body>> {
+    ///
+    /// `Warning` headers in the response are passed to the [`WarningHandler`] of the client.
+    pub async fn send(&self, mut request: Request<Body>) -> Result<Response<Body>> {
+        if let Some(headers) = &self.impersonation {
+            request.headers_mut().extend(headers.iter().cloned());
+        }
+        let (method, uri) = (request.method().clone(), request.uri().clone());
         let mut svc = self.inner.clone();
         let res = svc
             .ready()
@@ -220,6 +279,10 @@ impl Client {
                     // Error from another middleware
                     .unwrap_or_else(Error::Service)
             })?;
+        let warnings = Warning::from_headers(res.headers());
+        if !warnings.is_empty() {
+            self.warning_handler.handle(&method, &uri, &warnings)?;
+        }
         Ok(res)
     }
 
@@ -289,6 +352,25 @@ impl Client {
         Ok(text)
     }
 
+    /// Perform a raw HTTP request against the API and deserialize the response as a JSON list
+    ///
+    /// Unlike [`Client::request`], the items are decoded as the response body arrives,
+    /// so large lists never have to be buffered in their encoded form.
+    pub async fn request_list<T>(&self, request: Request<Vec<u8>>) -> Result<ObjectList<T>>
+    where
+        T: Clone + DeserializeOwned,
+    {
+        let res = self.send(request.map(Body::from)).await?;
+        let mut body = handle_api_errors(res).await?.into_body();
+        let mut decoder = ListDecoder::default();
+        while let Some(frame) = body.frame().await {
+            if let Ok(data) = frame?.into_data() {
+                decoder.feed(&data).map_err(Error::SerdeError)?;
+            }
+        }
+        decoder.finish().map_err(Error::SerdeError)
+    }
+
     /// Perform a raw HTTP request against the API and stream the response body.
     ///
     /// The response can be processed using [`AsyncReadExt`](futures::AsyncReadExt)
@@ -336,7 +418,6 @@ impl Client {
         let res = self.send(request.map(Body::from)).await?;
         // trace!("Streaming from {} -> {}", res.url(), res.status().as_str());
         tracing::trace!("headers: {:?}", res.headers());
-
         let frames = FramedRead::new(
             StreamReader::new(res.into_body().into_data_stream().map_err(|e| {
                 // Unexpected EOF from chunked decoder.
@@ -569,4 +650,90 @@ mod tests {
         assert_eq!(pod.metadata.annotations.unwrap().get("kube-rs").unwrap(), "test");
         spawned.await.unwrap();
     }
+
+    #[tokio::test]
+    async fn test_api_impersonation_overrides_config() {
+        use crate::{client::ConfigExt, config::Impersonation, Config};
+        use tower::Layer;
+
+        let mut config = Config::new("https://localhost:6443".parse().unwrap());
+        config.impersonate(Impersonation::user("admin").groups(["ops"]).uid("42"));
+        let (mock_service, handle) = mock::pair::<Request<Body>, Response<Body>>();
+        let service = config.extra_headers_layer().unwrap().layer(mock_service);
+        let spawned = tokio::spawn(async move {
+            let mut handle = pin!(handle);
+            let (request, send) = handle.next_request().await.expect("service not called");
+            let values = |name| {
+                request
+                    .headers()
+                    .get_all(name)
+                    .iter()
+                    .map(|v| v.to_str().unwrap().to_string())
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(values("impersonate-user"), ["jane"]);
+            assert_eq!(values("impersonate-group"), ["devs"]);
+            assert!(values("impersonate-uid").is_empty());
+            let pod =
+                serde_json::json!({ "apiVersion": "v1", "kind": "Pod", "metadata": { "name": "test" } });
+            send.send_response(
+                Response::builder()
+                    .body(Body::from(pod.to_string().into_bytes()))
+                    .unwrap(),
+            );
+        });
+
+        let pods: Api<Pod> = Api::default_namespaced(Client::new(service, "default"));
+        pods.impersonate("jane", ["devs"])
+            .unwrap()
+            .get("test")
+            .await
+            .unwrap();
+        spawned.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_warning_handlers() {
+        use crate::{
+            api::{Patch, PatchParams},
+            client::{CollectWarnings, DenyWarnings},
+            Error,
+        };
+
+        let (mock_service, handle) = mock::pair::<Request<Body>, Response<Body>>();
+        let spawned = tokio::spawn(async move {
+            let mut handle = pin!(handle);
+            for _ in 0..2 {
+                let (request, send) = handle.next_request().await.expect("service not called");
+                assert_eq!(request.uri().query(), Some("&fieldValidation=Warn"));
+                let pod =
+                    serde_json::json!({ "apiVersion": "v1", "kind": "Pod", "metadata": { "name": "test" } });
+                send.send_response(
+                    Response::builder()
+                        .header(http::header::WARNING, r#"299 - "unknown field \"spec.foo\"""#)
+                        .body(Body::from(pod.to_string().into_bytes()))
+                        .unwrap(),
+                );
+            }
+        });
+
+        let pods: Api<Pod> = Api::default_namespaced(Client::new(mock_service, "default"));
+        let patch = Patch::Merge(serde_json::json!({ "spec": { "foo": true } }));
+        let pp = PatchParams::default().validation_warn();
+        let warnings = CollectWarnings::default();
+        pods.with_warning_handler(warnings.clone())
+            .patch("test", &pp, &patch)
+            .await
+            .unwrap();
+        let texts = warnings.take().into_iter().map(|w| w.text).collect::<Vec<_>>();
+        assert_eq!(texts, [r#"unknown field "spec.foo""#]);
+
+        let err = pods
+            .with_warning_handler(DenyWarnings)
+            .patch("test", &pp, &patch)
+            .await
+            .unwrap_err();
+        assert!(matches!(&err, Error::Warnings(w) if w.len() == 1), "{err}");
+        spawned.await.unwrap();
+    }
 }
@@ -1,14 +1,95 @@
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+// Same interval as the bearer token file
+const RELOAD_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Files to periodically re-read TLS material from, so rotated certificates are picked up without a restart
+#[derive(Clone, Debug, Default)]
+pub struct TlsFiles {
+    /// Client certificate and key files
+    pub identity: Option<(PathBuf, PathBuf)>,
+    /// Root certificate bundle file
+    pub root_cert: Option<PathBuf>,
+}
+
+// Contents of a file that may be rotated on disk. Reloads at most once per minute.
+#[derive(Debug)]
+struct ReloadingFile {
+    path: PathBuf,
+    data: Option<Vec<u8>>,
+    expires_at: Instant,
+}
+
+impl ReloadingFile {
+    // The caller already has the current contents, so the first read happens after one interval
+    fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            data: None,
+            expires_at: Instant::now() + RELOAD_INTERVAL,
+        }
+    }
+
+    /// Re-read the file if the cached contents are stale. Returns whether the contents changed.
+    fn refresh(&mut self) -> bool {
+        if Instant::now() < self.expires_at {
+            return false;
+        }
+        self.expires_at = Instant::now() + RELOAD_INTERVAL;
+        match std::fs::read(&self.path) {
+            Ok(data) if self.data.as_ref() != Some(&data) => {
+                self.data = Some(data);
+                true
+            }
+            Ok(_) => false,
+            // Keep using the last-read contents, the file might be in the middle of being replaced
+            Err(err) => {
+                tracing::warn!(
+                    path = ?self.path,
+                    error = &err as &dyn std::error::Error,
+                    "failed to reload TLS file"
+                );
+                false
+            }
+        }
+    }
+
+    /// Force a re-read on the next refresh, e.g. when a certificate was written before its key
+    fn expire(&mut self) {
+        self.expires_at = Instant::now();
+    }
+
+    fn data(&self) -> &[u8] {
+        self.data.as_deref().unwrap_or_default()
+    }
+}
+
 #[cfg(feature = "rustls-tls")]
 pub mod rustls_tls {
+    use std::{
+        path::PathBuf,
+        sync::{Arc, Mutex, PoisonError},
+    };
+
     use hyper_rustls::ConfigBuilderExt;
     use rustls::{
         self,
-        client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+        client::{
+            danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+            ResolvesClientCert, VerifierBuilderError, WebPkiServerVerifier,
+        },
+        crypto::CryptoProvider,
         pki_types::{CertificateDer, InvalidDnsNameError, PrivateKeyDer, ServerName},
-        ClientConfig, DigitallySignedStruct,
+        sign::CertifiedKey,
+        ClientConfig, DigitallySignedStruct, InconsistentKeys, RootCertStore,
     };
     use thiserror::Error;
 
+    use super::{ReloadingFile, TlsFiles};
+
     /// Errors from Rustls
     #[derive(Debug, Error)]
     pub enum Error {
@@ -16,6 +97,10 @@ pub mod rustls_tls {
         #[error("identity PEM is invalid: {0}")]
         InvalidIdentityPem(#[source] rustls::pki_types::pem::Error),
 
+        /// Root certificate PEM is invalid
+        #[error("root certificate PEM is invalid: {0}")]
+        InvalidRootCertificatePem(#[source] rustls::pki_types::pem::Error),
+
         /// Identity PEM is missing a private key: the key must be PKCS8 or RSA/PKCS1
         #[error("identity PEM is missing a private key: the key must be PKCS8 or RSA/PKCS1")]
         MissingPrivateKey,
@@ -44,16 +129,34 @@ pub mod rustls_tls {
         /// Invalid server name
         #[error("invalid server name: {0}")]
         InvalidServerName(#[source] InvalidDnsNameError),
+
+        /// Failed to build the server certificate verifier
+        #[error("failed to build the server certificate verifier: {0}")]
+        BuildCertificateVerifier(#[source] VerifierBuilderError),
     }
 
     /// Create `rustls::ClientConfig`.
+    ///
+    /// The client identity and root certificates are re-read from `files` when set.
     pub fn rustls_client_config(
         identity_pem: Option<&[u8]>,
         root_certs: Option<&[Vec<u8>]>,
         accept_invalid: bool,
+        files: &TlsFiles,
     ) -> Result<ClientConfig, Error> {
         let config_builder = if let Some(certs) = root_certs {
-            ClientConfig::builder().with_root_certificates(root_store(certs)?)
+            let roots = root_store(certs)?;
+            match &files.root_cert {
+                Some(path) if !accept_invalid => {
+                    let builder = ClientConfig::builder();
+                    let verifier =
+                        ReloadingVerifier::new(path.clone(), roots, builder.crypto_provider().clone())?;
+                    builder
+                        .dangerous()
+                        .with_custom_certificate_verifier(Arc::new(verifier))
+                }
+                _ => ClientConfig::builder().with_root_certificates(roots),
+            }
         } else {
             #[cfg(feature = "webpki-roots")]
             {
@@ -69,24 +172,31 @@ pub mod rustls_tls {
             }
         };
 
-        let mut client_config = if let Some((chain, pkey)) = identity_pem.map(client_auth).transpose()? {
-            config_builder
-                .with_client_auth_cert(chain, pkey)
-                .map_err(Error::InvalidPrivateKey)?
-        } else {
-            config_builder.with_no_client_auth()
+        let mut client_config = match (identity_pem, &files.identity) {
+            (Some(pem), Some((cert, key))) => {
+                let provider = config_builder.crypto_provider().clone();
+                let resolver = ReloadingIdentity::new(cert.clone(), key.clone(), pem, provider)?;
+                config_builder.with_client_cert_resolver(Arc::new(resolver))
+            }
+            (Some(pem), None) => {
+                let (chain, pkey) = client_auth(pem)?;
+                config_builder
+                    .with_client_auth_cert(chain, pkey)
+                    .map_err(Error::InvalidPrivateKey)?
+            }
+            (None, _) => config_builder.with_no_client_auth(),
         };
 
         if accept_invalid {
             client_config
                 .dangerous()
-                .set_certificate_verifier(std::sync::Arc::new(NoCertificateVerification {}));
+                .set_certificate_verifier(Arc::new(NoCertificateVerification {}));
         }
         Ok(client_config)
     }
 
-    fn root_store(root_certs: &[Vec<u8>]) -> Result<rustls::RootCertStore, Error> {
-        let mut root_store = rustls::RootCertStore::empty();
+    fn root_store(root_certs: &[Vec<u8>]) -> Result<RootCertStore, Error> {
+        let mut root_store = RootCertStore::empty();
         for der in root_certs {
             root_store
                 .add(CertificateDer::from(der.to_owned()))
@@ -123,6 +233,187 @@ pub mod rustls_tls {
         Ok((cert_chain, private_key))
     }
 
+    fn certified_key(identity_pem: &[u8], provider: &CryptoProvider) -> Result<CertifiedKey, Error> {
+        let (chain, pkey) = client_auth(identity_pem)?;
+        let signing_key = provider
+            .key_provider
+            .load_private_key(pkey)
+            .map_err(Error::InvalidPrivateKey)?;
+        Ok(CertifiedKey::new(chain, signing_key))
+    }
+
+    // Client identity that is re-read from the certificate and key files
+    #[derive(Debug)]
+    struct ReloadingIdentity {
+        state: Mutex<IdentityState>,
+        provider: Arc<CryptoProvider>,
+    }
+
+    #[derive(Debug)]
+    struct IdentityState {
+        cert: ReloadingFile,
+        key: ReloadingFile,
+        current: Arc<CertifiedKey>,
+    }
+
+    impl ReloadingIdentity {
+        fn new(
+            cert: PathBuf,
+            key: PathBuf,
+            identity_pem: &[u8],
+            provider: Arc<CryptoProvider>,
+        ) -> Result<Self, Error> {
+            let current = Arc::new(certified_key(identity_pem, &provider)?);
+            Ok(Self {
+                state: Mutex::new(IdentityState {
+                    cert: ReloadingFile::new(cert),
+                    key: ReloadingFile::new(key),
+                    current,
+                }),
+                provider,
+            })
+        }
+    }
+
+    impl ResolvesClientCert for ReloadingIdentity {
+        fn resolve(&self, _: &[&[u8]], _: &[rustls::SignatureScheme]) -> Option<Arc<CertifiedKey>> {
+            let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+            // Refresh both, a rotation usually replaces the pair
+            let changed = state.cert.refresh() | state.key.refresh();
+            if changed {
+                let mut pem = state.key.data().to_vec();
+                pem.push(b'\n');
+                pem.extend_from_slice(state.cert.data());
+                match certified_key(&pem, &self.provider).map(|k| (k.keys_match(), k)) {
+                    Ok((Ok(()), key)) => state.current = Arc::new(key),
+                    // Only one of the files has been replaced so far, try again on the next handshake
+                    Ok((Err(rustls::Error::InconsistentKeys(InconsistentKeys::KeyMismatch)), _)) => {
+                        state.cert.expire();
+                        state.key.expire();
+                    }
+                    Ok((Err(_), key)) => state.current = Arc::new(key),
+                    Err(err) => {
+                        tracing::warn!(
+                            error = &err as &dyn std::error::Error,
+                            "failed to reload client identity"
+                        );
+                    }
+                }
+            }
+            Some(state.current.clone())
+        }
+
+        fn has_certs(&self) -> bool {
+            true
+        }
+    }
+
+    // Server certificate verifier that is rebuilt when the root certificate file changes
+    #[derive(Debug)]
+    struct ReloadingVerifier {
+        state: Mutex<(ReloadingFile, Arc<WebPkiServerVerifier>)>,
+        provider: Arc<CryptoProvider>,
+    }
+
+    impl ReloadingVerifier {
+        fn new(path: PathBuf, roots: RootCertStore, provider: Arc<CryptoProvider>) -> Result<Self, Error> {
+            let verifier = Self::verifier(roots, provider.clone())?;
+            Ok(Self {
+                state: Mutex::new((ReloadingFile::new(path), verifier)),
+                provider,
+            })
+        }
+
+        fn verifier(
+            roots: RootCertStore,
+            provider: Arc<CryptoProvider>,
+        ) -> Result<Arc<WebPkiServerVerifier>, Error> {
+            WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider)
+                .build()
+                .map_err(Error::BuildCertificateVerifier)
+        }
+
+        fn current(&self) -> Arc<WebPkiServerVerifier> {
+            let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+            if state.0.refresh() {
+                let verifier = root_certs(state.0.data())
+                    .and_then(|certs| root_store(&certs))
+                    .and_then(|roots| Self::verifier(roots, self.provider.clone()));
+                match verifier {
+                    Ok(verifier) => state.1 = verifier,
+                    Err(err) => {
+                        tracing::warn!(
+                            error = &err as &dyn std::error::Error,
+                            "failed to reload root certificates"
+                        );
+                    }
+                }
+            }
+            state.1.clone()
+        }
+    }
+
+    impl ServerCertVerifier for ReloadingVerifier {
+        fn verify_server_cert(
+            &self,
+            end_entity: &CertificateDer,
+            intermediates: &[CertificateDer],
+            server_name: &ServerName,
+            ocsp_response: &[u8],
+            now: rustls::pki_types::UnixTime,
+        ) -> Result<ServerCertVerified, rustls::Error> {
+            self.current()
+                .verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)
+        }
+
+        fn verify_tls12_signature(
+            &self,
+            message: &[u8],
+            cert: &CertificateDer,
+            dss: &DigitallySignedStruct,
+        ) -> Result<HandshakeSignatureValid, rustls::Error> {
+            rustls::crypto::verify_tls12_signature(
+                message,
+                cert,
+                dss,
+                &self.provider.signature_verification_algorithms,
+            )
+        }
+
+        fn verify_tls13_signature(
+            &self,
+            message: &[u8],
+            cert: &CertificateDer,
+            dss: &DigitallySignedStruct,
+        ) -> Result<HandshakeSignatureValid, rustls::Error> {
+            rustls::crypto::verify_tls13_signature(
+                message,
+                cert,
+                dss,
+                &self.provider.signature_verification_algorithms,
+            )
+        }
+
+        fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
+            self.provider
+                .signature_verification_algorithms
+                .supported_schemes()
+        }
+    }
+
+    fn root_certs(data: &[u8]) -> Result<Vec<Vec<u8>>, Error> {
+        use rustls::pki_types::pem::{self, SectionKind};
+
+        let mut certs = Vec::new();
+        let mut reader = std::io::Cursor::new(data);
+        while let Some((kind, der)) = pem::from_buf(&mut reader).map_err(Error::InvalidRootCertificatePem)? {
+            if kind == SectionKind::Certificate {
+                certs.push(der);
+            }
+        }
+        Ok(certs)
+    }
+
     #[derive(Debug)]
     struct NoCertificateVerification {}
 
@@ -176,17 +467,100 @@ pub mod rustls_tls {
             ]
         }
     }
+
+    #[cfg(test)]
+    mod tests {
+        use rustls::pki_types::UnixTime;
+
+        use super::*;
+
+        const CLIENT1: &str = concat!(
+            include_str!("./test_data/client1.crt"),
+            include_str!("./test_data/client1.key")
+        );
+
+        fn provider() -> Arc<CryptoProvider> {
+            ClientConfig::builder().crypto_provider().clone()
+        }
+
+        fn der(pem: &str) -> Vec<u8> {
+            root_certs(pem.as_bytes()).unwrap().remove(0)
+        }
+
+        fn resolved_cert(identity: &ReloadingIdentity) -> Vec<u8> {
+            identity.resolve(&[], &[]).unwrap().cert[0].to_vec()
+        }
+
+        fn expire(identity: &ReloadingIdentity) {
+            let mut state = identity.state.lock().unwrap();
+            state.cert.expire();
+            state.key.expire();
+        }
+
+        #[test]
+        fn resolver_reloads_rotated_identity() {
+            let dir = tempfile::tempdir().unwrap();
+            let (cert, key) = (dir.path().join("client.crt"), dir.path().join("client.key"));
+            std::fs::write(&cert, include_str!("./test_data/client1.crt")).unwrap();
+            std::fs::write(&key, include_str!("./test_data/client1.key")).unwrap();
+            let identity =
+                ReloadingIdentity::new(cert.clone(), key.clone(), CLIENT1.as_bytes(), provider()).unwrap();
+            let client1 = der(include_str!("./test_data/client1.crt"));
+            let client2 = der(include_str!("./test_data/client2.crt"));
+            assert_eq!(resolved_cert(&identity), client1);
+
+            // The certificate was replaced before its key, keep the old pair
+            std::fs::write(&cert, include_str!("./test_data/client2.crt")).unwrap();
+            expire(&identity);
+            assert_eq!(resolved_cert(&identity), client1);
+
+            std::fs::write(&key, include_str!("./test_data/client2.key")).unwrap();
+            assert_eq!(resolved_cert(&identity), client2, "retried on the next handshake");
+        }
+
+        #[test]
+        fn verifier_reloads_rotated_root_certificates() {
+            let file = tempfile::NamedTempFile::new().unwrap();
+            std::fs::write(file.path(), include_str!("./test_data/ca1.crt")).unwrap();
+            let roots = root_store(&[der(include_str!("./test_data/ca1.crt"))]).unwrap();
+            let verifier = ReloadingVerifier::new(file.path().to_owned(), roots, provider()).unwrap();
+            let server = CertificateDer::from(der(include_str!("./test_data/server.crt")));
+            let name = ServerName::try_from("kubernetes").unwrap();
+            let verify = || verifier.verify_server_cert(&server, &[], &name, &[], UnixTime::now());
+            assert!(verify().is_err(), "server is signed by ca2");
+
+            std::fs::write(file.path(), include_str!("./test_data/ca2.crt")).unwrap();
+            verifier.state.lock().unwrap().0.expire();
+            assert!(verify().is_ok());
+
+            // A half written bundle keeps the last roots
+            std::fs::write(file.path(), "").unwrap();
+            verifier.state.lock().unwrap().0.expire();
+            assert!(verify().is_ok());
+        }
+    }
 }
 
 #[cfg(feature = "openssl-tls")]
 pub mod openssl_tls {
+    use std::{
+        path::PathBuf,
+        sync::{Mutex, PoisonError},
+    };
+
     use openssl::{
-        pkey::PKey,
-        ssl::{SslConnector, SslConnectorBuilder, SslMethod},
-        x509::X509,
+        error::ErrorStack,
+        pkey::{PKey, Private},
+        ssl::{SslConnector, SslConnectorBuilder, SslMethod, SslRef},
+        x509::{
+            store::{X509Store, X509StoreBuilder},
+            X509,
+        },
     };
     use thiserror::Error;
 
+    use super::ReloadingFile;
+
     /// Errors from OpenSSL TLS
     #[derive(Debug, Error)]
     pub enum Error {
@@ -278,4 +652,197 @@ pub mod openssl_tls {
 
         Ok(builder)
     }
+
+    /// Client identity that is re-read from the certificate and key files
+    pub struct ReloadingIdentity {
+        state: Mutex<IdentityState>,
+    }
+
+    struct IdentityState {
+        cert: ReloadingFile,
+        key: ReloadingFile,
+        current: Option<(X509, PKey<Private>)>,
+    }
+
+    impl ReloadingIdentity {
+        /// Watch the given certificate and key files
+        pub fn new(cert: PathBuf, key: PathBuf) -> Self {
+            Self {
+                state: Mutex::new(IdentityState {
+                    cert: ReloadingFile::new(cert),
+                    key: ReloadingFile::new(key),
+                    current: None,
+                }),
+            }
+        }
+
+        /// Set the latest identity on a new connection
+        ///
+        /// Until the files change, the identity the `SslConnector` was built with is kept.
+        pub fn configure(&self, ssl: &mut SslRef) -> Result<(), ErrorStack> {
+            let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+            // Refresh both, a rotation usually replaces the pair
+            let changed = state.cert.refresh() | state.key.refresh();
+            if changed {
+                let identity = X509::from_pem(state.cert.data()).and_then(|cert| {
+                    let key = PKey::private_key_from_pem(state.key.data())?;
+                    let matches = cert.public_key()?.public_eq(&key);
+                    Ok(matches.then_some((cert, key)))
+                });
+                match identity {
+                    Ok(Some(identity)) => state.current = Some(identity),
+                    // Only one of the files has been replaced so far, try again on the next connection
+                    Ok(None) => {
+                        state.cert.expire();
+                        state.key.expire();
+                    }
+                    Err(err) => {
+                        tracing::warn!(
+                            error = &err as &dyn std::error::Error,
+                            "failed to reload client identity"
+                        );
+                    }
+                }
+            }
+            if let Some((cert, key)) = &state.current {
+                ssl.set_certificate(cert)?;
+                ssl.set_private_key(key)?;
+            }
+            Ok(())
+        }
+    }
+
+    /// Root certificates that are re-read from the CA bundle file
+    ///
+    /// The connector is built with the initial bundle, a rotated bundle replaces the verify store of new connections.
+    pub struct ReloadingRoots {
+        state: Mutex<RootsState>,
+    }
+
+    struct RootsState {
+        file: ReloadingFile,
+        current: Option<Vec<X509>>,
+    }
+
+    impl ReloadingRoots {
+        /// Watch the given CA bundle file
+        pub fn new(path: PathBuf) -> Self {
+            Self {
+                state: Mutex::new(RootsState {
+                    file: ReloadingFile::new(path),
+                    current: None,
+                }),
+            }
+        }
+
+        /// Verify new connections against the latest root certificates
+        ///
+        /// Until the file changes, the root certificates the `SslConnector` was built with are kept.
+        pub fn configure(&self, ssl: &mut SslRef) -> Result<(), ErrorStack> {
+            if let Some(store) = self.store()? {
+                ssl.set_verify_cert_store(store)?;
+            }
+            Ok(())
+        }
+
+        // A store with the latest root certificates, or `None` when the file never changed
+        fn store(&self) -> Result<Option<X509Store>, ErrorStack> {
+            let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+            if state.file.refresh() {
+                match X509::stack_from_pem(state.file.data()) {
+                    Ok(certs) if !certs.is_empty() => state.current = Some(certs),
+                    // The file might be in the middle of being replaced
+                    Ok(_) => state.file.expire(),
+                    Err(err) => {
+                        tracing::warn!(
+                            error = &err as &dyn std::error::Error,
+                            "failed to reload root certificates"
+                        );
+                    }
+                }
+            }
+            let Some(certs) = &state.current else {
+                return Ok(None);
+            };
+            // Like `SslConnector::builder`, trust the system roots next to the bundle
+            let mut store = X509StoreBuilder::new()?;
+            store.set_default_paths()?;
+            for cert in certs {
+                store.add_cert(cert.clone())?;
+            }
+            Ok(Some(store.build()))
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use openssl::x509::{store::X509StoreRef, X509StoreContext};
+
+        use super::*;
+
+        fn verifies(store: &X509StoreRef, cert: &X509) -> bool {
+            let chain = openssl::stack::Stack::new().unwrap();
+            let mut context = X509StoreContext::new().unwrap();
+            context.init(store, cert, &chain, |c| c.verify_cert()).unwrap()
+        }
+
+        #[test]
+        fn reloads_root_certificates() {
+            let server = X509::from_pem(include_bytes!("./test_data/server.crt")).unwrap();
+            let file = tempfile::NamedTempFile::new().unwrap();
+            std::fs::write(file.path(), include_str!("./test_data/ca1.crt")).unwrap();
+            let roots = ReloadingRoots::new(file.path().to_owned());
+            assert!(
+                roots.store().unwrap().is_none(),
+                "connector roots until the file changes"
+            );
+
+            roots.state.lock().unwrap().file.expire();
+            let store = roots.store().unwrap().unwrap();
+            assert!(!verifies(&store, &server), "server is signed by ca2");
+
+            std::fs::write(file.path(), include_str!("./test_data/ca2.crt")).unwrap();
+            roots.state.lock().unwrap().file.expire();
+            let store = roots.store().unwrap().unwrap();
+            assert!(verifies(&store, &server));
+
+            // A half written bundle keeps the last roots
+            std::fs::write(file.path(), "").unwrap();
+            roots.state.lock().unwrap().file.expire();
+            assert!(verifies(&roots.store().unwrap().unwrap(), &server));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReloadingFile;
+
+    #[test]
+    fn reloading_file_keeps_last_contents() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), "first").unwrap();
+        let mut reloading = ReloadingFile::new(file.path().to_owned());
+        // Not read until the interval elapsed
+        assert!(!reloading.refresh());
+
+        reloading.expire();
+        assert!(reloading.refresh());
+        assert_eq!(reloading.data(), b"first");
+
+        reloading.expire();
+        assert!(!reloading.refresh(), "unchanged contents");
+
+        std::fs::write(file.path(), "second").unwrap();
+        reloading.expire();
+        assert!(reloading.refresh());
+        assert_eq!(reloading.data(), b"second");
+
+        let path = file.path().to_owned();
+        drop(file);
+        assert!(!path.exists());
+        reloading.expire();
+        assert!(!reloading.refresh(), "missing file");
+        assert_eq!(reloading.data(), b"second");
+    }
 }
@@ -0,0 +1,257 @@
+//! Handling of `Warning` headers returned by the API server
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use http::{header::WARNING, HeaderMap, Method, Uri};
+
+use crate::Error;
+
+/// A warning returned by the API server in a `Warning` header
+///
+/// The API server warns about the use of deprecated APIs, and about unknown or duplicate fields
+/// in requests using `fieldValidation=Warn`. Admission webhooks can add their own warnings.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Warning {
+    /// The warn-code, which is always `299` for warnings from the API server
+    pub code: u16,
+    /// The warn-agent, which is `-` for warnings from the API server
+    pub agent: String,
+    /// The warning message
+    pub text: String,
+    /// The warn-date, if the warning has one
+    pub date: Option<String>,
+}
+
+impl Warning {
+    /// Parse the warnings of all `Warning` headers, as defined in [RFC 7234](https://httpwg.org/specs/rfc7234.html#header.warning)
+    ///
+    /// Malformed warnings are skipped, along with the rest of the header they appear in.
+    pub fn from_headers(headers: &HeaderMap) -> Vec<Self> {
+        headers
+            .get_all(WARNING)
+            .iter()
+            .flat_map(|value| Self::parse(&String::from_utf8_lossy(value.as_bytes())))
+            .collect()
+    }
+
+    /// Parse the warnings of a single `Warning` header value
+    pub fn parse(value: &str) -> Vec<Self> {
+        let mut warnings = Vec::new();
+        let mut rest = value;
+        loop {
+            rest = rest.trim_start_matches([' ', '\t', ',']);
+            if rest.is_empty() {
+                break;
+            }
+            match parse_warning(rest) {
+                Some((warning, remaining)) => {
+                    warnings.push(warning);
+                    rest = remaining;
+                }
+                None => {
+                    tracing::debug!("ignoring malformed warning header {value:?}");
+                    break;
+                }
+            }
+        }
+        warnings
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+/// Parses `warn-code SP warn-agent SP warn-text [ SP warn-date ]`, returning what follows it
+fn parse_warning(value: &str) -> Option<(Warning, &str)> {
+    let (code, rest) = value.split_once(' ')?;
+    if code.len() != 3 || !code.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let (agent, rest) = rest.split_once(' ')?;
+    let (text, rest) = parse_quoted(rest)?;
+    let (date, rest) = match rest.trim_start_matches(' ') {
+        quoted if quoted.starts_with('"') => parse_quoted(quoted).map(|(date, rest)| (Some(date), rest))?,
+        rest => (None, rest),
+    };
+    let rest = rest.trim_start_matches([' ', '\t']);
+    if !rest.is_empty() && !rest.starts_with(',') {
+        return None;
+    }
+    let warning = Warning {
+        code: code.parse().ok()?,
+        agent: agent.to_owned(),
+        text,
+        date,
+    };
+    Some((warning, rest))
+}
+
+/// Parses a quoted-string, unescaping quoted-pairs
+fn parse_quoted(value: &str) -> Option<(String, &str)> {
+    let mut chars = value.strip_prefix('"')?.char_indices();
+    let mut unquoted = String::new();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((unquoted, &value[i + 2..])),
+            '\\' => unquoted.push(chars.next()?.1),
+            c => unquoted.push(c),
+        }
+    }
+    None
+}
+
+/// Handles the warnings returned by the API server for a request
+///
+/// The handler of a [`Client`](crate::Client) is called for every response with at least one warning,
+/// and can fail the request by returning an error. Set it with [`Client::with_warning_handler`](crate::Client::with_warning_handler).
+///
+/// Closures taking the method, the uri and the warnings of a request implement this trait.
+pub trait WarningHandler: Send + Sync {
+    /// Handle the warnings of a response to a request
+    fn handle(&self, method: &Method, uri: &Uri, warnings: &[Warning]) -> Result<(), Error>;
+}
+
+impl<F> WarningHandler for F
+where
+    F: Fn(&Method, &Uri, &[Warning]) -> Result<(), Error> + Send + Sync,
+{
+    fn handle(&self, method: &Method, uri: &Uri, warnings: &[Warning]) -> Result<(), Error> {
+        self(method, uri, warnings)
+    }
+}
+
+/// Logs warnings through `tracing`, which is what clients do by default
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LogWarnings;
+
+impl WarningHandler for LogWarnings {
+    fn handle(&self, method: &Method, uri: &Uri, warnings: &[Warning]) -> Result<(), Error> {
+        for warning in warnings {
+            tracing::warn!(%method, %uri, code = warning.code, agent = %warning.agent, "{}", warning.text);
+        }
+        Ok(())
+    }
+}
+
+/// Collects warnings so they can be inspected after making requests
+///
+/// This is a cheap handle to the collected warnings, so keep a clone to [`take`](Self::take) them:
+///
+/// ```no_run
+/// # async fn wrapper() -> Result<(), Box<dyn std::error::Error>> {
+/// use kube::{api::PostParams, client::CollectWarnings, Api, Client};
+/// use k8s_openapi::api::core::v1::ConfigMap;
+///
+/// let warnings = CollectWarnings::default();
+/// let client = Client::try_default().await?.with_warning_handler(warnings.clone());
+/// let configmaps: Api<ConfigMap> = Api::default_namespaced(client);
+/// configmaps.create(&PostParams::default(), &ConfigMap::default()).await?;
+/// for warning in warnings.take() {
+///     println!("warning: {warning}");
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct CollectWarnings {
+    warnings: Arc<Mutex<Vec<Warning>>>,
+}
+
+impl CollectWarnings {
+    /// Remove and return the warnings collected so far
+    pub fn take(&self) -> Vec<Warning> {
+        std::mem::take(&mut *self.lock())
+    }
+
+    /// Return the warnings collected so far
+    pub fn warnings(&self) -> Vec<Warning> {
+        self.lock().clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Warning>> {
+        self.warnings
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+impl WarningHandler for CollectWarnings {
+    fn handle(&self, _method: &Method, _uri: &Uri, warnings: &[Warning]) -> Result<(), Error> {
+        self.lock().extend_from_slice(warnings);
+        Ok(())
+    }
+}
+
+/// Fails requests with [`Error::Warnings`] when the API server returns warnings
+///
+/// The API server has already processed the request when it returns warnings,
+/// so a denied create or update has still been persisted.
+/// Pair this with a dry run to reject requests before they take effect.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DenyWarnings;
+
+impl WarningHandler for DenyWarnings {
+    fn handle(&self, _method: &Method, _uri: &Uri, warnings: &[Warning]) -> Result<(), Error> {
+        Err(Error::Warnings(warnings.to_vec()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_warning_headers() {
+        let warning = |code, agent: &str, text: &str, date: Option<&str>| Warning {
+            code,
+            agent: agent.into(),
+            text: text.into(),
+            date: date.map(Into::into),
+        };
+        assert_eq!(
+            Warning::parse(r#"299 - "policy/v1beta1 PodSecurityPolicy is deprecated in v1.21+""#),
+            [warning(
+                299,
+                "-",
+                "policy/v1beta1 PodSecurityPolicy is deprecated in v1.21+",
+                None
+            )]
+        );
+        assert_eq!(
+            Warning::parse(
+                r#"299 - "unknown field \"spec.foo\"", 110 proxy:8080 "stale \\ response" "Sat, 25 Aug 2012 23:34:45 GMT""#
+            ),
+            [
+                warning(299, "-", r#"unknown field "spec.foo""#, None),
+                warning(
+                    110,
+                    "proxy:8080",
+                    r"stale \ response",
+                    Some("Sat, 25 Aug 2012 23:34:45 GMT")
+                ),
+            ]
+        );
+        // Everything after a malformed warning is skipped
+        assert_eq!(
+            Warning::parse(r#"299 - "first", 29 - "short code", 299 - "last""#),
+            [warning(299, "-", "first", None)]
+        );
+        assert!(Warning::parse(r#"299 - "unterminated"#).is_empty());
+        assert!(Warning::parse(r#"299 - unquoted"#).is_empty());
+        assert!(Warning::parse("").is_empty());
+
+        let mut headers = HeaderMap::new();
+        headers.append(WARNING, r#"299 - "a""#.parse().unwrap());
+        headers.append(WARNING, r#"299 - "b", 299 - "c""#.parse().unwrap());
+        let texts = Warning::from_headers(&headers)
+            .into_iter()
+            .map(|w| w.text)
+            .collect::<Vec<_>>();
+        assert_eq!(texts, ["a", "b", "c"]);
+    }
+}
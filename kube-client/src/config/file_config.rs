@@ -230,6 +230,14 @@ pub struct AuthInfo {
     #[serde(rename = "as-groups")]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub impersonate_groups: Option<Vec<String>>,
+    /// The uid to impersonate.
+    #[serde(rename = "as-uid")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub impersonate_uid: Option<String>,
+    /// Extra information to impersonate, such as scopes.
+    #[serde(rename = "as-user-extra")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub impersonate_user_extra: Option<HashMap<String, Vec<String>>>,
 
     /// Specifies a custom authentication plugin for the kubernetes cluster.
     #[serde(rename = "auth-provider")]
@@ -304,6 +312,15 @@ pub struct ExecConfig {
     /// Should be used only when `provide_cluster_info` is True.
     #[serde(skip)]
     pub cluster: Option<ExecAuthCluster>,
+
+    /// Directory to persist credentials returned by the plugin in, so other processes can reuse them until they expire.
+    ///
+    /// Only credentials with an `expirationTimestamp` are persisted.
+    /// This does not exist upstream and cannot be specified on disk; it is set to `~/.kube/cache/kube-rs/exec`
+    /// by [`Config::cache_exec_credentials`](super::Config::cache_exec_credentials).
+    /// The directory is created only readable by the current user, as the credentials are stored in plaintext.
+    #[serde(skip)]
+    pub credential_cache: Option<PathBuf>,
 }
 
 /// ExecInteractiveMode define the interactity of the child process
@@ -345,56 +362,58 @@ pub struct Context {
     pub extensions: Option<Vec<NamedExtension>>,
 }
 
-const KUBECONFIG: &str = "KUBECONFIG";
+pub(crate) const KUBECONFIG: &str = "KUBECONFIG";
 
 /// Some helpers on the raw Config object are exposed for people needing to parse it
 impl Kubeconfig {
     /// Read a Config from an arbitrary location
     pub fn read_from<P: AsRef<Path>>(path: P) -> Result<Kubeconfig, KubeconfigError> {
+        let mut config = Self::read_unresolved(&path)?;
+        // Remap all files we read to absolute paths.
+        if let Some(dir) = path.as_ref().parent() {
+            config.resolve_paths(dir);
+        }
+        Ok(config)
+    }
+
+    /// Read a Config from an arbitrary location, leaving relative paths as they are written in the file
+    pub(crate) fn read_unresolved<P: AsRef<Path>>(path: P) -> Result<Kubeconfig, KubeconfigError> {
         let data =
             read_path(&path).map_err(|source| KubeconfigError::ReadConfig(source, path.as_ref().into()))?;
+        // Empty file defaults to an empty Kubeconfig
+        Self::from_yaml(&data)
+    }
 
-        // Remap all files we read to absolute paths.
-        let mut merged_docs = None;
-        for mut config in kubeconfig_from_yaml(&data)? {
-            if let Some(dir) = path.as_ref().parent() {
-                for named in config.clusters.iter_mut() {
-                    if let Some(cluster) = &mut named.cluster {
-                        if let Some(path) = &cluster.certificate_authority {
-                            if let Some(abs_path) = to_absolute(dir, path) {
-                                cluster.certificate_authority = Some(abs_path);
-                            }
-                        }
+    /// Make all relative paths of the config relative to `dir`
+    pub(crate) fn resolve_paths(&mut self, dir: &Path) {
+        for named in self.clusters.iter_mut() {
+            if let Some(cluster) = &mut named.cluster {
+                if let Some(path) = &cluster.certificate_authority {
+                    if let Some(abs_path) = to_absolute(dir, path) {
+                        cluster.certificate_authority = Some(abs_path);
                     }
                 }
-                for named in config.auth_infos.iter_mut() {
-                    if let Some(auth_info) = &mut named.auth_info {
-                        if let Some(path) = &auth_info.client_certificate {
-                            if let Some(abs_path) = to_absolute(dir, path) {
-                                auth_info.client_certificate = Some(abs_path);
-                            }
-                        }
-                        if let Some(path) = &auth_info.client_key {
-                            if let Some(abs_path) = to_absolute(dir, path) {
-                                auth_info.client_key = Some(abs_path);
-                            }
-                        }
-                        if let Some(path) = &auth_info.token_file {
-                            if let Some(abs_path) = to_absolute(dir, path) {
-                                auth_info.token_file = Some(abs_path);
-                            }
-                        }
+            }
+        }
+        for named in self.auth_infos.iter_mut() {
+            if let Some(auth_info) = &mut named.auth_info {
+                if let Some(path) = &auth_info.client_certificate {
+                    if let Some(abs_path) = to_absolute(dir, path) {
+                        auth_info.client_certificate = Some(abs_path);
+                    }
+                }
+                if let Some(path) = &auth_info.client_key {
+                    if let Some(abs_path) = to_absolute(dir, path) {
+                        auth_info.client_key = Some(abs_path);
+                    }
+                }
+                if let Some(path) = &auth_info.token_file {
+                    if let Some(abs_path) = to_absolute(dir, path) {
+                        auth_info.token_file = Some(abs_path);
                     }
                 }
-            }
-            if let Some(c) = merged_docs {
-                merged_docs = Some(Kubeconfig::merge(c, config)?);
-            } else {
-                merged_docs = Some(config);
             }
         }
-        // Empty file defaults to an empty Kubeconfig
-        Ok(merged_docs.unwrap_or_default())
     }
 
     /// Read a Config from an arbitrary YAML string
@@ -471,6 +490,118 @@ impl Kubeconfig {
         self.extensions = self.extensions.or(next.extensions);
         Ok(self)
     }
+
+    /// Write the config to a file, replacing its current contents
+    ///
+    /// The file is created with permissions `0600`, and replaced atomically so that concurrent readers never see a partial file.
+    /// To write changes to a config merged from several files, use [`KubeconfigFiles`](super::KubeconfigFiles).
+    pub fn write_to<P: AsRef<Path>>(&self, path: P) -> Result<(), KubeconfigError> {
+        let path = path.as_ref();
+        let mut config = self.clone();
+        config.kind.get_or_insert_with(|| "Config".into());
+        config.api_version.get_or_insert_with(|| "v1".into());
+        let data = serde_yaml::to_string(&config).map_err(KubeconfigError::Serialize)?;
+        write_path(path, data.as_bytes()).map_err(|source| KubeconfigError::WriteConfig(source, path.into()))
+    }
+
+    /// Use the context with the given name by default, like `kubectl config use-context`
+    pub fn set_current_context(&mut self, context: impl Into<String>) {
+        self.current_context = Some(context.into());
+    }
+
+    /// Add a cluster, or replace the cluster with the same name, like `kubectl config set-cluster`
+    pub fn set_cluster(&mut self, name: impl Into<String>, cluster: Cluster) {
+        let name = name.into();
+        match self.clusters.iter_mut().find(|c| c.name == name) {
+            Some(named) => named.cluster = Some(cluster),
+            None => self.clusters.push(NamedCluster {
+                name,
+                cluster: Some(cluster),
+            }),
+        }
+    }
+
+    /// Add a user, or replace the user with the same name, like `kubectl config set-credentials`
+    pub fn set_auth_info(&mut self, name: impl Into<String>, auth_info: AuthInfo) {
+        let name = name.into();
+        match self.auth_infos.iter_mut().find(|a| a.name == name) {
+            Some(named) => named.auth_info = Some(auth_info),
+            None => self.auth_infos.push(NamedAuthInfo {
+                name,
+                auth_info: Some(auth_info),
+            }),
+        }
+    }
+
+    /// Add a context, or replace the context with the same name, like `kubectl config set-context`
+    pub fn set_context(&mut self, name: impl Into<String>, context: Context) {
+        let name = name.into();
+        match self.contexts.iter_mut().find(|c| c.name == name) {
+            Some(named) => named.context = Some(context),
+            None => self.contexts.push(NamedContext {
+                name,
+                context: Some(context),
+            }),
+        }
+    }
+
+    /// Set the default namespace of an existing context, like `kubectl config set-context --namespace`
+    pub fn set_namespace(
+        &mut self,
+        context: &str,
+        namespace: impl Into<String>,
+    ) -> Result<(), KubeconfigError> {
+        let context = self
+            .context_mut(context)
+            .ok_or_else(|| KubeconfigError::MissingContext(context.to_owned()))?;
+        context.namespace = Some(namespace.into());
+        Ok(())
+    }
+
+    /// Mutable access to the cluster with the given name
+    pub fn cluster_mut(&mut self, name: &str) -> Option<&mut Cluster> {
+        self.clusters
+            .iter_mut()
+            .find(|c| c.name == name)?
+            .cluster
+            .as_mut()
+    }
+
+    /// Mutable access to the user with the given name, e.g. to store refreshed tokens
+    pub fn auth_info_mut(&mut self, name: &str) -> Option<&mut AuthInfo> {
+        self.auth_infos
+            .iter_mut()
+            .find(|a| a.name == name)?
+            .auth_info
+            .as_mut()
+    }
+
+    /// Mutable access to the context with the given name
+    pub fn context_mut(&mut self, name: &str) -> Option<&mut Context> {
+        self.contexts
+            .iter_mut()
+            .find(|c| c.name == name)?
+            .context
+            .as_mut()
+    }
+
+    /// Remove the cluster with the given name, like `kubectl config delete-cluster`
+    pub fn remove_cluster(&mut self, name: &str) -> Option<NamedCluster> {
+        let index = self.clusters.iter().position(|c| c.name == name)?;
+        Some(self.clusters.remove(index))
+    }
+
+    /// Remove the user with the given name, like `kubectl config delete-user`
+    pub fn remove_auth_info(&mut self, name: &str) -> Option<NamedAuthInfo> {
+        let index = self.auth_infos.iter().position(|a| a.name == name)?;
+        Some(self.auth_infos.remove(index))
+    }
+
+    /// Remove the context with the given name, like `kubectl config delete-context`
+    pub fn remove_context(&mut self, name: &str) -> Option<NamedContext> {
+        let index = self.contexts.iter().position(|c| c.name == name)?;
+        Some(self.contexts.remove(index))
+    }
 }
 
 fn kubeconfig_from_yaml(text: &str) -> Result<Vec<Kubeconfig>, KubeconfigError> {
@@ -524,6 +655,26 @@ fn read_path<P: AsRef<Path>>(path: P) -> io::Result<String> {
     }
 }
 
+fn write_path(path: &Path, data: &[u8]) -> io::Result<()> {
+    // Write through symlinks, like kubectl does
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_owned());
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension(format!("tmp{}", std::process::id()));
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(&tmp)?;
+    io::Write::write_all(&mut file, data)?;
+    drop(file);
+    fs::rename(&tmp, &path)
+}
+
 fn to_absolute(dir: &Path, file: &str) -> Option<String> {
     let path = Path::new(&file);
     if path.is_relative() {
@@ -533,7 +684,25 @@ fn to_absolute(dir: &Path, file: &str) -> Option<String> {
     }
 }
 
+/// Undo [`to_absolute`] for a path that still resolves to the one originally written in the file
+fn to_original(dir: &Path, original: &Option<String>, path: &mut Option<String>) {
+    if let Some(original) = original {
+        if path.is_some() && to_absolute(dir, original) == *path {
+            *path = Some(original.clone());
+        }
+    }
+}
+
 impl Cluster {
+    /// Restore the relative paths of `original`, as written in a file in `dir`, that were made absolute on read
+    pub(crate) fn unresolve_paths(&mut self, original: &Self, dir: &Path) {
+        to_original(
+            dir,
+            &original.certificate_authority,
+            &mut self.certificate_authority,
+        );
+    }
+
     pub(crate) fn load_certificate_authority(&self) -> Result<Option<Vec<u8>>, KubeconfigError> {
         if self.certificate_authority.is_none() && self.certificate_authority_data.is_none() {
             return Ok(None);
@@ -549,6 +718,13 @@ impl Cluster {
 }
 
 impl AuthInfo {
+    /// Restore the relative paths of `original`, as written in a file in `dir`, that were made absolute on read
+    pub(crate) fn unresolve_paths(&mut self, original: &Self, dir: &Path) {
+        to_original(dir, &original.client_certificate, &mut self.client_certificate);
+        to_original(dir, &original.client_key, &mut self.client_key);
+        to_original(dir, &original.token_file, &mut self.token_file);
+    }
+
     pub(crate) fn identity_pem(&self) -> Result<Vec<u8>, KubeconfigError> {
         let client_cert = &self.load_client_certificate()?;
         let client_key = &self.load_client_key()?;
@@ -557,6 +733,17 @@ impl AuthInfo {
         Ok(buffer)
     }
 
+    /// Paths of the client certificate and key, when the identity is read from files rather than inline data
+    pub(crate) fn identity_files(&self) -> Option<(PathBuf, PathBuf)> {
+        if self.client_certificate_data.is_some() || self.client_key_data.is_some() {
+            return None;
+        }
+        Some((
+            PathBuf::from(self.client_certificate.as_ref()?),
+            PathBuf::from(self.client_key.as_ref()?),
+        ))
+    }
+
     pub(crate) fn load_client_certificate(&self) -> Result<Vec<u8>, KubeconfigError> {
         // TODO Shouldn't error when `self.client_certificate_data.is_none() && self.client_certificate.is_none()`
 
@@ -658,8 +845,13 @@ fn ensure_trailing_newline(mut data: Vec<u8>) -> Vec<u8> {
     data
 }
 
+/// Returns the exec credential cache directory `$HOME/.kube/cache/kube-rs/exec`.
+pub(crate) fn default_exec_cache_dir() -> Option<PathBuf> {
+    home::home_dir().map(|h| h.join(".kube").join("cache").join("kube-rs").join("exec"))
+}
+
 /// Returns kubeconfig path from `$HOME/.kube/config`.
-fn default_kube_path() -> Option<PathBuf> {
+pub(crate) fn default_kube_path() -> Option<PathBuf> {
     home::home_dir().map(|h| h.join(".kube").join("config"))
 }
 
@@ -946,6 +1138,8 @@ password: kube_rs
         client_certificate_data: None, client_key: None, \
         client_key_data: None, impersonate: None, \
         impersonate_groups: None, \
+        impersonate_uid: None, \
+        impersonate_user_extra: None, \
         auth_provider: None, \
         exec: None \
         }";
@@ -990,6 +1184,10 @@ users:
         let auth_info = config_loader.user;
         let exec = auth_info.exec.unwrap();
         assert!(exec.provide_cluster_info);
+        assert!(
+            exec.credential_cache.is_none(),
+            "credentials are not cached by default"
+        );
         let cluster = exec.cluster.unwrap();
         assert_eq!(
             cluster.config.unwrap(),
@@ -0,0 +1,457 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+
+use super::{
+    file_config::{default_kube_path, Kubeconfig, KUBECONFIG},
+    KubeconfigError,
+};
+
+/// The files a [`Kubeconfig`] is merged from
+///
+/// Edits to the merged config are written back to the files the same way `kubectl config set-*` does:
+///
+/// - changes to an existing cluster, user or context go to the first file defining it,
+/// - new entries and a new `current-context` go to the first file that exists (or the last file if none do),
+///   unless a file already sets `current-context`,
+/// - removed entries are removed from every file defining them.
+///
+/// ```no_run
+/// use kube::config::KubeconfigFiles;
+///
+/// # fn wrapper() -> Result<(), kube::config::KubeconfigError> {
+/// let mut files = KubeconfigFiles::read()?;
+/// let mut kubeconfig = files.kubeconfig()?;
+/// kubeconfig.set_current_context("prod");
+/// kubeconfig.set_namespace("prod", "monitoring")?;
+/// files.write(&kubeconfig)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct KubeconfigFiles {
+    files: Vec<File>,
+}
+
+#[derive(Clone, Debug)]
+struct File {
+    path: PathBuf,
+    exists: bool,
+    /// The contents as written in the file, without resolving relative paths
+    config: Kubeconfig,
+}
+
+impl KubeconfigFiles {
+    /// Read the files in the given order of precedence
+    ///
+    /// Files that do not exist are treated as empty, and are created when they are written to.
+    pub fn from_paths<I, P>(paths: I) -> Result<Self, KubeconfigError>
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        let files = paths
+            .into_iter()
+            .map(|path| {
+                let path = path.into();
+                match Kubeconfig::read_unresolved(&path) {
+                    Ok(config) => Ok(File {
+                        path,
+                        exists: true,
+                        config,
+                    }),
+                    Err(KubeconfigError::ReadConfig(err, _)) if err.kind() == io::ErrorKind::NotFound => {
+                        Ok(File {
+                            path,
+                            exists: false,
+                            config: Kubeconfig::default(),
+                        })
+                    }
+                    Err(err) => Err(err),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if files.is_empty() {
+            return Err(KubeconfigError::FindPath);
+        }
+        Ok(Self { files })
+    }
+
+    /// Read the files listed in the `KUBECONFIG` environment variable
+    pub fn from_env() -> Result<Option<Self>, KubeconfigError> {
+        let Some(value) = std::env::var_os(KUBECONFIG) else {
+            return Ok(None);
+        };
+        let paths = std::env::split_paths(&value)
+            .filter(|p| !p.as_os_str().is_empty())
+            .collect::<Vec<_>>();
+        if paths.is_empty() {
+            return Ok(None);
+        }
+        Self::from_paths(paths).map(Some)
+    }
+
+    /// Read the files listed in `KUBECONFIG`, or the default location
+    pub fn read() -> Result<Self, KubeconfigError> {
+        match Self::from_env()? {
+            Some(files) => Ok(files),
+            None => Self::from_paths([default_kube_path().ok_or(KubeconfigError::FindPath)?]),
+        }
+    }
+
+    /// The paths of the files, in order of precedence
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.files.iter().map(|f| f.path.as_path())
+    }
+
+    /// The merged config, as returned by [`Kubeconfig::read`]
+    pub fn kubeconfig(&self) -> Result<Kubeconfig, KubeconfigError> {
+        self.files.iter().try_fold(Kubeconfig::default(), |merged, file| {
+            let mut config = file.config.clone();
+            if let Some(dir) = file.path.parent() {
+                config.resolve_paths(dir);
+            }
+            merged.merge(config)
+        })
+    }
+
+    /// Write the changes made to the merged config back to the files they belong to
+    ///
+    /// Only files with changes are written. If writing a file fails, the changes are kept
+    /// until they are written, so writing again retries all of them.
+    pub fn write(&mut self, kubeconfig: &Kubeconfig) -> Result<(), KubeconfigError> {
+        let start = self.kubeconfig()?;
+        let default = self.default_file();
+        let mut files = self.files.clone();
+        let mut dirty = vec![false; files.len()];
+
+        let mut write_back = WriteBack {
+            files: &mut files,
+            dirty: &mut dirty,
+            default,
+        };
+        write_back.named(
+            &start.clusters,
+            &kubeconfig.clusters,
+            |c| &mut c.clusters,
+            |c| &c.name,
+            |edited, original, dir| {
+                if let (Some(edited), Some(original)) = (&mut edited.cluster, &original.cluster) {
+                    edited.unresolve_paths(original, dir);
+                }
+            },
+        );
+        write_back.named(
+            &start.auth_infos,
+            &kubeconfig.auth_infos,
+            |c| &mut c.auth_infos,
+            |a| &a.name,
+            |edited, original, dir| {
+                if let (Some(edited), Some(original)) = (&mut edited.auth_info, &original.auth_info) {
+                    edited.unresolve_paths(original, dir);
+                }
+            },
+        );
+        write_back.named(
+            &start.contexts,
+            &kubeconfig.contexts,
+            |c| &mut c.contexts,
+            |c| &c.name,
+            |_, _, _| {},
+        );
+
+        if start.current_context != kubeconfig.current_context {
+            match &kubeconfig.current_context {
+                Some(context) => {
+                    let index = files
+                        .iter()
+                        .position(|f| f.config.current_context.is_some())
+                        .unwrap_or(default);
+                    files[index].config.current_context = Some(context.clone());
+                    dirty[index] = true;
+                }
+                // Clear it everywhere, otherwise the next file would take over
+                None => {
+                    for (file, dirty) in files.iter_mut().zip(&mut dirty) {
+                        if file.config.current_context.take().is_some() {
+                            *dirty = true;
+                        }
+                    }
+                }
+            }
+        }
+
+        for (file, dirty) in files.iter_mut().zip(dirty) {
+            if dirty {
+                file.config.write_to(&file.path)?;
+                file.exists = true;
+            }
+        }
+        self.files = files;
+        Ok(())
+    }
+
+    /// The file new entries are written to, matching client-go
+    fn default_file(&self) -> usize {
+        self.files
+            .iter()
+            .position(|f| f.exists)
+            .unwrap_or(self.files.len() - 1)
+    }
+}
+
+struct WriteBack<'a> {
+    files: &'a mut [File],
+    dirty: &'a mut [bool],
+    default: usize,
+}
+
+impl WriteBack<'_> {
+    /// Apply the difference between two lists of named entries to the files
+    ///
+    /// The merged config has relative paths resolved against the directory of their file,
+    /// `unresolve` restores the ones an edited entry did not change, like client-go does.
+    fn named<T: Clone + Serialize>(
+        &mut self,
+        start: &[T],
+        edited: &[T],
+        list: fn(&mut Kubeconfig) -> &mut Vec<T>,
+        name: fn(&T) -> &String,
+        unresolve: fn(&mut T, &T, &Path),
+    ) {
+        for entry in edited {
+            let unchanged = start
+                .iter()
+                .find(|s| name(s) == name(entry))
+                .is_some_and(|s| same(s, entry));
+            if unchanged {
+                continue;
+            }
+            let index = self
+                .files
+                .iter_mut()
+                .position(|f| list(&mut f.config).iter().any(|e| name(e) == name(entry)))
+                .unwrap_or(self.default);
+            let file = &mut self.files[index];
+            let entries = list(&mut file.config);
+            match entries.iter_mut().find(|e| name(e) == name(entry)) {
+                Some(existing) => {
+                    let mut entry = entry.clone();
+                    if let Some(dir) = file.path.parent() {
+                        unresolve(&mut entry, existing, dir);
+                    }
+                    *existing = entry;
+                }
+                None => entries.push(entry.clone()),
+            }
+            self.dirty[index] = true;
+        }
+
+        for removed in start
+            .iter()
+            .filter(|s| !edited.iter().any(|e| name(e) == name(s)))
+        {
+            for (file, dirty) in self.files.iter_mut().zip(self.dirty.iter_mut()) {
+                let entries = list(&mut file.config);
+                let len = entries.len();
+                entries.retain(|e| name(e) != name(removed));
+                *dirty |= entries.len() != len;
+            }
+        }
+    }
+}
+
+// Not all config types implement `PartialEq`, and secrets are deliberately left out of it
+fn same<T: Serialize>(a: &T, b: &T) -> bool {
+    match (serde_yaml::to_value(a), serde_yaml::to_value(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use secrecy::{ExposeSecret, SecretString};
+
+    use super::*;
+    use crate::config::{AuthInfo, Cluster, Context};
+
+    #[test]
+    fn kubeconfig_files_write_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = dir.path().join("first");
+        let second = dir.path().join("second");
+        fs::write(
+            &first,
+            r#"
+            clusters:
+            - name: dev
+              cluster:
+                server: https://dev.example.com
+            contexts:
+            - name: dev
+              context:
+                cluster: dev
+                user: dev
+            current-context: dev
+            "#,
+        )
+        .unwrap();
+        fs::write(
+            &second,
+            r#"
+            clusters:
+            - name: prod
+              cluster:
+                server: https://prod.example.com
+                certificate-authority: ca.crt
+            users:
+            - name: dev
+              user:
+                token: old
+                client-certificate: certs/dev.crt
+                client-key: /etc/kube/dev.key
+            "#,
+        )
+        .unwrap();
+
+        let mut files = KubeconfigFiles::from_paths([&first, &second]).unwrap();
+        let mut kubeconfig = files.kubeconfig().unwrap();
+        kubeconfig.auth_info_mut("dev").unwrap().token = Some(SecretString::new("new".into()));
+        kubeconfig.set_context("prod", Context {
+            cluster: "prod".into(),
+            user: Some("dev".into()),
+            ..Context::default()
+        });
+        kubeconfig.set_namespace("prod", "monitoring").unwrap();
+        kubeconfig.set_current_context("prod");
+        files.write(&kubeconfig).unwrap();
+
+        let first = Kubeconfig::read_unresolved(&first).unwrap();
+        assert_eq!(first.current_context.as_deref(), Some("prod"));
+        assert_eq!(first.contexts.len(), 2);
+        assert_eq!(
+            first.contexts[1].context.as_ref().unwrap().namespace.as_deref(),
+            Some("monitoring")
+        );
+        assert!(first.auth_infos.is_empty());
+
+        let second = Kubeconfig::read_unresolved(&second).unwrap();
+        let token = second.auth_infos[0]
+            .auth_info
+            .as_ref()
+            .unwrap()
+            .token
+            .as_ref()
+            .unwrap();
+        assert_eq!(token.expose_secret(), "new");
+        // Edited entries keep their relative paths too
+        let dev = second.auth_infos[0].auth_info.as_ref().unwrap();
+        assert_eq!(dev.client_certificate.as_deref(), Some("certs/dev.crt"));
+        assert_eq!(dev.client_key.as_deref(), Some("/etc/kube/dev.key"));
+        // Entries that were not changed keep their relative paths
+        let prod = second.clusters[0].cluster.as_ref().unwrap();
+        assert_eq!(prod.certificate_authority.as_deref(), Some("ca.crt"));
+        assert!(second.contexts.is_empty());
+
+        assert_eq!(
+            files.kubeconfig().unwrap().current_context.as_deref(),
+            Some("prod")
+        );
+    }
+
+    #[test]
+    fn kubeconfig_files_retry_failed_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = dir.path().join("first");
+        let second = dir.path().join("second");
+        let contents = r#"
+            users:
+            - name: dev
+              user:
+                token: old
+            "#;
+        fs::write(
+            &first,
+            r#"
+            contexts:
+            - name: dev
+              context:
+                cluster: dev
+                user: dev
+            "#,
+        )
+        .unwrap();
+        fs::write(&second, contents).unwrap();
+
+        let mut files = KubeconfigFiles::from_paths([&first, &second]).unwrap();
+        let mut kubeconfig = files.kubeconfig().unwrap();
+        kubeconfig.auth_info_mut("dev").unwrap().token = Some(SecretString::new("new".into()));
+        kubeconfig.set_namespace("dev", "monitoring").unwrap();
+        // A directory cannot be replaced by the written file
+        fs::remove_file(&second).unwrap();
+        fs::create_dir(&second).unwrap();
+        fs::write(second.join("keep"), "").unwrap();
+        assert!(files.write(&kubeconfig).is_err());
+
+        fs::remove_dir_all(&second).unwrap();
+        fs::write(&second, contents).unwrap();
+        files.write(&kubeconfig).unwrap();
+        let written = Kubeconfig::read_unresolved(&first).unwrap();
+        let context = written.contexts[0].context.as_ref().unwrap();
+        assert_eq!(context.namespace.as_deref(), Some("monitoring"));
+        let written = Kubeconfig::read_unresolved(&second).unwrap();
+        let token = written.auth_infos[0].auth_info.as_ref().unwrap().token.as_ref();
+        assert_eq!(token.unwrap().expose_secret(), "new");
+    }
+
+    #[test]
+    fn kubeconfig_files_create_missing() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join("config");
+        let mut files = KubeconfigFiles::from_paths([&path]).unwrap();
+        let mut kubeconfig = files.kubeconfig().unwrap();
+        assert!(kubeconfig.set_namespace("kind", "default").is_err());
+
+        kubeconfig.set_cluster("kind", Cluster {
+            server: Some("https://127.0.0.1:6443".into()),
+            ..Cluster::default()
+        });
+        kubeconfig.set_auth_info("kind", AuthInfo::default());
+        kubeconfig.set_context("kind", Context {
+            cluster: "kind".into(),
+            user: Some("kind".into()),
+            ..Context::default()
+        });
+        kubeconfig.set_current_context("kind");
+        files.write(&kubeconfig).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let written = Kubeconfig::read_from(&path).unwrap();
+        assert_eq!(written.kind.as_deref(), Some("Config"));
+        assert_eq!(written.current_context.as_deref(), Some("kind"));
+        assert_eq!(
+            written.clusters[0].cluster.as_ref().unwrap().server.as_deref(),
+            Some("https://127.0.0.1:6443")
+        );
+
+        let mut kubeconfig = files.kubeconfig().unwrap();
+        assert!(kubeconfig.remove_context("kind").is_some());
+        kubeconfig.current_context = None;
+        files.write(&kubeconfig).unwrap();
+        let written = Kubeconfig::read_from(&path).unwrap();
+        assert!(written.contexts.is_empty());
+        assert!(written.current_context.is_none());
+        assert_eq!(written.clusters.len(), 1);
+    }
+}
@@ -0,0 +1,206 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use http::{HeaderName, HeaderValue};
+
+use super::AuthInfo;
+
+const IMPERSONATE_USER: &str = "impersonate-user";
+const IMPERSONATE_GROUP: &str = "impersonate-group";
+const IMPERSONATE_UID: &str = "impersonate-uid";
+const IMPERSONATE_EXTRA_PREFIX: &str = "impersonate-extra-";
+
+/// The identity to act as when talking to the apiserver
+///
+/// Requests are sent with the credentials of the authenticated user, but authorized as the impersonated one.
+/// The authenticated user needs the `impersonate` verb on the impersonated `users`, `groups`, `uids` and `userextras`.
+///
+/// See <https://kubernetes.io/docs/reference/access-authn-authz/authentication/#user-impersonation>
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Impersonation {
+    /// The username to act as
+    pub user: String,
+    /// The groups to act as
+    pub groups: Vec<String>,
+    /// The uid to act as
+    pub uid: Option<String>,
+    /// Extra fields of the impersonated user, such as scopes
+    pub extra: BTreeMap<String, Vec<String>>,
+}
+
+impl Impersonation {
+    /// Impersonate the given user
+    pub fn user(user: impl Into<String>) -> Self {
+        Self {
+            user: user.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Also impersonate the given groups
+    #[must_use]
+    pub fn groups<I, S>(mut self, groups: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.groups.extend(groups.into_iter().map(Into::into));
+        self
+    }
+
+    /// Also impersonate the given uid
+    #[must_use]
+    pub fn uid(mut self, uid: impl Into<String>) -> Self {
+        self.uid = Some(uid.into());
+        self
+    }
+
+    /// Also impersonate an extra field of the user
+    #[must_use]
+    pub fn extra<I, S>(mut self, key: impl Into<String>, values: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.extra
+            .entry(key.into())
+            .or_default()
+            .extend(values.into_iter().map(Into::into));
+        self
+    }
+
+    /// Read the `as`, `as-groups`, `as-uid` and `as-user-extra` fields of a kubeconfig user
+    ///
+    /// Returns `None` when no user is impersonated.
+    pub(crate) fn from_auth_info(auth_info: &AuthInfo) -> Option<Self> {
+        let user = auth_info.impersonate.clone()?;
+        Some(Self {
+            user,
+            groups: auth_info.impersonate_groups.clone().unwrap_or_default(),
+            uid: auth_info.impersonate_uid.clone(),
+            extra: auth_info
+                .impersonate_user_extra
+                .clone()
+                .unwrap_or_default()
+                .into_iter()
+                .collect(),
+        })
+    }
+
+    /// Write back into the fields of a kubeconfig user
+    pub(crate) fn apply_to(self, auth_info: &mut AuthInfo) {
+        auth_info.impersonate = Some(self.user);
+        auth_info.impersonate_groups = (!self.groups.is_empty()).then_some(self.groups);
+        auth_info.impersonate_uid = self.uid;
+        auth_info.impersonate_user_extra = (!self.extra.is_empty()).then(|| self.extra.into_iter().collect());
+    }
+
+    /// The `Impersonate-*` headers to send with each request
+    pub fn headers(&self) -> Result<Vec<(HeaderName, HeaderValue)>, http::Error> {
+        let mut headers = vec![(
+            HeaderName::from_static(IMPERSONATE_USER),
+            HeaderValue::from_str(&self.user)?,
+        )];
+        for group in &self.groups {
+            headers.push((
+                HeaderName::from_static(IMPERSONATE_GROUP),
+                HeaderValue::from_str(group)?,
+            ));
+        }
+        if let Some(uid) = &self.uid {
+            headers.push((
+                HeaderName::from_static(IMPERSONATE_UID),
+                HeaderValue::from_str(uid)?,
+            ));
+        }
+        for (key, values) in &self.extra {
+            let name = HeaderName::from_bytes(extra_header_name(key).as_bytes())?;
+            for value in values {
+                headers.push((name.clone(), HeaderValue::from_str(value)?));
+            }
+        }
+        Ok(headers)
+    }
+
+    /// Whether the header is one of the headers set by [`Impersonation::headers`]
+    #[cfg(feature = "client")]
+    pub(crate) fn is_impersonation_header(name: &HeaderName) -> bool {
+        name.as_str().starts_with("impersonate-")
+    }
+
+    /// Whether the header overrides any impersonation configured further down the stack
+    #[cfg(feature = "client")]
+    pub(crate) fn is_user_header(name: &HeaderName) -> bool {
+        name.as_str() == IMPERSONATE_USER
+    }
+}
+
+/// Extra keys are escaped like path segments since header names only allow a small set of characters
+///
+/// Matches client-go, which uses `url.PathEscape` on the key.
+fn extra_header_name(key: &str) -> String {
+    let mut name = String::from(IMPERSONATE_EXTRA_PREFIX);
+    for byte in key.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+            name.push(char::from(byte));
+        } else {
+            let _ = write!(name, "%{byte:02X}");
+        }
+    }
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn impersonation_headers() {
+        let imp = Impersonation::user("jane")
+            .groups(["devs", "system:authenticated"])
+            .uid("1234")
+            .extra("scopes.example.com/team", ["a", "b"]);
+        let headers = imp
+            .headers()
+            .unwrap()
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_str().unwrap().to_string()))
+            .collect::<Vec<_>>();
+        let expected = [
+            ("impersonate-user", "jane"),
+            ("impersonate-group", "devs"),
+            ("impersonate-group", "system:authenticated"),
+            ("impersonate-uid", "1234"),
+            ("impersonate-extra-scopes.example.com%2fteam", "a"),
+            ("impersonate-extra-scopes.example.com%2fteam", "b"),
+        ]
+        .map(|(k, v)| (k.to_string(), v.to_string()));
+        assert_eq!(headers, expected);
+    }
+
+    #[test]
+    fn impersonation_from_kubeconfig_user() {
+        let auth_info: AuthInfo = serde_yaml::from_str(
+            r#"
+            as: jane
+            as-groups: [devs]
+            as-uid: "1234"
+            as-user-extra:
+              reason: [debugging]
+            "#,
+        )
+        .unwrap();
+        let imp = Impersonation::from_auth_info(&auth_info).unwrap();
+        assert_eq!(
+            imp,
+            Impersonation::user("jane")
+                .groups(["devs"])
+                .uid("1234")
+                .extra("reason", ["debugging"])
+        );
+
+        let mut roundtrip = AuthInfo::default();
+        imp.apply_to(&mut roundtrip);
+        assert_eq!(roundtrip, auth_info);
+        assert!(Impersonation::from_auth_info(&AuthInfo::default()).is_none());
+    }
+}
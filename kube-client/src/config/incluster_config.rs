@@ -1,4 +1,4 @@
-use std::env;
+use std::{env, path::PathBuf};
 use thiserror::Error;
 
 const SERVICE_HOSTENV: &str = "KUBERNETES_SERVICE_HOST";
@@ -43,6 +43,12 @@ pub(super) fn kube_dns() -> http::Uri {
     http::Uri::from_static("https://kubernetes.default.svc/")
 }
 
+/// Whether the host is the address of the in-cluster Kubernetes API server
+pub(super) fn is_service_host(host: &str) -> bool {
+    host == "kubernetes.default.svc"
+        || env::var(SERVICE_HOSTENV).is_ok_and(|service_host| service_host == host)
+}
+
 /// Returns the URI of the Kubernetes API server by reading the
 /// `KUBERNETES_SERVICE_HOST` and `KUBERNETES_SERVICE_PORT` environment
 /// variables.
@@ -91,6 +97,10 @@ pub fn token_file() -> String {
     SERVICE_TOKENFILE.to_owned()
 }
 
+pub fn cert_file() -> PathBuf {
+    PathBuf::from(SERVICE_CERTFILE)
+}
+
 /// Returns certification from specified path in cluster.
 pub fn load_cert() -> Result<Vec<Vec<u8>>, Error> {
     let certs = std::fs::read(SERVICE_CERTFILE).map_err(Error::ReadCertificateBundle)?;
@@ -7,17 +7,26 @@
 //! The [`Config`] has several constructors plus logic to infer environment.
 //!
 //! Unless you have issues, prefer using [`Config::infer`], and pass it to a [`Client`][crate::Client].
-use std::{path::PathBuf, time::Duration};
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 use http::{HeaderName, HeaderValue};
 use thiserror::Error;
 
 mod file_config;
 mod file_loader;
+mod file_writer;
+mod impersonation;
 mod incluster_config;
+mod proxy_env;
+pub(crate) mod unix_socket;
 
 use file_loader::ConfigLoader;
 pub use file_loader::KubeConfigOptions;
+pub use file_writer::KubeconfigFiles;
+pub use impersonation::Impersonation;
 pub use incluster_config::Error as InClusterError;
 
 /// Failed to infer config
@@ -61,6 +70,18 @@ pub enum KubeconfigError {
     #[error("failed to read kubeconfig from '{1:?}': {0}")]
     ReadConfig(#[source] std::io::Error, PathBuf),
 
+    /// Failed to write kubeconfig
+    #[error("failed to write kubeconfig to '{1:?}': {0}")]
+    WriteConfig(#[source] std::io::Error, PathBuf),
+
+    /// Failed to serialize kubeconfig YAML
+    #[error("failed to serialize kubeconfig YAML: {0}")]
+    Serialize(#[source] serde_yaml::Error),
+
+    /// The context to modify does not exist
+    #[error("context '{0}' does not exist")]
+    MissingContext(String),
+
     /// Failed to parse kubeconfig YAML
     #[error("failed to parse kubeconfig YAML: {0}")]
     Parse(#[source] serde_yaml::Error),
@@ -135,6 +156,10 @@ pub struct Config {
     pub default_namespace: String,
     /// The configured root certificate
     pub root_cert: Option<Vec<Vec<u8>>>,
+    /// The file the root certificate was read from, if any
+    ///
+    /// When set, TLS connectors periodically re-read the file so that a rotated CA bundle replaces `root_cert`.
+    pub root_cert_file: Option<PathBuf>,
     /// Set the timeout for connecting to the Kubernetes API.
     ///
     /// A value of `None` means no timeout
@@ -153,7 +178,9 @@ pub struct Config {
     pub auth_info: AuthInfo,
     /// Whether to disable compression (would only have an effect when the `gzip` feature is enabled)
     pub disable_compression: bool,
-    /// Optional proxy URL. Proxy support requires the `socks5` feature.
+    /// Optional proxy URL. Proxy support requires the `socks5` or `http-proxy` feature.
+    ///
+    /// When unset, the `HTTPS_PROXY`, `HTTP_PROXY` and `NO_PROXY` environment variables are used, see [`Config::proxy`].
     pub proxy_url: Option<http::Uri>,
     /// If set, apiserver certificate will be validated to contain this string
     ///
@@ -174,6 +201,7 @@ impl Config {
             cluster_url,
             default_namespace: String::from("default"),
             root_cert: None,
+            root_cert_file: None,
             connect_timeout: Some(DEFAULT_CONNECT_TIMEOUT),
             read_timeout: Some(DEFAULT_READ_TIMEOUT),
             write_timeout: Some(DEFAULT_WRITE_TIMEOUT),
@@ -186,6 +214,31 @@ impl Config {
         }
     }
 
+    /// Construct a new config for an apiserver listening on a Unix domain socket
+    ///
+    /// This is useful with `kubectl proxy --unix-socket` or agents exposing the apiserver on a local socket.
+    /// Requests, including upgrades for exec and portforward, are sent as plain HTTP over the socket.
+    ///
+    /// The socket path is hex encoded into the host of a `unix` [`Config::cluster_url`],
+    /// since URIs cannot contain a path in that position. Kubeconfig servers like `unix:///path/to/socket`
+    /// are read the same way.
+    ///
+    /// ```rust
+    /// # use kube::Config;
+    /// let config = Config::new_unix_socket("/var/run/kubectl-proxy.sock");
+    /// assert_eq!(config.unix_socket().unwrap().to_str(), Some("/var/run/kubectl-proxy.sock"));
+    /// ```
+    pub fn new_unix_socket(path: impl AsRef<Path>) -> Self {
+        let cluster_url =
+            unix_socket::url(path.as_ref(), "/").expect("hex encoded socket path is a valid uri");
+        Self::new(cluster_url)
+    }
+
+    /// The Unix domain socket the apiserver is reached through, if [`Config::cluster_url`] is a `unix` url
+    pub fn unix_socket(&self) -> Option<PathBuf> {
+        unix_socket::path(&self.cluster_url)
+    }
+
     /// Infer a Kubernetes client configuration.
     ///
     /// First, a user's kubeconfig is loaded from `KUBECONFIG` or
@@ -254,6 +307,7 @@ impl Config {
             cluster_url,
             default_namespace,
             root_cert: Some(root_cert),
+            root_cert_file: Some(incluster_config::cert_file()),
             connect_timeout: Some(DEFAULT_CONNECT_TIMEOUT),
             read_timeout: Some(DEFAULT_READ_TIMEOUT),
             write_timeout: Some(DEFAULT_WRITE_TIMEOUT),
@@ -291,13 +345,12 @@ impl Config {
     }
 
     async fn new_from_loader(loader: ConfigLoader) -> Result<Self, KubeconfigError> {
-        let cluster_url = loader
+        let server = loader
             .cluster
             .server
-            .clone()
-            .ok_or(KubeconfigError::MissingClusterUrl)?
-            .parse::<http::Uri>()
-            .map_err(KubeconfigError::ParseClusterUrl)?;
+            .as_deref()
+            .ok_or(KubeconfigError::MissingClusterUrl)?;
+        let cluster_url = unix_socket::parse_server(server).map_err(KubeconfigError::ParseClusterUrl)?;
 
         let default_namespace = loader
             .current_context
@@ -313,11 +366,17 @@ impl Config {
         if let Some(ca_bundle) = loader.ca_bundle()? {
             root_cert = Some(ca_bundle);
         }
+        // Inline data takes precedence over the file, so only watch the file when it was actually used
+        let root_cert_file = match &loader.cluster.certificate_authority_data {
+            None => loader.cluster.certificate_authority.as_ref().map(PathBuf::from),
+            Some(_) => None,
+        };
 
         Ok(Self {
             cluster_url,
             default_namespace,
             root_cert,
+            root_cert_file,
             connect_timeout: Some(DEFAULT_CONNECT_TIMEOUT),
             read_timeout: Some(DEFAULT_READ_TIMEOUT),
             write_timeout: Some(DEFAULT_WRITE_TIMEOUT),
@@ -330,6 +389,52 @@ impl Config {
         })
     }
 
+    /// The identity requests are impersonating, if any
+    ///
+    /// This is read from the `as`, `as-groups`, `as-uid` and `as-user-extra` fields of the kubeconfig user.
+    pub fn impersonation(&self) -> Option<Impersonation> {
+        Impersonation::from_auth_info(&self.auth_info)
+    }
+
+    /// Impersonate another identity for all requests made with this config
+    ///
+    /// Replaces any impersonation configured in the kubeconfig.
+    /// To impersonate for only some requests, see [`Client::impersonate`](crate::Client::impersonate).
+    ///
+    /// ```rust
+    /// # use kube::config::{Config, Impersonation};
+    /// let mut config = Config::new("https://10.0.0.1".parse().unwrap());
+    /// config.impersonate(Impersonation::user("jane").groups(["devs"]));
+    /// ```
+    pub fn impersonate(&mut self, impersonation: Impersonation) {
+        impersonation.apply_to(&mut self.auth_info);
+    }
+
+    /// Persist credentials returned by the exec plugin in `~/.kube/cache/kube-rs/exec`
+    ///
+    /// Other processes using the same kubeconfig user then reuse the credentials until they expire,
+    /// rather than running the plugin again, like kubectl does with some plugins.
+    /// Tokens and client keys are written in plaintext, in files only readable by the current user.
+    ///
+    /// This has no effect unless the user authenticates with an exec plugin.
+    /// See [`ExecConfig::credential_cache`] to use another directory.
+    ///
+    /// ```rust,no_run
+    /// # async fn wrapper() -> Result<(), Box<dyn std::error::Error>> {
+    /// # use kube::{Client, Config};
+    /// let config = Config::infer().await?.cache_exec_credentials(true);
+    /// let client = Client::try_from(config)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn cache_exec_credentials(mut self, cache: bool) -> Self {
+        if let Some(exec) = &mut self.auth_info.exec {
+            exec.credential_cache = cache.then(file_config::default_exec_cache_dir).flatten();
+        }
+        self
+    }
+
     /// Override configuration based on environment variables
     ///
     /// This is only intended for use as a debugging aid, and the specific variables and their behaviour
@@ -368,6 +473,28 @@ impl Config {
         }
     }
 
+    /// The proxy to connect to the cluster through
+    ///
+    /// This is [`Config::proxy_url`] if set (from the kubeconfig `proxy-url`), and otherwise follows the
+    /// `HTTPS_PROXY`, `HTTP_PROXY` and `NO_PROXY` environment variables like client-go does.
+    /// `NO_PROXY` entries may be IP addresses, CIDR ranges, or domains which also match their subdomains.
+    ///
+    /// The in-cluster apiserver address from `KUBERNETES_SERVICE_HOST` is never proxied through the environment variables.
+    pub fn proxy(&self) -> Option<http::Uri> {
+        if let Some(proxy_url) = &self.proxy_url {
+            return Some(proxy_url.clone());
+        }
+        let host = self
+            .cluster_url
+            .host()?
+            .trim_start_matches('[')
+            .trim_end_matches(']');
+        if incluster_config::is_service_host(host) {
+            return None;
+        }
+        proxy_env::ProxyEnv::from_env().proxy_for(&self.cluster_url)
+    }
+
     /// Client certificate and private key in PEM.
     pub(crate) fn identity_pem(&self) -> Option<Vec<u8>> {
         self.auth_info.identity_pem().ok()
@@ -431,4 +558,22 @@ mod tests {
         let kubeconfig = Config::infer().await.unwrap();
         assert_eq!(kubeconfig.cluster_url, "https://0.0.0.0:6443/");
     }
+
+    #[test]
+    fn cache_exec_credentials() {
+        use super::{file_config::default_exec_cache_dir, Config, ExecConfig};
+        let mut config = Config::new("https://10.0.0.1".parse().unwrap());
+        config.auth_info.exec = Some(
+            serde_yaml::from_str::<ExecConfig>("command: foo\napiVersion: client.authentication.k8s.io/v1")
+                .unwrap(),
+        );
+        assert!(config.auth_info.exec.as_ref().unwrap().credential_cache.is_none());
+
+        let config = config.cache_exec_credentials(true);
+        let cache = &config.auth_info.exec.as_ref().unwrap().credential_cache;
+        assert_eq!(cache, &default_exec_cache_dir());
+
+        let config = config.cache_exec_credentials(false);
+        assert!(config.auth_info.exec.unwrap().credential_cache.is_none());
+    }
 }
@@ -0,0 +1,249 @@
+use std::net::IpAddr;
+
+/// Proxy settings from the `HTTPS_PROXY`, `HTTP_PROXY` and `NO_PROXY` environment variables
+///
+/// Follows the rules of Go's `http.ProxyFromEnvironment`, which client-go uses:
+///
+/// - uppercase variables take precedence over lowercase ones,
+/// - `HTTPS_PROXY` is used for `https` urls, `HTTP_PROXY` for `http` urls,
+/// - `NO_PROXY` is a comma separated list of IP addresses, CIDR ranges and domains (optionally with a port),
+///   where a domain also matches its subdomains, and `*` disables the proxy entirely,
+/// - `localhost` and loopback addresses are never proxied.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ProxyEnv {
+    https_proxy: Option<String>,
+    http_proxy: Option<String>,
+    no_proxy: Vec<NoProxy>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum NoProxy {
+    All,
+    Ip(IpAddr),
+    Cidr(IpAddr, u8),
+    Domain {
+        /// The domain with a leading dot
+        suffix: String,
+        /// Whether the domain itself matches, not only subdomains
+        exact: bool,
+        port: Option<u16>,
+    },
+}
+
+impl ProxyEnv {
+    pub(crate) fn from_env() -> Self {
+        Self::new(
+            get_env_any("HTTPS_PROXY", "https_proxy"),
+            get_env_any("HTTP_PROXY", "http_proxy"),
+            get_env_any("NO_PROXY", "no_proxy"),
+        )
+    }
+
+    fn new(https_proxy: Option<String>, http_proxy: Option<String>, no_proxy: Option<String>) -> Self {
+        Self {
+            https_proxy,
+            http_proxy,
+            no_proxy: no_proxy.as_deref().map(parse_no_proxy).unwrap_or_default(),
+        }
+    }
+
+    /// The proxy to use for the url, if any
+    pub(crate) fn proxy_for(&self, url: &http::Uri) -> Option<http::Uri> {
+        let proxy = match url.scheme_str() {
+            Some("https") => self.https_proxy.as_deref()?,
+            Some("http") => self.http_proxy.as_deref()?,
+            _ => return None,
+        };
+        let host = url
+            .host()?
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_ascii_lowercase();
+        let port = url.port_u16().unwrap_or(if url.scheme_str() == Some("https") {
+            443
+        } else {
+            80
+        });
+        if !self.use_proxy(&host, port) {
+            return None;
+        }
+        parse_proxy(proxy)
+    }
+
+    fn use_proxy(&self, host: &str, port: u16) -> bool {
+        if host == "localhost" {
+            return false;
+        }
+        let ip = host.parse::<IpAddr>().ok();
+        if ip.is_some_and(|ip| ip.is_loopback()) {
+            return false;
+        }
+        !self.no_proxy.iter().any(|entry| match entry {
+            NoProxy::All => true,
+            NoProxy::Ip(no_proxy) => ip == Some(*no_proxy),
+            NoProxy::Cidr(network, prefix) => ip.is_some_and(|ip| in_cidr(ip, *network, *prefix)),
+            NoProxy::Domain {
+                suffix,
+                exact,
+                port: no_proxy_port,
+            } => {
+                let matches = host.ends_with(suffix.as_str()) || (*exact && host == &suffix[1..]);
+                matches && no_proxy_port.is_none_or(|p| p == port)
+            }
+        })
+    }
+}
+
+fn get_env_any(upper: &str, lower: &str) -> Option<String> {
+    [upper, lower]
+        .into_iter()
+        .find_map(|name| std::env::var(name).ok().filter(|v| !v.is_empty()))
+}
+
+/// Proxies are often given without a scheme, which defaults to `http`
+fn parse_proxy(proxy: &str) -> Option<http::Uri> {
+    let uri = if proxy.contains("://") {
+        proxy.parse()
+    } else {
+        format!("http://{proxy}").parse()
+    };
+    match uri {
+        Ok(uri) => Some(uri),
+        Err(err) => {
+            tracing::warn!(
+                proxy,
+                error = &err as &dyn std::error::Error,
+                "ignoring invalid proxy url from the environment"
+            );
+            None
+        }
+    }
+}
+
+fn parse_no_proxy(value: &str) -> Vec<NoProxy> {
+    value
+        .split(',')
+        .map(|entry| entry.trim().to_ascii_lowercase())
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            if entry == "*" {
+                return NoProxy::All;
+            }
+            if let Some((ip, prefix)) = entry.split_once('/') {
+                if let (Ok(ip), Ok(prefix)) = (ip.parse::<IpAddr>(), prefix.parse::<u8>()) {
+                    return NoProxy::Cidr(ip, prefix);
+                }
+            }
+            if let Ok(ip) = entry.trim_start_matches('[').trim_end_matches(']').parse() {
+                return NoProxy::Ip(ip);
+            }
+            let (host, port) = match entry.rsplit_once(':') {
+                Some((host, port)) if port.parse::<u16>().is_ok() => (
+                    host.trim_start_matches('[').trim_end_matches(']'),
+                    port.parse().ok(),
+                ),
+                _ => (entry.as_str(), None),
+            };
+            if let Ok(ip) = host.parse() {
+                return NoProxy::Ip(ip);
+            }
+            let host = host.strip_prefix('*').unwrap_or(host);
+            match host.strip_prefix('.') {
+                Some(_) => NoProxy::Domain {
+                    suffix: host.to_owned(),
+                    exact: false,
+                    port,
+                },
+                None => NoProxy::Domain {
+                    suffix: format!(".{host}"),
+                    exact: true,
+                    port,
+                },
+            }
+        })
+        .collect()
+}
+
+fn in_cidr(ip: IpAddr, network: IpAddr, prefix: u8) -> bool {
+    match (ip, network) {
+        (IpAddr::V4(ip), IpAddr::V4(network)) if prefix <= 32 => {
+            let mask = u32::MAX.checked_shl(32 - u32::from(prefix)).unwrap_or(0);
+            u32::from(ip) & mask == u32::from(network) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(network)) if prefix <= 128 => {
+            let mask = u128::MAX.checked_shl(128 - u32::from(prefix)).unwrap_or(0);
+            u128::from(ip) & mask == u128::from(network) & mask
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proxy_for(env: &ProxyEnv, url: &str) -> Option<String> {
+        env.proxy_for(&url.parse().unwrap()).map(|uri| uri.to_string())
+    }
+
+    #[test]
+    fn proxy_env_scheme() {
+        let env = ProxyEnv::new(
+            Some("proxy.corp:3128".into()),
+            Some("http://plain.corp".into()),
+            None,
+        );
+        assert_eq!(
+            proxy_for(&env, "https://api.example.com:6443").as_deref(),
+            Some("http://proxy.corp:3128/")
+        );
+        assert_eq!(
+            proxy_for(&env, "http://api.example.com").as_deref(),
+            Some("http://plain.corp/")
+        );
+        assert_eq!(proxy_for(&env, "https://localhost:6443"), None);
+        assert_eq!(proxy_for(&env, "https://127.0.0.1:6443"), None);
+        assert_eq!(proxy_for(&env, "https://[::1]:6443"), None);
+
+        let env = ProxyEnv::new(None, Some("http://plain.corp".into()), None);
+        assert_eq!(proxy_for(&env, "https://api.example.com"), None);
+    }
+
+    #[test]
+    fn proxy_env_no_proxy() {
+        let env = ProxyEnv::new(
+            Some("http://proxy.corp:3128".into()),
+            None,
+            Some(
+                "10.0.0.0/8, .internal, example.com, *.wild.org, other.net:8443, fd00::/8, 192.168.1.1"
+                    .into(),
+            ),
+        );
+        for url in [
+            "https://10.96.0.1",
+            "https://api.internal",
+            "https://example.com",
+            "https://api.example.com",
+            "https://api.wild.org",
+            "https://other.net:8443",
+            "https://[fd00::1]:6443",
+            "https://192.168.1.1:6443",
+            "https://API.EXAMPLE.COM",
+        ] {
+            assert_eq!(proxy_for(&env, url), None, "{url} should not be proxied");
+        }
+        for url in [
+            "https://11.0.0.1",
+            "https://internal",
+            "https://notexample.com",
+            "https://wild.org.evil.com",
+            "https://other.net",
+            "https://192.168.1.2",
+        ] {
+            assert!(proxy_for(&env, url).is_some(), "{url} should be proxied");
+        }
+
+        let env = ProxyEnv::new(Some("http://proxy.corp:3128".into()), None, Some("*".into()));
+        assert_eq!(proxy_for(&env, "https://api.example.com"), None);
+    }
+}
@@ -0,0 +1,78 @@
+//! Apiservers listening on Unix domain sockets
+//!
+//! A socket path cannot be the authority of a URI, so it is hex encoded into the host of a `unix` URI,
+//! the same way as the `hyperlocal` crate does. Any path of the URI is the base path of the apiserver.
+use std::path::{Path, PathBuf};
+
+pub(crate) const SCHEME: &str = "unix";
+
+/// The URI of an apiserver listening on the socket at `path`
+pub(crate) fn url(path: &Path, base_path: &str) -> Result<http::Uri, http::uri::InvalidUri> {
+    let host = path_bytes(path)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<String>();
+    format!("{SCHEME}://{host}{base_path}").parse()
+}
+
+/// The socket of a `unix` URI, if it is one
+pub(crate) fn path(url: &http::Uri) -> Option<PathBuf> {
+    if url.scheme_str() != Some(SCHEME) {
+        return None;
+    }
+    let host = url.host()?.as_bytes();
+    let bytes = host
+        .chunks(2)
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    Some(path_from_bytes(bytes))
+}
+
+/// Parse a kubeconfig `server`, which may be a `unix:///path/to/socket` url
+pub(crate) fn parse_server(server: &str) -> Result<http::Uri, http::uri::InvalidUri> {
+    match server.strip_prefix("unix://") {
+        Some(path) if path.starts_with('/') => url(Path::new(path), "/"),
+        _ => server.parse(),
+    }
+}
+
+#[cfg(unix)]
+fn path_bytes(path: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_os_str().as_bytes().to_vec()
+}
+
+#[cfg(not(unix))]
+fn path_bytes(path: &Path) -> Vec<u8> {
+    path.to_string_lossy().into_owned().into_bytes()
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    use std::os::unix::ffi::OsStringExt;
+    PathBuf::from(std::ffi::OsString::from_vec(bytes))
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unix_socket_url_roundtrip() {
+        let server = parse_server("unix:///run/kube proxy.sock").unwrap();
+        assert_eq!(server.scheme_str(), Some("unix"));
+        assert_eq!(server.path(), "/");
+        assert_eq!(path(&server), Some(PathBuf::from("/run/kube proxy.sock")));
+
+        let proxied = url(Path::new("/tmp/k.sock"), "/proxy").unwrap();
+        assert_eq!(proxied.path(), "/proxy");
+        assert_eq!(path(&proxied), Some(PathBuf::from("/tmp/k.sock")));
+
+        assert_eq!(path(&parse_server("https://10.0.0.1:6443").unwrap()), None);
+    }
+}
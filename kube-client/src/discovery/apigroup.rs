@@ -1,4 +1,4 @@
-use super::parse::{self, GroupVersionData};
+use super::parse::{self, ApiGroupDiscovery, GroupVersionData};
 use crate::{error::DiscoveryError, Client, Error, Result};
 use k8s_openapi::apimachinery::pkg::apis::meta::v1::{APIGroup, APIVersions};
 pub use kube_core::discovery::{ApiCapabilities, ApiResource};
@@ -124,6 +124,38 @@ impl ApiGroup {
         Ok(group)
     }
 
+    /// Convert a group from aggregated discovery, skipping versions that are stale
+    ///
+    /// Returns `None` when no version is left.
+    pub(crate) fn from_aggregated(g: ApiGroupDiscovery) -> Option<Self> {
+        let name = g.metadata.name.unwrap_or_default();
+        let data = g
+            .versions
+            .into_iter()
+            .filter(|v| {
+                let stale = v.freshness.as_deref() == Some("Stale");
+                if stale {
+                    tracing::debug!(
+                        group = name.as_str(),
+                        version = v.version.as_str(),
+                        "skipping stale version"
+                    );
+                }
+                !stale
+            })
+            .map(|v| GroupVersionData::from_aggregated(&name, v))
+            .collect::<Vec<_>>();
+        // Versions are listed in order of preference
+        let preferred = data.first()?.version.clone();
+        let mut group = ApiGroup {
+            name,
+            data,
+            preferred: Some(preferred),
+        };
+        group.sort_versions();
+        Some(group)
+    }
+
     fn sort_versions(&mut self) {
         self.data
             .sort_by_cached_key(|gvd| Reverse(Version::parse(gvd.version.as_str()).priority()))
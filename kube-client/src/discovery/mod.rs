@@ -1,8 +1,12 @@
 //! High-level utilities for runtime API discovery.
 
-use crate::{Client, Result};
+use crate::{Client, Error, Result};
+use http::{header::ACCEPT, Request};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{APIGroupList, APIVersions};
 pub use kube_core::discovery::{verbs, ApiCapabilities, ApiResource, Scope};
 use kube_core::gvk::GroupVersionKind;
+use parse::{ApiGroupDiscoveryList, AGGREGATED_DISCOVERY_ACCEPT, AGGREGATED_DISCOVERY_KIND};
+use serde::de::DeserializeOwned;
 use std::collections::HashMap;
 mod apigroup;
 pub mod oneshot;
@@ -83,8 +87,14 @@ impl Discovery {
 
     /// Runs or re-runs the configured discovery algorithm and updates/populates the cache
     ///
-    /// The cache is empty cleared when this is started. By default, every api group found is checked,
+    /// The cache is empty cleared when this is started. By default, every api group found is checked.
+    ///
+    /// Apiservers that support aggregated discovery (`apidiscovery.k8s.io/v2`, Kubernetes 1.30+)
+    /// describe all groups in two queries, one for `/apis` and one for `/api`.
+    /// Older apiservers are queried with legacy discovery instead,
     /// causing `N+2` queries to the api server (where `N` is number of api groups).
+    /// Versions of aggregated apiservers that are unavailable are left out with aggregated discovery,
+    /// while legacy discovery fails on them.
     ///
     /// ```no_run
     /// use kube::{Client, api::{Api, DynamicObject}, discovery::{Discovery, verbs, Scope}, ResourceExt};
@@ -110,24 +120,70 @@ impl Discovery {
     /// See a bigger example in [examples/dynamic.api](https://github.com/kube-rs/kube/blob/main/examples/dynamic_api.rs)
     pub async fn run(mut self) -> Result<Self> {
         self.groups.clear();
-        let api_groups = self.client.list_api_groups().await?;
         // query regular groups + crds under /apis
-        for g in api_groups.groups {
-            let key = g.name.clone();
-            if self.mode.is_queryable(&key) {
-                let apigroup = ApiGroup::query_apis(&self.client, g).await?;
-                self.groups.insert(key, apigroup);
+        match discover::<APIGroupList>(&self.client, "/apis").await? {
+            Document::Aggregated(list) => self.insert_aggregated(list),
+            Document::Legacy(api_groups) => {
+                for g in api_groups.groups {
+                    let key = g.name.clone();
+                    if self.mode.is_queryable(&key) {
+                        let apigroup = ApiGroup::query_apis(&self.client, g).await?;
+                        self.groups.insert(key, apigroup);
+                    }
+                }
             }
         }
         // query core versions under /api
         let corekey = ApiGroup::CORE_GROUP.to_string();
         if self.mode.is_queryable(&corekey) {
-            let coreapis = self.client.list_core_api_versions().await?;
-            let apigroup = ApiGroup::query_core(&self.client, coreapis).await?;
-            self.groups.insert(corekey, apigroup);
+            match discover::<APIVersions>(&self.client, "/api").await? {
+                Document::Aggregated(list) => self.insert_aggregated(list),
+                Document::Legacy(coreapis) => {
+                    let apigroup = ApiGroup::query_core(&self.client, coreapis).await?;
+                    self.groups.insert(corekey, apigroup);
+                }
+            }
         }
         Ok(self)
     }
+
+    fn insert_aggregated(&mut self, list: ApiGroupDiscoveryList) {
+        for g in list.items {
+            let key = g.metadata.name.clone().unwrap_or_default();
+            if self.mode.is_queryable(&key) {
+                if let Some(apigroup) = ApiGroup::from_aggregated(g) {
+                    self.groups.insert(key, apigroup);
+                }
+            }
+        }
+    }
+}
+
+/// A discovery document for `/api` or `/apis`
+enum Document<T> {
+    /// All groups with their resources, from aggregated discovery
+    Aggregated(ApiGroupDiscoveryList),
+    /// The groups or versions to query for resources, from legacy discovery
+    Legacy(T),
+}
+
+/// Request the aggregated discovery document, which older apiservers answer with the legacy one
+async fn discover<T: DeserializeOwned>(client: &Client, path: &str) -> Result<Document<T>> {
+    let req = Request::builder()
+        .uri(path)
+        .header(ACCEPT, AGGREGATED_DISCOVERY_ACCEPT)
+        .body(vec![])
+        .map_err(Error::HttpError)?;
+    let document: serde_json::Value = client.request(req).await?;
+    if document.get("kind").and_then(serde_json::Value::as_str) == Some(AGGREGATED_DISCOVERY_KIND) {
+        Ok(Document::Aggregated(
+            serde_json::from_value(document).map_err(Error::SerdeError)?,
+        ))
+    } else {
+        Ok(Document::Legacy(
+            serde_json::from_value(document).map_err(Error::SerdeError)?,
+        ))
+    }
 }
 
 /// Interface to the Discovery cache
@@ -168,3 +224,184 @@ impl Discovery {
             .find(|res| res.0.kind == gvk.kind)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        convert::Infallible,
+        sync::{Arc, Mutex},
+    };
+
+    use http::Response;
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::client::Body;
+
+    /// A fake apiserver answering discovery requests, and recording their paths
+    fn apiserver(aggregated: bool) -> (Client, Arc<Mutex<Vec<String>>>) {
+        let paths = Arc::new(Mutex::new(Vec::new()));
+        let seen = paths.clone();
+        let service = tower::service_fn(move |request: http::Request<Body>| {
+            let path = request.uri().path().to_owned();
+            seen.lock().unwrap().push(path.clone());
+            let accepts_aggregated = request
+                .headers()
+                .get(ACCEPT)
+                .and_then(|accept| accept.to_str().ok())
+                .is_some_and(|accept| accept.starts_with("application/json;g=apidiscovery.k8s.io;v=v2;"));
+            let body = if path == "/apis" && aggregated && accepts_aggregated {
+                json!({
+                    "kind": "APIGroupDiscoveryList",
+                    "apiVersion": "apidiscovery.k8s.io/v2",
+                    "items": [{
+                        "metadata": { "name": "apps" },
+                        "versions": [
+                            { "version": "v1", "resources": [deployments()] },
+                            { "version": "v1beta1", "resources": [], "freshness": "Stale" },
+                        ],
+                    }, {
+                        "metadata": { "name": "metrics.k8s.io" },
+                        "versions": [{ "version": "v1beta1", "resources": [], "freshness": "Stale" }],
+                    }],
+                })
+            } else if path == "/api" && aggregated && accepts_aggregated {
+                json!({
+                    "kind": "APIGroupDiscoveryList",
+                    "apiVersion": "apidiscovery.k8s.io/v2",
+                    "items": [{
+                        "metadata": {},
+                        "versions": [{ "version": "v1", "resources": [{
+                            "resource": "namespaces",
+                            "responseKind": { "group": "", "version": "v1", "kind": "Namespace" },
+                            "scope": "Cluster",
+                            "verbs": ["get", "list"],
+                        }] }],
+                    }],
+                })
+            } else {
+                legacy(&path)
+            };
+            async move { Ok::<_, Infallible>(Response::new(Body::from(serde_json::to_vec(&body).unwrap()))) }
+        });
+        (Client::new(service, "default"), paths)
+    }
+
+    fn deployments() -> Value {
+        json!({
+            "resource": "deployments",
+            "responseKind": { "group": "", "version": "", "kind": "Deployment" },
+            "scope": "Namespaced",
+            "verbs": ["create", "get", "list", "watch"],
+            "subresources": [{
+                "subresource": "scale",
+                "responseKind": { "group": "autoscaling", "version": "v1", "kind": "Scale" },
+                "verbs": ["get", "patch", "update"],
+            }],
+        })
+    }
+
+    fn legacy(path: &str) -> Value {
+        match path {
+            "/apis" => json!({
+                "kind": "APIGroupList",
+                "apiVersion": "v1",
+                "groups": [{
+                    "name": "apps",
+                    "versions": [{ "groupVersion": "apps/v1", "version": "v1" }],
+                    "preferredVersion": { "groupVersion": "apps/v1", "version": "v1" },
+                }],
+            }),
+            "/apis/apps/v1" => json!({
+                "kind": "APIResourceList",
+                "groupVersion": "apps/v1",
+                "resources": [{
+                    "name": "deployments",
+                    "singularName": "deployment",
+                    "namespaced": true,
+                    "kind": "Deployment",
+                    "verbs": ["create", "get", "list", "watch"],
+                }, {
+                    "name": "deployments/scale",
+                    "singularName": "",
+                    "namespaced": true,
+                    "group": "autoscaling",
+                    "version": "v1",
+                    "kind": "Scale",
+                    "verbs": ["get", "patch", "update"],
+                }],
+            }),
+            "/api" => json!({ "kind": "APIVersions", "versions": ["v1"], "serverAddressByClientCIDRs": [] }),
+            "/api/v1" => json!({
+                "kind": "APIResourceList",
+                "groupVersion": "v1",
+                "resources": [{
+                    "name": "namespaces",
+                    "singularName": "namespace",
+                    "namespaced": false,
+                    "kind": "Namespace",
+                    "verbs": ["get", "list"],
+                }],
+            }),
+            _ => panic!("unexpected request to {path}"),
+        }
+    }
+
+    /// The discovered resources and capabilities, which do not depend on the kind of discovery
+    fn assert_discovered(discovery: &Discovery) {
+        let apps = discovery.get("apps").unwrap();
+        assert_eq!(apps.versions().collect::<Vec<_>>(), ["v1"]);
+        assert_eq!(apps.preferred_version(), Some("v1"));
+        let (ar, caps) = apps.recommended_kind("Deployment").unwrap();
+        assert_eq!(ar, ApiResource {
+            group: "apps".into(),
+            version: "v1".into(),
+            api_version: "apps/v1".into(),
+            kind: "Deployment".into(),
+            plural: "deployments".into(),
+        });
+        assert_eq!(caps.scope, Scope::Namespaced);
+        assert!(caps.supports_operation(verbs::WATCH));
+        let (scale, scale_caps) = &caps.subresources[0];
+        assert_eq!(
+            (
+                scale.group.as_str(),
+                scale.version.as_str(),
+                scale.kind.as_str(),
+                scale.plural.as_str()
+            ),
+            ("autoscaling", "v1", "Scale", "scale")
+        );
+        assert_eq!(scale_caps.operations, ["get", "patch", "update"]);
+
+        let gvk = GroupVersionKind::gvk("", "v1", "Namespace");
+        let (namespaces, caps) = discovery.resolve_gvk(&gvk).unwrap();
+        assert_eq!(namespaces.plural, "namespaces");
+        assert_eq!(caps.scope, Scope::Cluster);
+        assert!(
+            !discovery.has_group("metrics.k8s.io"),
+            "groups with only stale versions are left out"
+        );
+    }
+
+    #[tokio::test]
+    async fn aggregated_discovery() {
+        let (client, paths) = apiserver(true);
+        let discovery = Discovery::new(client).run().await.unwrap();
+        assert_discovered(&discovery);
+        assert_eq!(*paths.lock().unwrap(), ["/apis", "/api"]);
+    }
+
+    #[tokio::test]
+    async fn legacy_discovery_fallback() {
+        let (client, paths) = apiserver(false);
+        let discovery = Discovery::new(client).run().await.unwrap();
+        assert_discovered(&discovery);
+        assert_eq!(*paths.lock().unwrap(), [
+            "/apis",
+            "/apis/apps/v1",
+            "/api",
+            "/api/v1"
+        ]);
+    }
+}
@@ -1,10 +1,11 @@
 //! Abstractions on top of k8s_openapi::apimachinery::pkg::apis::meta::v1
 use crate::{error::DiscoveryError, Error, Result};
-use k8s_openapi::apimachinery::pkg::apis::meta::v1::{APIResource, APIResourceList};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{APIResource, APIResourceList, ObjectMeta};
 use kube_core::{
     discovery::{ApiCapabilities, ApiResource, Scope},
     gvk::{GroupVersion, ParseGroupVersionError},
 };
+use serde::Deserialize;
 
 /// Creates an `ApiResource` from a `meta::v1::APIResource` instance + its groupversion.
 ///
@@ -86,3 +87,130 @@ impl GroupVersionData {
         Ok(GroupVersionData { version, resources })
     }
 }
+
+impl GroupVersionData {
+    /// Extract all information for a version of a group from aggregated discovery
+    pub(crate) fn from_aggregated(group: &str, version: ApiVersionDiscovery) -> Self {
+        let gv = GroupVersion::gv(group, &version.version);
+        let resources = version
+            .resources
+            .into_iter()
+            .filter_map(|res| {
+                let Some(kind) = res.response_kind else {
+                    tracing::debug!(resource = res.resource, "skipping resource without a kind");
+                    return None;
+                };
+                let scope = if res.scope == "Namespaced" {
+                    Scope::Namespaced
+                } else {
+                    Scope::Cluster
+                };
+                let subresources = res
+                    .subresources
+                    .into_iter()
+                    .filter_map(|sub| {
+                        let kind = sub.response_kind?;
+                        let ar = kind.api_resource(&gv, sub.subresource);
+                        let caps = ApiCapabilities {
+                            scope: scope.clone(),
+                            subresources: vec![],
+                            operations: sub.verbs,
+                        };
+                        Some((ar, caps))
+                    })
+                    .collect();
+                let ar = kind.api_resource(&gv, res.resource);
+                let caps = ApiCapabilities {
+                    scope,
+                    subresources,
+                    operations: res.verbs,
+                };
+                Some((ar, caps))
+            })
+            .collect();
+        GroupVersionData {
+            version: version.version,
+            resources,
+        }
+    }
+}
+
+/// The `Accept` header for aggregated discovery, falling back to the legacy discovery documents
+pub(crate) const AGGREGATED_DISCOVERY_ACCEPT: &str = concat!(
+    "application/json;g=apidiscovery.k8s.io;v=v2;as=APIGroupDiscoveryList,",
+    "application/json;g=apidiscovery.k8s.io;v=v2beta1;as=APIGroupDiscoveryList,",
+    "application/json"
+);
+
+/// The kind of the aggregated discovery document
+pub(crate) const AGGREGATED_DISCOVERY_KIND: &str = "APIGroupDiscoveryList";
+
+/// An `APIGroupDiscoveryList` from `apidiscovery.k8s.io`, describing all groups under `/api` or `/apis`
+#[derive(Deserialize, Debug)]
+pub(crate) struct ApiGroupDiscoveryList {
+    #[serde(default)]
+    pub(crate) items: Vec<ApiGroupDiscovery>,
+}
+
+/// All versions of a group, with the preferred version first
+#[derive(Deserialize, Debug)]
+pub(crate) struct ApiGroupDiscovery {
+    #[serde(default)]
+    pub(crate) metadata: ObjectMeta,
+    #[serde(default)]
+    pub(crate) versions: Vec<ApiVersionDiscovery>,
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct ApiVersionDiscovery {
+    pub(crate) version: String,
+    #[serde(default)]
+    resources: Vec<ApiResourceDiscovery>,
+    /// `Stale` when an aggregated apiserver could not be reached
+    pub(crate) freshness: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ApiResourceDiscovery {
+    resource: String,
+    response_kind: Option<GroupVersionKindDiscovery>,
+    scope: String,
+    #[serde(default)]
+    verbs: Vec<String>,
+    #[serde(default)]
+    subresources: Vec<ApiSubresourceDiscovery>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ApiSubresourceDiscovery {
+    subresource: String,
+    response_kind: Option<GroupVersionKindDiscovery>,
+    #[serde(default)]
+    verbs: Vec<String>,
+}
+
+/// A kind, with a group and version that are empty when the same as those of the resource
+#[derive(Deserialize, Debug)]
+struct GroupVersionKindDiscovery {
+    #[serde(default)]
+    group: String,
+    #[serde(default)]
+    version: String,
+    kind: String,
+}
+
+impl GroupVersionKindDiscovery {
+    /// Like `parse_apiresource`, an `ApiResource` with the apiVersion of the discovered group version
+    fn api_resource(self, gv: &GroupVersion, plural: String) -> ApiResource {
+        let or = |value: String, default: &String| if value.is_empty() { default.clone() } else { value };
+        ApiResource {
+            group: or(self.group, &gv.group),
+            version: or(self.version, &gv.version),
+            api_version: gv.api_version(),
+            kind: self.kind,
+            plural,
+        }
+    }
+}
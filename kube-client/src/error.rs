@@ -103,6 +103,12 @@ pub enum Error {
     #[error("auth error: {0}")]
     Auth(#[source] crate::client::AuthError),
 
+    /// The API server returned warnings that were denied by [`DenyWarnings`](crate::client::DenyWarnings)
+    #[cfg(feature = "client")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "client")))]
+    #[error("request returned warnings: {}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("; "))]
+    Warnings(Vec<crate::client::Warning>),
+
     /// Error resolving resource reference
     #[cfg(feature = "unstable-client")]
     #[cfg_attr(docsrs, doc(cfg(feature = "unstable-client")))]
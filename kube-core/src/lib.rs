@@ -39,6 +39,8 @@ pub use metadata::{ListMeta, ObjectMeta, PartialObjectMeta, PartialObjectMetaExt
 
 pub mod labels;
 
+pub mod metrics;
+
 #[cfg(feature = "kubelet-debug")] pub mod kubelet_debug;
 
 pub mod object;
@@ -66,6 +68,9 @@ pub mod schema;
 
 pub mod subresource;
 
+pub mod table;
+pub use table::Table;
+
 pub mod util;
 
 pub mod watch;
@@ -0,0 +1,525 @@
+//! Types for the `metrics.k8s.io` API of the metrics-server, as shown by `kubectl top`
+//!
+//! [`PodMetrics`] and [`NodeMetrics`] can be fetched with `Api::list` and `Api::get`,
+//! and compared to the requests and limits of their pods, or the allocatable resources of their nodes.
+//!
+//! ```no_run
+//! use k8s_openapi::api::core::v1::Pod;
+//! use kube::{api::{Api, ListParams}, core::metrics::{self, PodMetrics}, Client};
+//! # async fn wrapper() -> Result<(), Box<dyn std::error::Error>> {
+//! # let client: Client = todo!();
+//! let pods: Api<Pod> = Api::default_namespaced(client.clone());
+//! let metrics: Api<PodMetrics> = Api::default_namespaced(client);
+//! let pods = pods.list(&ListParams::default()).await?;
+//! let metrics = metrics.list(&ListParams::default()).await?;
+//! for (pod, metrics) in metrics::join(&pods, &metrics) {
+//!     let utilization = metrics.utilization(pod)?;
+//!     if let Some(ratio) = utilization.cpu_of_requests() {
+//!         println!("{}: {:.0}% of requested cpu", metrics.metadata.name.as_deref().unwrap_or_default(), ratio * 100.0);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+};
+
+use k8s_openapi::{
+    api::core::v1::{Container, Node, Pod},
+    apimachinery::pkg::{api::resource::Quantity, apis::meta::v1::Time},
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    metadata::{ObjectMeta, TypeMeta},
+    ClusterResourceScope, Duration, NamespaceResourceScope, Resource, ResourceExt,
+};
+
+const GROUP: &str = "metrics.k8s.io";
+const VERSION: &str = "v1beta1";
+
+/// The resource usage of the containers of a pod
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PodMetrics {
+    /// The type fields, not always present
+    #[serde(flatten, default)]
+    pub types: Option<TypeMeta>,
+    /// Object metadata, with the name and namespace of the pod
+    #[serde(default)]
+    pub metadata: ObjectMeta,
+    /// The end of the window the usage was measured over
+    pub timestamp: Time,
+    /// The length of the window the usage was measured over
+    pub window: Duration,
+    /// The usage of each container
+    #[serde(default)]
+    pub containers: Vec<ContainerMetrics>,
+}
+
+/// The resource usage of a container
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ContainerMetrics {
+    /// The name of the container
+    pub name: String,
+    /// The usage of each resource, such as `cpu` and `memory`
+    #[serde(default)]
+    pub usage: BTreeMap<String, Quantity>,
+}
+
+/// The resource usage of a node
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct NodeMetrics {
+    /// The type fields, not always present
+    #[serde(flatten, default)]
+    pub types: Option<TypeMeta>,
+    /// Object metadata, with the name of the node
+    #[serde(default)]
+    pub metadata: ObjectMeta,
+    /// The end of the window the usage was measured over
+    pub timestamp: Time,
+    /// The length of the window the usage was measured over
+    pub window: Duration,
+    /// The usage of each resource, such as `cpu` and `memory`
+    #[serde(default)]
+    pub usage: BTreeMap<String, Quantity>,
+}
+
+impl Resource for PodMetrics {
+    type DynamicType = ();
+    type Scope = NamespaceResourceScope;
+
+    fn kind(_: &()) -> Cow<'_, str> {
+        "PodMetrics".into()
+    }
+
+    fn group(_: &()) -> Cow<'_, str> {
+        GROUP.into()
+    }
+
+    fn version(_: &()) -> Cow<'_, str> {
+        VERSION.into()
+    }
+
+    fn plural(_: &()) -> Cow<'_, str> {
+        "pods".into()
+    }
+
+    fn meta(&self) -> &ObjectMeta {
+        &self.metadata
+    }
+
+    fn meta_mut(&mut self) -> &mut ObjectMeta {
+        &mut self.metadata
+    }
+}
+
+impl Resource for NodeMetrics {
+    type DynamicType = ();
+    type Scope = ClusterResourceScope;
+
+    fn kind(_: &()) -> Cow<'_, str> {
+        "NodeMetrics".into()
+    }
+
+    fn group(_: &()) -> Cow<'_, str> {
+        GROUP.into()
+    }
+
+    fn version(_: &()) -> Cow<'_, str> {
+        VERSION.into()
+    }
+
+    fn plural(_: &()) -> Cow<'_, str> {
+        "nodes".into()
+    }
+
+    fn meta(&self) -> &ObjectMeta {
+        &self.metadata
+    }
+
+    fn meta_mut(&mut self) -> &mut ObjectMeta {
+        &mut self.metadata
+    }
+}
+
+impl PodMetrics {
+    /// The usage of all containers of the pod
+    pub fn usage(&self) -> Result<Resources, ParseQuantityError> {
+        let usages = self
+            .containers
+            .iter()
+            .map(ContainerMetrics::usage)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Resources::sum(&usages))
+    }
+
+    /// The usage of the pod compared to the requests and limits of its containers
+    ///
+    /// The pod has a limit only when all of its containers do. Containers with a limit but no request
+    /// are requesting their limit, as the API server defaults them to. The usage of containers that request
+    /// nothing still counts towards the usage of the pod, so the usage can exceed the requests.
+    pub fn utilization(&self, pod: &Pod) -> Result<Utilization, ParseQuantityError> {
+        let containers = pod
+            .spec
+            .as_ref()
+            .map(|spec| spec.containers.as_slice())
+            .unwrap_or_default();
+        let mut requests = Vec::with_capacity(containers.len());
+        let mut limits = Vec::with_capacity(containers.len());
+        for container in containers {
+            let (request, limit) = requests_and_limits(container)?;
+            requests.push(request);
+            limits.push(limit);
+        }
+        Ok(Utilization {
+            usage: self.usage()?,
+            requests: Resources::sum(&requests),
+            limits: Resources::sum_all(&limits),
+        })
+    }
+}
+
+impl ContainerMetrics {
+    /// The usage of the container
+    pub fn usage(&self) -> Result<Resources, ParseQuantityError> {
+        Resources::from_quantities(Some(&self.usage))
+    }
+
+    /// The usage of the container compared to its requests and limits
+    ///
+    /// A resource with a limit but no request is requesting its limit, as the API server defaults it to.
+    pub fn utilization(&self, container: &Container) -> Result<Utilization, ParseQuantityError> {
+        let (requests, limits) = requests_and_limits(container)?;
+        Ok(Utilization {
+            usage: self.usage()?,
+            requests,
+            limits,
+        })
+    }
+}
+
+/// The requests and limits of a container, with requests defaulting to limits
+fn requests_and_limits(container: &Container) -> Result<(Resources, Resources), ParseQuantityError> {
+    let resources = container.resources.as_ref();
+    let requests = Resources::from_quantities(resources.and_then(|r| r.requests.as_ref()))?;
+    let limits = Resources::from_quantities(resources.and_then(|r| r.limits.as_ref()))?;
+    let requests = Resources {
+        cpu: requests.cpu.or(limits.cpu),
+        memory: requests.memory.or(limits.memory),
+    };
+    Ok((requests, limits))
+}
+
+impl NodeMetrics {
+    /// The usage of the node
+    pub fn usage(&self) -> Result<Resources, ParseQuantityError> {
+        Resources::from_quantities(Some(&self.usage))
+    }
+
+    /// The usage of the node compared to its allocatable resources and capacity
+    pub fn utilization(&self, node: &Node) -> Result<NodeUtilization, ParseQuantityError> {
+        let status = node.status.as_ref();
+        Ok(NodeUtilization {
+            usage: self.usage()?,
+            allocatable: Resources::from_quantities(status.and_then(|s| s.allocatable.as_ref()))?,
+            capacity: Resources::from_quantities(status.and_then(|s| s.capacity.as_ref()))?,
+        })
+    }
+}
+
+/// Pair objects with their metrics, by namespace and name
+///
+/// Objects without metrics, such as pods that have not started yet, are skipped.
+pub fn join<'a, K, M>(
+    objects: impl IntoIterator<Item = &'a K>,
+    metrics: impl IntoIterator<Item = &'a M>,
+) -> impl Iterator<Item = (&'a K, &'a M)>
+where
+    K: Resource + 'a,
+    M: Resource + 'a,
+{
+    let metrics = metrics
+        .into_iter()
+        .map(|m| ((m.namespace(), m.name_any()), m))
+        .collect::<HashMap<_, _>>();
+    objects
+        .into_iter()
+        .filter_map(move |o| Some((o, *metrics.get(&(o.namespace(), o.name_any()))?)))
+}
+
+/// Amounts of cpu and memory
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Resources {
+    /// CPU in cores
+    pub cpu: Option<f64>,
+    /// Memory in bytes
+    pub memory: Option<f64>,
+}
+
+impl Resources {
+    /// Parse the `cpu` and `memory` of a list of resources
+    pub fn from_quantities(list: Option<&BTreeMap<String, Quantity>>) -> Result<Self, ParseQuantityError> {
+        let get = |name| list.and_then(|l| l.get(name)).map(parse_quantity).transpose();
+        Ok(Self {
+            cpu: get("cpu")?,
+            memory: get("memory")?,
+        })
+    }
+
+    /// The sum of the resources that are set
+    fn sum(all: &[Self]) -> Self {
+        let add = |a: Option<f64>, b: Option<f64>| match (a, b) {
+            (Some(a), Some(b)) => Some(a + b),
+            (a, b) => a.or(b),
+        };
+        all.iter().fold(Self::default(), |acc, r| Self {
+            cpu: add(acc.cpu, r.cpu),
+            memory: add(acc.memory, r.memory),
+        })
+    }
+
+    /// The sum of the resources that are set everywhere
+    fn sum_all(all: &[Self]) -> Self {
+        let total = |get: fn(&Self) -> Option<f64>| {
+            if all.is_empty() {
+                return None;
+            }
+            all.iter().map(get).sum()
+        };
+        Self {
+            cpu: total(|r| r.cpu),
+            memory: total(|r| r.memory),
+        }
+    }
+}
+
+/// The usage of a pod or container, compared to its requests and limits
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Utilization {
+    /// The measured usage
+    pub usage: Resources,
+    /// The requested resources
+    pub requests: Resources,
+    /// The resource limits
+    pub limits: Resources,
+}
+
+impl Utilization {
+    /// CPU usage as a fraction of the requested cpu
+    pub fn cpu_of_requests(&self) -> Option<f64> {
+        ratio(self.usage.cpu, self.requests.cpu)
+    }
+
+    /// Memory usage as a fraction of the requested memory
+    pub fn memory_of_requests(&self) -> Option<f64> {
+        ratio(self.usage.memory, self.requests.memory)
+    }
+
+    /// CPU usage as a fraction of the cpu limit
+    pub fn cpu_of_limits(&self) -> Option<f64> {
+        ratio(self.usage.cpu, self.limits.cpu)
+    }
+
+    /// Memory usage as a fraction of the memory limit
+    pub fn memory_of_limits(&self) -> Option<f64> {
+        ratio(self.usage.memory, self.limits.memory)
+    }
+}
+
+/// The usage of a node, compared to its allocatable resources and capacity
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct NodeUtilization {
+    /// The measured usage
+    pub usage: Resources,
+    /// The resources available to pods
+    pub allocatable: Resources,
+    /// The total resources of the node
+    pub capacity: Resources,
+}
+
+impl NodeUtilization {
+    /// CPU usage as a fraction of the allocatable cpu, as shown by `kubectl top node`
+    pub fn cpu_of_allocatable(&self) -> Option<f64> {
+        ratio(self.usage.cpu, self.allocatable.cpu)
+    }
+
+    /// Memory usage as a fraction of the allocatable memory, as shown by `kubectl top node`
+    pub fn memory_of_allocatable(&self) -> Option<f64> {
+        ratio(self.usage.memory, self.allocatable.memory)
+    }
+}
+
+fn ratio(usage: Option<f64>, of: Option<f64>) -> Option<f64> {
+    match (usage, of) {
+        (Some(usage), Some(of)) if of > 0.0 => Some(usage / of),
+        _ => None,
+    }
+}
+
+/// Failed to parse a [`Quantity`]
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+#[error("invalid quantity {0:?}")]
+pub struct ParseQuantityError(pub String);
+
+/// Parse a [`Quantity`] into a number in base units, such as cores or bytes
+///
+/// Supports decimal (`m`, `k`, `M`, ...) and binary (`Ki`, `Mi`, ...) suffixes and exponents (`1e3`).
+pub fn parse_quantity(quantity: &Quantity) -> Result<f64, ParseQuantityError> {
+    let err = || ParseQuantityError(quantity.0.clone());
+    let value = quantity.0.trim();
+    let split = value
+        .find(|c: char| !(c.is_ascii_digit() || matches!(c, '.' | '+' | '-')))
+        .unwrap_or(value.len());
+    let (number, suffix) = value.split_at(split);
+    if !number.contains(|c: char| c.is_ascii_digit()) {
+        return Err(err());
+    }
+    let number = number.parse::<f64>().map_err(|_| err())?;
+    let multiplier = match suffix {
+        "n" => 1e-9,
+        "u" => 1e-6,
+        "m" => 1e-3,
+        "" => 1.0,
+        "k" => 1e3,
+        "M" => 1e6,
+        "G" => 1e9,
+        "T" => 1e12,
+        "P" => 1e15,
+        "E" => 1e18,
+        "Ki" => 1024_f64,
+        "Mi" => 1024_f64.powi(2),
+        "Gi" => 1024_f64.powi(3),
+        "Ti" => 1024_f64.powi(4),
+        "Pi" => 1024_f64.powi(5),
+        "Ei" => 1024_f64.powi(6),
+        exponent => {
+            let exponent = exponent
+                .strip_prefix(['e', 'E'])
+                .and_then(|e| e.parse::<i32>().ok())
+                .ok_or_else(err)?;
+            10_f64.powi(exponent)
+        }
+    };
+    Ok(number * multiplier)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parses_quantities() {
+        let parse = |q: &str| parse_quantity(&Quantity(q.into()));
+        assert_eq!(parse("250m"), Ok(0.25));
+        assert_eq!(parse("2"), Ok(2.0));
+        assert_eq!(parse("1.5"), Ok(1.5));
+        assert_eq!(parse("12345678n"), Ok(0.012345678));
+        assert_eq!(parse("128Mi"), Ok(128.0 * 1024.0 * 1024.0));
+        assert_eq!(parse("1G"), Ok(1e9));
+        assert_eq!(parse("1e3"), Ok(1000.0));
+        assert_eq!(parse("5E-1"), Ok(0.5));
+        assert!(parse("").is_err());
+        assert!(parse("Mi").is_err());
+        assert!(parse("1X").is_err());
+    }
+
+    #[test]
+    fn pod_utilization() {
+        let metrics: PodMetrics = serde_json::from_value(json!({
+            "kind": "PodMetrics",
+            "apiVersion": "metrics.k8s.io/v1beta1",
+            "metadata": { "name": "web", "namespace": "default" },
+            "timestamp": "2024-05-01T10:00:00Z",
+            "window": "15.2s",
+            "containers": [
+                { "name": "app", "usage": { "cpu": "100m", "memory": "64Mi" } },
+                { "name": "sidecar", "usage": { "cpu": "50m", "memory": "32Mi" } },
+            ],
+        }))
+        .unwrap();
+        let pod: Pod = serde_json::from_value(json!({
+            "metadata": { "name": "web", "namespace": "default" },
+            "spec": { "containers": [
+                { "name": "app", "resources": {
+                    "requests": { "cpu": "200m", "memory": "128Mi" },
+                    "limits": { "memory": "256Mi" },
+                } },
+                { "name": "sidecar", "resources": { "requests": { "cpu": "100m" } } },
+            ] },
+        }))
+        .unwrap();
+        assert_eq!(
+            PodMetrics::url_path(&(), Some("default")),
+            "/apis/metrics.k8s.io/v1beta1/namespaces/default/pods"
+        );
+
+        let joined = join([&pod], [&metrics]).collect::<Vec<_>>();
+        assert_eq!(joined.len(), 1);
+        let utilization = metrics.utilization(&pod).unwrap();
+        assert_eq!(utilization.cpu_of_requests(), Some(0.5));
+        assert_eq!(utilization.memory_of_requests(), Some(0.75));
+        assert_eq!(utilization.cpu_of_limits(), None, "no container has a cpu limit");
+        assert_eq!(
+            utilization.memory_of_limits(),
+            None,
+            "the sidecar has no memory limit"
+        );
+
+        // A limit without a request is requested
+        let limited: Pod = serde_json::from_value(json!({
+            "metadata": { "name": "web", "namespace": "default" },
+            "spec": { "containers": [
+                { "name": "app", "resources": {
+                    "requests": { "cpu": "200m", "memory": "128Mi" },
+                    "limits": { "memory": "256Mi" },
+                } },
+                { "name": "sidecar", "resources": { "limits": { "cpu": "100m", "memory": "64Mi" } } },
+            ] },
+        }))
+        .unwrap();
+        let utilization = metrics.utilization(&limited).unwrap();
+        assert_eq!(utilization.cpu_of_requests(), Some(0.5));
+        assert_eq!(utilization.memory_of_requests(), Some(0.5));
+        assert_eq!(utilization.memory_of_limits(), Some(0.3));
+        let sidecar = metrics.containers[1]
+            .utilization(&limited.spec.unwrap().containers[1])
+            .unwrap();
+        assert_eq!(sidecar.cpu_of_requests(), Some(0.5));
+
+        let app = metrics.containers[0]
+            .utilization(&pod.spec.unwrap().containers[0])
+            .unwrap();
+        assert_eq!(app.memory_of_limits(), Some(0.25));
+    }
+
+    #[test]
+    fn node_utilization() {
+        let metrics: NodeMetrics = serde_json::from_value(json!({
+            "metadata": { "name": "node1" },
+            "timestamp": "2024-05-01T10:00:00Z",
+            "window": "20s",
+            "usage": { "cpu": "1500m", "memory": "2Gi" },
+        }))
+        .unwrap();
+        let node: Node = serde_json::from_value(json!({
+            "metadata": { "name": "node1" },
+            "status": {
+                "allocatable": { "cpu": "3", "memory": "8Gi" },
+                "capacity": { "cpu": "4", "memory": "16Gi" },
+            },
+        }))
+        .unwrap();
+        assert_eq!(
+            NodeMetrics::url_path(&(), None),
+            "/apis/metrics.k8s.io/v1beta1/nodes"
+        );
+        let utilization = metrics.utilization(&node).unwrap();
+        assert_eq!(utilization.cpu_of_allocatable(), Some(0.5));
+        assert_eq!(utilization.memory_of_allocatable(), Some(0.25));
+        assert_eq!(utilization.capacity.cpu, Some(4.0));
+    }
+}
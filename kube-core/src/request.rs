@@ -1,7 +1,7 @@
 //! Request builder type for arbitrary api types
 use thiserror::Error;
 
-use crate::params::GetParams;
+use crate::{params::GetParams, table::IncludeObject};
 
 use super::params::{DeleteParams, ListParams, Patch, PatchParams, PostParams, WatchParams};
 
@@ -18,6 +18,11 @@ pub(crate) const JSON_METADATA_MIME: &str = "application/json;as=PartialObjectMe
 pub(crate) const JSON_METADATA_LIST_MIME: &str =
     "application/json;as=PartialObjectMetadataList;g=meta.k8s.io;v=v1";
 
+/// Extended Accept Header
+///
+/// Requests a meta.k8s.io/v1 Table with the server-side printer columns of a resource
+pub(crate) const JSON_TABLE_MIME: &str = "application/json;as=Table;g=meta.k8s.io;v=v1";
+
 /// Possible errors when building a request.
 #[derive(Debug, Error)]
 pub enum Error {
@@ -334,6 +339,47 @@ impl Request {
     }
 }
 
+/// Table request implementations
+///
+/// Requests set an extended Accept header asking the apiserver to render
+/// objects as a [`Table`](crate::table::Table) of printer columns.
+impl Request {
+    /// Get a single instance as a table
+    pub fn get_table(
+        &self,
+        name: &str,
+        gp: &GetParams,
+        include: IncludeObject,
+    ) -> Result<http::Request<Vec<u8>>, Error> {
+        validate_name(name)?;
+        let target = format!("{}/{}?", self.url_path, name);
+        let mut qp = form_urlencoded::Serializer::new(target);
+        if let Some(rv) = &gp.resource_version {
+            qp.append_pair("resourceVersion", rv);
+        }
+        qp.append_pair("includeObject", include.as_str());
+        let urlstr = qp.finish();
+        let req = http::Request::get(urlstr).header(http::header::ACCEPT, JSON_TABLE_MIME);
+        req.body(vec![]).map_err(Error::BuildRequest)
+    }
+
+    /// List a collection of a resource as a table
+    pub fn list_table(
+        &self,
+        lp: &ListParams,
+        include: IncludeObject,
+    ) -> Result<http::Request<Vec<u8>>, Error> {
+        let target = format!("{}?", self.url_path);
+        let mut qp = form_urlencoded::Serializer::new(target);
+        lp.validate()?;
+        lp.populate_qp(&mut qp);
+        qp.append_pair("includeObject", include.as_str());
+        let urlstr = qp.finish();
+        let req = http::Request::get(urlstr).header(http::header::ACCEPT, JSON_TABLE_MIME);
+        req.body(vec![]).map_err(Error::BuildRequest)
+    }
+}
+
 /// Names must not be empty as otherwise API server would interpret a `get` as `list`, or a `delete` as `delete_collection`
 fn validate_name(name: &str) -> Result<(), Error> {
     if name.is_empty() {
@@ -351,6 +397,7 @@ mod test {
         params::{GetParams, PostParams, VersionMatch, WatchParams},
         request::{Error, Request},
         resource::Resource,
+        table::IncludeObject,
     };
     use http::header;
     use k8s::{
@@ -508,6 +555,32 @@ mod test {
         );
     }
 
+    #[test]
+    fn list_table_path() {
+        let url = appsv1::Deployment::url_path(&(), Some("ns"));
+        let lp = ListParams::default().labels("app=blog");
+        let req = Request::new(url).list_table(&lp, IncludeObject::None).unwrap();
+        assert_eq!(
+            req.uri(),
+            "/apis/apps/v1/namespaces/ns/deployments?&labelSelector=app%3Dblog&includeObject=None"
+        );
+        assert_eq!(req.method(), "GET");
+        assert_eq!(req.headers().get(header::ACCEPT).unwrap(), super::JSON_TABLE_MIME);
+    }
+
+    #[test]
+    fn get_table_path() {
+        let url = appsv1::Deployment::url_path(&(), Some("ns"));
+        let req = Request::new(url)
+            .get_table("mydeploy", &GetParams::any(), IncludeObject::Metadata)
+            .unwrap();
+        assert_eq!(
+            req.uri(),
+            "/apis/apps/v1/namespaces/ns/deployments/mydeploy?&resourceVersion=0&includeObject=Metadata"
+        );
+        assert_eq!(req.headers().get(header::ACCEPT).unwrap(), super::JSON_TABLE_MIME);
+    }
+
     #[test]
     fn get_empty_name() {
         let url = appsv1::Deployment::url_path(&(), Some("ns"));
@@ -427,6 +427,89 @@ impl Request {
     }
 }
 
+// ----------------------------------------------------------------------------
+// Proxy subresource
+// ----------------------------------------------------------------------------
+
+/// Params for proxying requests to a pod, service or node
+#[derive(Default, Clone, Debug)]
+pub struct ProxyParams {
+    /// The scheme used to connect to the target, `http` or `https`. Defaults to `http`.
+    pub scheme: Option<String>,
+    /// The port to connect to, by number or name.
+    ///
+    /// Defaults to the first port of a pod or service, and to the kubelet port of a node.
+    pub port: Option<String>,
+}
+
+impl ProxyParams {
+    /// Proxy to a port, by number or name
+    #[must_use]
+    pub fn port(mut self, port: impl ToString) -> Self {
+        self.port = Some(port.to_string());
+        self
+    }
+
+    /// Connect to the target with https
+    #[must_use]
+    pub fn https(mut self) -> Self {
+        self.scheme = Some("https".into());
+        self
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        if let Some(scheme) = &self.scheme {
+            if scheme != "http" && scheme != "https" {
+                return Err(Error::Validation(format!(
+                    "proxy scheme must be http or https, found {scheme}"
+                )));
+            }
+        }
+        if let Some(port) = &self.port {
+            if port.is_empty() || port.contains([':', '/']) {
+                return Err(Error::Validation(format!("invalid proxy port {port:?}")));
+            }
+        }
+        Ok(())
+    }
+
+    /// The proxied object in the `[scheme:]name[:port]` form of the apiserver
+    fn target(&self, name: &str) -> String {
+        match (&self.scheme, &self.port) {
+            (Some(scheme), port) => format!("{scheme}:{name}:{}", port.as_deref().unwrap_or_default()),
+            (None, Some(port)) => format!("{name}:{port}"),
+            (None, None) => name.to_owned(),
+        }
+    }
+}
+
+impl Request {
+    /// Proxy a request to a pod, service or node
+    ///
+    /// The path and query of `request` are resolved relative to the proxy path of the object,
+    /// while its method, headers and body are kept.
+    pub fn proxy<B>(
+        &self,
+        name: &str,
+        pp: &ProxyParams,
+        request: http::Request<B>,
+    ) -> Result<http::Request<B>, Error> {
+        pp.validate()?;
+        if name.is_empty() || name.contains([':', '/']) {
+            return Err(Error::Validation(format!("invalid name to proxy to {name:?}")));
+        }
+
+        let (mut parts, body) = request.into_parts();
+        let path = parts.uri.path_and_query().map_or("/", |pq| pq.as_str());
+        let path = path.strip_prefix('/').unwrap_or(path);
+        let target = format!("{}/{}/proxy/{}", self.url_path, pp.target(name), path);
+        parts.uri = target
+            .parse()
+            .map_err(|e: http::uri::InvalidUri| Error::BuildRequest(e.into()))?;
+        Ok(http::Request::from_parts(parts, body))
+    }
+}
+
 // ----------------------------------------------------------------------------
 // tests
 // ----------------------------------------------------------------------------
@@ -477,4 +560,41 @@ mod test {
             "/api/v1/namespaces/ns/pods/mypod/log?&sinceTime=2023-10-19T13%3A14%3A26Z" // cross-referenced with kubectl
         );
     }
+
+    #[test]
+    fn proxy_paths() {
+        use crate::subresource::ProxyParams;
+
+        let get = |path: &str| http::Request::get(path).body(()).unwrap();
+        let url = corev1::Service::url_path(&(), Some("ns"));
+        let pp = ProxyParams::default().port(8080);
+        let req = Request::new(url)
+            .proxy("web", &pp, get("/api/items?page=2"))
+            .unwrap();
+        assert_eq!(
+            req.uri(),
+            "/api/v1/namespaces/ns/services/web:8080/proxy/api/items?page=2"
+        );
+
+        let url = corev1::Pod::url_path(&(), Some("ns"));
+        let pp = ProxyParams::default().https();
+        let req = Request::new(url).proxy("mypod", &pp, get("/")).unwrap();
+        assert_eq!(req.uri(), "/api/v1/namespaces/ns/pods/https:mypod:/proxy/");
+
+        let url = corev1::Node::url_path(&(), None);
+        let pp = ProxyParams::default().https().port(10250);
+        let req = Request::new(url).proxy("node1", &pp, get("/metrics")).unwrap();
+        assert_eq!(req.uri(), "/api/v1/nodes/https:node1:10250/proxy/metrics");
+
+        let pp = ProxyParams {
+            scheme: Some("ftp".into()),
+            ..ProxyParams::default()
+        };
+        assert!(Request::new("/api/v1/nodes")
+            .proxy("node1", &pp, get("/"))
+            .is_err());
+        assert!(Request::new("/api/v1/nodes")
+            .proxy("a/b", &ProxyParams::default(), get("/"))
+            .is_err());
+    }
 }
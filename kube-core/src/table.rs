@@ -0,0 +1,245 @@
+//! Server-side printing of resources as a [`Table`]
+//!
+//! The API server can render any resource the same way `kubectl get` does, using the built-in
+//! printers for core types and `additionalPrinterColumns` for custom resources.
+//!
+//! See <https://kubernetes.io/docs/reference/using-api/api-concepts/#receiving-resources-as-tables>
+use std::marker::PhantomData;
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    dynamic::DynamicObject,
+    metadata::{ListMeta, ObjectMeta, TypeMeta},
+};
+
+/// How much of each object to embed in the rows of a [`Table`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IncludeObject {
+    /// Only include the cells
+    None,
+    /// Include the object metadata as a `PartialObjectMetadata` (server default)
+    #[default]
+    Metadata,
+    /// Include the full object
+    Object,
+}
+
+impl IncludeObject {
+    /// Returns the string format of the option
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::None => "None",
+            Self::Metadata => "Metadata",
+            Self::Object => "Object",
+        }
+    }
+}
+
+/// A tabular representation of a set of resources
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase", bound = "")]
+pub struct Table<K = DynamicObject> {
+    /// The type fields, always `meta.k8s.io/v1` + `Table`
+    #[serde(flatten, default)]
+    pub types: Option<TypeMeta>,
+
+    /// Standard list metadata
+    #[serde(default)]
+    pub metadata: ListMeta,
+
+    /// Describes each column in the returned items array
+    ///
+    /// The number of cells per row will always match the number of column definitions.
+    #[serde(default)]
+    pub column_definitions: Vec<TableColumnDefinition>,
+
+    /// One row per object
+    #[serde(default)]
+    pub rows: Vec<TableRow<K>>,
+}
+
+impl<K> Table<K> {
+    /// Index of the column with the given name
+    pub fn column_index(&self, name: &str) -> Option<usize> {
+        self.column_definitions.iter().position(|c| c.name == name)
+    }
+
+    /// Columns to show at the given `priority`
+    ///
+    /// Columns with priority 0 are shown by `kubectl get`, higher priorities only in `-o wide`.
+    pub fn columns(&self, priority: i32) -> impl Iterator<Item = (usize, &TableColumnDefinition)> {
+        self.column_definitions
+            .iter()
+            .enumerate()
+            .filter(move |(_, c)| c.priority <= priority)
+    }
+}
+
+/// A column in a [`Table`]
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct TableColumnDefinition {
+    /// Human readable name for the column
+    pub name: String,
+
+    /// OpenAPI type definition for this column, e.g. `string`, `integer`, `number`, `boolean`
+    #[serde(rename = "type")]
+    pub type_: String,
+
+    /// Optional OpenAPI type modifier for this column, e.g. `date` or `name`
+    #[serde(default)]
+    pub format: String,
+
+    /// Human readable description of this column
+    #[serde(default)]
+    pub description: String,
+
+    /// Relative importance of this column
+    ///
+    /// Columns with priority greater than zero may be omitted in limited space scenarios.
+    #[serde(default)]
+    pub priority: i32,
+}
+
+/// A row in a [`Table`]
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase", bound = "")]
+pub struct TableRow<K = DynamicObject> {
+    /// The values for each column, in the order of [`Table::column_definitions`]
+    #[serde(default)]
+    pub cells: Vec<Value>,
+
+    /// Extra state information about the row
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conditions: Vec<TableRowCondition>,
+
+    /// The object this row represents, depending on the requested [`IncludeObject`]
+    ///
+    /// This is a `PartialObjectMetadata` for [`IncludeObject::Metadata`] and the full object
+    /// for [`IncludeObject::Object`], so it is kept as raw JSON.
+    /// Use [`TableRow::metadata`] or [`TableRow::parse_object`] to read it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub object: Option<Value>,
+
+    /// Type information for static dispatch
+    #[serde(skip, default)]
+    pub _phantom: PhantomData<K>,
+}
+
+impl<K> TableRow<K> {
+    /// The metadata of the object, for both [`IncludeObject::Metadata`] and [`IncludeObject::Object`]
+    ///
+    /// Returns `None` when no object was included.
+    pub fn metadata(&self) -> Result<Option<ObjectMeta>, serde_json::Error> {
+        self.object
+            .as_ref()
+            .and_then(|object| object.get("metadata"))
+            .map(ObjectMeta::deserialize)
+            .transpose()
+    }
+}
+
+impl<K: DeserializeOwned> TableRow<K> {
+    /// The full object, when the table was requested with [`IncludeObject::Object`]
+    ///
+    /// Returns `None` when no object was included.
+    /// With [`IncludeObject::Metadata`] the row only holds a `PartialObjectMetadata`,
+    /// which does not parse as `K`; use [`TableRow::metadata`] instead.
+    pub fn parse_object(&self) -> Result<Option<K>, serde_json::Error> {
+        self.object.as_ref().map(K::deserialize).transpose()
+    }
+}
+
+/// A condition on a [`TableRow`]
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct TableRowCondition {
+    /// Type of row condition; the only defined value is `Completed`
+    #[serde(rename = "type")]
+    pub type_: String,
+
+    /// Status of the condition, one of `True`, `False`, `Unknown`
+    pub status: String,
+
+    /// Machine readable reason for the condition's last transition
+    #[serde(default)]
+    pub reason: Option<String>,
+
+    /// Human readable message indicating details about the last transition
+    #[serde(default)]
+    pub message: Option<String>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::Table;
+    use crate::ResourceExt;
+    use k8s_openapi::api::core::v1::Pod;
+
+    #[test]
+    fn deserialize_pod_table() {
+        let table: Table<Pod> = serde_json::from_value(serde_json::json!({
+            "kind": "Table",
+            "apiVersion": "meta.k8s.io/v1",
+            "metadata": { "resourceVersion": "1234" },
+            "columnDefinitions": [
+                { "name": "Name", "type": "string", "format": "name", "description": "Name must be unique", "priority": 0 },
+                { "name": "Ready", "type": "string", "format": "", "description": "The aggregate readiness state", "priority": 0 },
+                { "name": "IP", "type": "string", "format": "", "description": "IP address", "priority": 1 }
+            ],
+            "rows": [{
+                "cells": ["blog", "1/1", "10.0.0.1"],
+                "object": {
+                    "kind": "PartialObjectMetadata",
+                    "apiVersion": "meta.k8s.io/v1",
+                    "metadata": { "name": "blog", "namespace": "apps" }
+                }
+            }]
+        }))
+        .unwrap();
+        assert_eq!(table.metadata.resource_version.as_deref(), Some("1234"));
+        assert_eq!(table.column_index("Ready"), Some(1));
+        assert_eq!(table.columns(0).count(), 2);
+        let row = &table.rows[0];
+        assert_eq!(row.cells[1], "1/1");
+        let metadata = row.metadata().unwrap().unwrap();
+        assert_eq!(metadata.name.as_deref(), Some("blog"));
+        assert!(row.parse_object().is_err());
+    }
+
+    #[test]
+    fn deserialize_pod_table_with_objects() {
+        let table: Table<Pod> = serde_json::from_value(serde_json::json!({
+            "kind": "Table",
+            "apiVersion": "meta.k8s.io/v1",
+            "metadata": {},
+            "columnDefinitions": [{ "name": "Name", "type": "string", "priority": 0 }],
+            "rows": [{
+                "cells": ["blog"],
+                "object": {
+                    "kind": "Pod",
+                    "apiVersion": "v1",
+                    "metadata": { "name": "blog", "namespace": "apps" },
+                    "spec": { "containers": [{ "name": "blog", "image": "clux/blog:0.1.0" }] }
+                }
+            }, {
+                "cells": ["empty"]
+            }]
+        }))
+        .unwrap();
+        let pod = table.rows[0].parse_object().unwrap().unwrap();
+        assert_eq!(pod.name_any(), "blog");
+        assert_eq!(
+            pod.spec.unwrap().containers[0].image.as_deref(),
+            Some("clux/blog:0.1.0")
+        );
+        assert_eq!(
+            table.rows[0].metadata().unwrap().unwrap().namespace.as_deref(),
+            Some("apps")
+        );
+        assert!(table.rows[1].parse_object().unwrap().is_none());
+        assert!(table.rows[1].metadata().unwrap().is_none());
+    }
+}
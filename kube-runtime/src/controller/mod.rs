@@ -19,7 +19,8 @@ use futures::{
     future::{self, BoxFuture},
     stream, FutureExt, Stream, StreamExt, TryFuture, TryFutureExt, TryStream, TryStreamExt,
 };
-use kube_client::api::{Api, DynamicObject, Resource};
+use k8s_openapi::api::rbac::v1::{ClusterRole, PolicyRule, Role};
+use kube_client::api::{Api, DynamicObject, ObjectMeta, Resource};
 use pin_project::pin_project;
 use serde::de::DeserializeOwned;
 use std::{
@@ -36,6 +37,7 @@ use tokio::{runtime::Handle, time::Instant};
 use tracing::{info_span, Instrument};
 
 mod future_hash_map;
+mod rbac;
 mod runner;
 
 pub type RunnerError = runner::Error<reflector::store::WriterDropped>;
@@ -513,6 +515,15 @@ where
     }
 }
 
+/// The verbs a [`Controller`] uses on the resources it watches
+const WATCH_VERBS: &[&str] = &["list", "watch"];
+
+fn watch_rules<K: Resource>(dyntype: &K::DynamicType) -> rbac::Rules {
+    let mut rules = rbac::Rules::default();
+    rules.add_resource::<K>(dyntype, None, WATCH_VERBS);
+    rules
+}
+
 /// Accumulates all options that can be used on a [`Controller`] invocation.
 #[derive(Clone, Debug, Default)]
 pub struct Config {
@@ -642,6 +653,8 @@ where
     dyntype: K::DynamicType,
     reader: Store<K>,
     config: Config,
+    /// The RBAC rules needed to run the controller
+    rules: rbac::Rules,
 }
 
 impl<K> Controller<K>
@@ -699,6 +712,7 @@ where
                 // Fallback future, ensuring that we never terminate if no additional futures are added to the selector
                 future::pending().boxed(),
             ],
+            rules: watch_rules::<K>(&dyntype),
             dyntype,
             reader,
             config: Default::default(),
@@ -785,6 +799,7 @@ where
                 // Fallback future, ensuring that we never terminate if no additional futures are added to the selector
                 future::pending().boxed(),
             ],
+            rules: watch_rules::<K>(&dyntype),
             dyntype,
             reader,
             config: Default::default(),
@@ -896,6 +911,7 @@ where
                 // Fallback future, ensuring that we never terminate if no additional futures are added to the selector
                 future::pending().boxed(),
             ],
+            rules: watch_rules::<K>(&dyntype),
             dyntype,
             reader,
             config: Default::default(),
@@ -926,6 +942,89 @@ where
         self.reader.clone()
     }
 
+    /// Declare verbs the reconciler uses on `R`, for the [`Controller::policy_rules`]
+    ///
+    /// The controller only knows that it lists and watches the resources it is given.
+    /// Verbs used by the reconciler, such as `create` and `patch` on owned objects,
+    /// or `patch` on the main resource to manage finalizers, have to be declared.
+    #[must_use]
+    pub fn writes<R: Resource<DynamicType = ()>>(self, verbs: &[&str]) -> Self {
+        self.writes_with::<R>(&(), verbs)
+    }
+
+    /// Declare verbs the reconciler uses on `R`, for the [`Controller::policy_rules`]
+    ///
+    /// Same as [`Controller::writes`], but accepts a `DynamicType` so it can be used with dynamic resources.
+    #[must_use]
+    pub fn writes_with<R: Resource>(mut self, dyntype: &R::DynamicType, verbs: &[&str]) -> Self {
+        self.rules.add_resource::<R>(dyntype, None, verbs);
+        self
+    }
+
+    /// Declare verbs the reconciler uses on a subresource of `R`, such as `status`, for the [`Controller::policy_rules`]
+    #[must_use]
+    pub fn writes_subresource<R: Resource<DynamicType = ()>>(
+        mut self,
+        subresource: &str,
+        verbs: &[&str],
+    ) -> Self {
+        self.rules.add_resource::<R>(&(), Some(subresource), verbs);
+        self
+    }
+
+    /// Declare that the reconciler publishes events with a [`Recorder`](crate::events::Recorder), for the [`Controller::policy_rules`]
+    #[must_use]
+    pub fn publishes_events(mut self) -> Self {
+        self.rules.add("events.k8s.io", "events", &["create", "patch"]);
+        self
+    }
+
+    /// The RBAC rules needed to run the controller
+    ///
+    /// These cover the watches of the main resource and of the resources given to [`Controller::owns`]
+    /// and [`Controller::watches`] and their variants, along with what was declared with [`Controller::writes`],
+    /// [`Controller::writes_subresource`] and [`Controller::publishes_events`].
+    pub fn policy_rules(&self) -> Vec<PolicyRule> {
+        self.rules.policy_rules()
+    }
+
+    /// A `ClusterRole` with the [`Controller::policy_rules`], to generate the RBAC of an operator
+    ///
+    /// ```no_run
+    /// # use k8s_openapi::api::{apps::v1::Deployment, core::v1::ConfigMap};
+    /// # use kube::{Api, Client, runtime::{watcher, Controller}};
+    /// # async fn doc(client: Client) -> Result<(), Box<dyn std::error::Error>> {
+    /// let controller = Controller::new(Api::<Deployment>::all(client.clone()), watcher::Config::default())
+    ///     .owns(Api::<ConfigMap>::all(client), watcher::Config::default())
+    ///     .writes::<ConfigMap>(&["create", "patch"])
+    ///     .publishes_events();
+    /// println!("{}", serde_yaml::to_string(&controller.cluster_role("my-operator"))?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn cluster_role(&self, name: &str) -> ClusterRole {
+        ClusterRole {
+            metadata: ObjectMeta {
+                name: Some(name.to_owned()),
+                ..ObjectMeta::default()
+            },
+            rules: Some(self.policy_rules()),
+            ..ClusterRole::default()
+        }
+    }
+
+    /// A `Role` with the [`Controller::policy_rules`], for controllers that only watch a single namespace
+    pub fn role(&self, name: &str, namespace: &str) -> Role {
+        Role {
+            metadata: ObjectMeta {
+                name: Some(name.to_owned()),
+                namespace: Some(namespace.to_owned()),
+                ..ObjectMeta::default()
+            },
+            rules: Some(self.policy_rules()),
+        }
+    }
+
     /// Specify `Child` objects which `K` owns and should be watched
     ///
     /// Takes an [`Api`] object that determines how the `Controller` listens for changes to the `Child`.
@@ -959,6 +1058,7 @@ where
         Child::DynamicType: Debug + Eq + Hash + Clone,
     {
         // TODO: call owns_stream_with when it's stable
+        self.rules.add_resource::<Child>(&dyntype, None, WATCH_VERBS);
         let child_watcher = trigger_owners(
             metadata_watcher(api, wc).touched_objects(),
             self.dyntype.clone(),
@@ -1031,6 +1131,7 @@ where
     where
         Child::DynamicType: Debug + Eq + Hash + Clone,
     {
+        self.rules.add_resource::<Child>(&dyntype, None, WATCH_VERBS);
         let child_watcher = trigger_owners(trigger, self.dyntype.clone(), dyntype);
         self.trigger_selector.push(child_watcher.boxed());
         self
@@ -1136,6 +1237,7 @@ where
     where
         Child::DynamicType: Debug + Eq + Hash + Clone,
     {
+        self.rules.add_resource::<Child>(&dyntype, None, WATCH_VERBS);
         let child_watcher = trigger_owners_shared(trigger.map(Ok), self.dyntype.clone(), dyntype);
         self.trigger_selector.push(child_watcher.boxed());
         self
@@ -1238,6 +1340,7 @@ where
         I::IntoIter: Send,
         Other::DynamicType: Debug + Clone + Eq + Hash,
     {
+        self.rules.add_resource::<Other>(&dyntype, None, WATCH_VERBS);
         let other_watcher = trigger_others(watcher(api, wc).touched_objects(), mapper, dyntype);
         self.trigger_selector.push(other_watcher.boxed());
         self
@@ -1320,6 +1423,7 @@ where
         I: 'static + IntoIterator<Item = ObjectRef<K>>,
         I::IntoIter: Send,
     {
+        self.rules.add_resource::<Other>(&dyntype, None, WATCH_VERBS);
         let other_watcher = trigger_others(trigger, mapper, dyntype);
         self.trigger_selector.push(other_watcher.boxed());
         self
@@ -1418,6 +1522,7 @@ where
         I: 'static + IntoIterator<Item = ObjectRef<K>>,
         I::IntoIter: Send,
     {
+        self.rules.add_resource::<Other>(&dyntype, None, WATCH_VERBS);
         let other_watcher = trigger_others_shared(trigger.map(Ok), mapper, dyntype);
         self.trigger_selector.push(other_watcher.boxed());
         self
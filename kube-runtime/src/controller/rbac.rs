@@ -0,0 +1,90 @@
+//! The RBAC rules a [`Controller`](super::Controller) requires
+use std::collections::{BTreeMap, BTreeSet};
+
+use k8s_openapi::api::rbac::v1::PolicyRule;
+use kube_client::Resource;
+
+/// The verbs used on each resource, by API group
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Rules {
+    verbs: BTreeMap<(String, String), BTreeSet<String>>,
+}
+
+impl Rules {
+    pub(crate) fn add(&mut self, group: &str, resource: &str, verbs: &[&str]) {
+        self.verbs
+            .entry((group.to_owned(), resource.to_owned()))
+            .or_default()
+            .extend(verbs.iter().map(|verb| (*verb).to_owned()));
+    }
+
+    pub(crate) fn add_resource<R: Resource>(
+        &mut self,
+        dyntype: &R::DynamicType,
+        subresource: Option<&str>,
+        verbs: &[&str],
+    ) {
+        let plural = R::plural(dyntype);
+        let resource = match subresource {
+            Some(subresource) => format!("{plural}/{subresource}"),
+            None => plural.into_owned(),
+        };
+        self.add(&R::group(dyntype), &resource, verbs);
+    }
+
+    /// One rule for each API group and set of verbs, listing the resources they are used on
+    pub(crate) fn policy_rules(&self) -> Vec<PolicyRule> {
+        let mut resources = BTreeMap::<_, Vec<String>>::new();
+        for ((group, resource), verbs) in &self.verbs {
+            resources
+                .entry((group, verbs))
+                .or_default()
+                .push(resource.clone());
+        }
+        resources
+            .into_iter()
+            .map(|((group, verbs), resources)| PolicyRule {
+                api_groups: Some(vec![group.clone()]),
+                resources: Some(resources),
+                verbs: verbs.iter().cloned().collect(),
+                ..PolicyRule::default()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use k8s_openapi::api::{
+        apps::v1::Deployment,
+        core::v1::{ConfigMap, Secret},
+    };
+    use kube_client::{Api, Client, Config};
+
+    use crate::{reflector::ObjectRef, watcher, Controller};
+
+    #[tokio::test]
+    async fn rules_of_controller() {
+        // The controller is not run, so the client is never used
+        let client = Client::try_from(Config::new("http://127.0.0.1:1".parse().unwrap())).unwrap();
+        let controller = Controller::new(Api::<Deployment>::all(client.clone()), watcher::Config::default())
+            .owns(Api::<ConfigMap>::all(client.clone()), watcher::Config::default())
+            .watches(Api::<Secret>::all(client), watcher::Config::default(), |_| {
+                None::<ObjectRef<Deployment>>
+            })
+            .writes::<ConfigMap>(&["create", "patch"])
+            .writes_subresource::<Deployment>("status", &["patch"])
+            .publishes_events();
+
+        let role = controller.cluster_role("operator");
+        assert_eq!(role.metadata.name.as_deref(), Some("operator"));
+        let yaml = serde_yaml::to_string(&role.rules).unwrap();
+        assert_eq!(yaml, concat!(
+            "- apiGroups:\n  - ''\n  resources:\n  - configmaps\n  verbs:\n  - create\n  - list\n  - patch\n  - watch\n",
+            "- apiGroups:\n  - ''\n  resources:\n  - secrets\n  verbs:\n  - list\n  - watch\n",
+            "- apiGroups:\n  - apps\n  resources:\n  - deployments\n  verbs:\n  - list\n  - watch\n",
+            "- apiGroups:\n  - apps\n  resources:\n  - deployments/status\n  verbs:\n  - patch\n",
+            "- apiGroups:\n  - events.k8s.io\n  resources:\n  - events\n  verbs:\n  - create\n  - patch\n",
+        ));
+    }
+}
@@ -24,6 +24,7 @@ pub mod controller;
 pub mod events;
 
 pub mod finalizer;
+pub mod logs;
 pub mod reflector;
 pub mod scheduler;
 pub mod utils;
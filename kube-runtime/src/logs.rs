@@ -0,0 +1,622 @@
+//! Follows the logs of all containers of a set of pods
+use std::{
+    collections::{HashMap, HashSet},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::{
+    stream::{self, AbortHandle, BoxStream, SelectAll},
+    AsyncBufReadExt, Stream, StreamExt,
+};
+use k8s_openapi::{
+    api::core::v1::{ContainerStatus, Pod},
+    chrono::{DateTime, Utc},
+};
+use kube_client::{api::LogParams, Api, ResourceExt};
+use thiserror::Error;
+
+use crate::{
+    watcher::{self, watcher},
+    WatchStreamExt,
+};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("failed to watch pods: {0}")]
+    Watcher(#[source] watcher::Error),
+    #[error("failed to stream logs of {pod}/{container}: {source}")]
+    LogStream {
+        pod: String,
+        container: String,
+        #[source]
+        source: kube_client::Error,
+    },
+    #[error("failed to read logs of {pod}/{container}: {source}")]
+    ReadLogs {
+        pod: String,
+        container: String,
+        #[source]
+        source: std::io::Error,
+    },
+}
+
+/// A line of the logs of a container
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LogLine {
+    /// The name of the pod
+    pub pod: String,
+    /// The name of the container
+    pub container: String,
+    /// The line, without its line ending
+    pub line: String,
+}
+
+/// Follow the logs of all containers of the pods matching a [`watcher::Config`]
+///
+/// Containers are attached to once they have started, including init containers and restarted containers,
+/// and detached from when their pod is deleted. The [`LogParams`] apply to every container;
+/// if it names a container then only containers with that name are followed.
+///
+/// When followed logs of a running container end, for example because the API server timed out the request,
+/// the container is attached to again from the timestamp of the last returned line, skipping the lines
+/// that were already returned. This happens right away if the logs returned lines,
+/// and otherwise on the next change of the pod.
+///
+/// Failing to stream the logs of a container is reported as an error for that container,
+/// after which the stream continues with the other containers. Failing to watch the pods is reported
+/// as an error too, and the watch is retried with [`WatchStreamExt::default_backoff`].
+///
+/// ```no_run
+/// use futures::TryStreamExt;
+/// use k8s_openapi::api::core::v1::Pod;
+/// use kube::{api::{Api, LogParams}, runtime::{logs::pod_logs, watcher}, Client};
+/// # async fn wrapper() -> Result<(), Box<dyn std::error::Error>> {
+/// # let client: Client = todo!();
+///
+/// let pods: Api<Pod> = Api::default_namespaced(client);
+/// let wc = watcher::Config::default().labels("app=web");
+/// let lp = LogParams { follow: true, tail_lines: Some(10), ..LogParams::default() };
+/// let mut lines = std::pin::pin!(pod_logs(pods, wc, lp));
+/// while let Some(line) = lines.try_next().await? {
+///     println!("{}/{}: {}", line.pod, line.container, line.line);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn pod_logs(
+    api: Api<Pod>,
+    wc: watcher::Config,
+    lp: LogParams,
+) -> impl Stream<Item = Result<LogLine, Error>> + Send {
+    let pods = watcher(api.clone(), wc).default_backoff().boxed();
+    aggregate(
+        pods,
+        lp.container.clone(),
+        lp.follow,
+        move |pod, container, resume| tail(api.clone(), pod, container, resume, &lp),
+    )
+}
+
+/// The lines of the logs of a container, with the timestamps they were logged at
+type Tail = BoxStream<'static, Result<(LogLine, Option<DateTime<Utc>>), Error>>;
+
+/// Where to resume the logs of a container that was attached to before
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Resume {
+    /// The timestamp of the last returned line
+    since: DateTime<Utc>,
+    /// The number of returned lines with that timestamp
+    at_since: usize,
+}
+
+impl Resume {
+    /// The position after a line with `timestamp`
+    fn after(resume: Option<Self>, timestamp: DateTime<Utc>) -> Self {
+        match resume {
+            Some(resume) if resume.since == timestamp => Self {
+                at_since: resume.at_since + 1,
+                ..resume
+            },
+            _ => Self {
+                since: timestamp,
+                at_since: 1,
+            },
+        }
+    }
+}
+
+/// Strips the timestamps the logs are requested with, and skips the lines returned before resuming
+struct Timestamps {
+    keep: bool,
+    skip: Option<Resume>,
+}
+
+impl Timestamps {
+    fn accept(&mut self, line: String) -> Option<(String, Option<DateTime<Utc>>)> {
+        let (timestamp, message) = line.split_once(' ').unwrap_or((&line, ""));
+        let Ok(timestamp) = DateTime::parse_from_rfc3339(timestamp) else {
+            return Some((line, None));
+        };
+        let timestamp = timestamp.with_timezone(&Utc);
+        // Resuming from the start of the second of the last line repeats the lines before it
+        if let Some(skip) = self.skip.as_mut() {
+            if timestamp < skip.since {
+                return None;
+            }
+            if timestamp == skip.since && skip.at_since > 0 {
+                skip.at_since -= 1;
+                return None;
+            }
+            self.skip = None;
+        }
+        let line = if self.keep { line } else { message.to_owned() };
+        Some((line, Some(timestamp)))
+    }
+}
+
+/// The logs of a single container, resuming after the lines returned before if it is attached to again
+fn tail(api: Api<Pod>, pod: String, container: String, resume: Option<Resume>, lp: &LogParams) -> Tail {
+    let mut timestamps = Timestamps {
+        keep: lp.timestamps,
+        skip: resume,
+    };
+    let mut lp = LogParams {
+        container: Some(container.clone()),
+        timestamps: true,
+        ..lp.clone()
+    };
+    if let Some(resume) = resume {
+        lp.since_time = Some(resume.since);
+        lp.since_seconds = None;
+        lp.tail_lines = None;
+        lp.limit_bytes = None;
+    }
+    async_stream::stream! {
+        let reader = match api.log_stream(&pod, &lp).await {
+            Ok(reader) => reader,
+            Err(source) => {
+                yield Err(Error::LogStream { pod, container, source });
+                return;
+            }
+        };
+        let mut lines = reader.lines();
+        while let Some(line) = lines.next().await {
+            match line {
+                Ok(line) => if let Some((line, timestamp)) = timestamps.accept(line) {
+                    let line = LogLine {
+                        pod: pod.clone(),
+                        container: container.clone(),
+                        line,
+                    };
+                    yield Ok((line, timestamp));
+                },
+                Err(source) => {
+                    yield Err(Error::ReadLogs { pod, container, source });
+                    return;
+                }
+            }
+        }
+    }
+    .boxed()
+}
+
+/// Identifies an instance of a container, which changes when the container is restarted
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct Instance {
+    pod: String,
+    container: String,
+    restarts: i32,
+}
+
+/// An item of the logs of a container, or their end
+enum Tailed {
+    Line(Result<LogLine, Error>),
+    End {
+        instance: Instance,
+        resume: Option<Resume>,
+        lines: bool,
+    },
+}
+
+/// Merges the logs of the started containers of the pods of a watch
+fn aggregate<F>(
+    pods: BoxStream<'static, watcher::Result<watcher::Event<Pod>>>,
+    container: Option<String>,
+    follow: bool,
+    tail: F,
+) -> Aggregate<F>
+where
+    F: FnMut(String, String, Option<Resume>) -> Tail,
+{
+    Aggregate {
+        pods: Some(pods),
+        container,
+        follow,
+        tail,
+        tails: SelectAll::new(),
+        attached: HashMap::new(),
+        ended: HashMap::new(),
+        listed: HashSet::new(),
+    }
+}
+
+struct Aggregate<F> {
+    pods: Option<BoxStream<'static, watcher::Result<watcher::Event<Pod>>>>,
+    container: Option<String>,
+    follow: bool,
+    tail: F,
+    tails: SelectAll<stream::Abortable<BoxStream<'static, Tailed>>>,
+    attached: HashMap<Instance, AbortHandle>,
+    /// The instances whose logs ended without lines, to attach to again once they run
+    ended: HashMap<Instance, Option<Resume>>,
+    /// The pods listed since the watch was last restarted
+    listed: HashSet<String>,
+}
+
+impl<F> Aggregate<F>
+where
+    F: FnMut(String, String, Option<Resume>) -> Tail,
+{
+    fn attach(&mut self, instance: Instance, resume: Option<Resume>) {
+        tracing::debug!(pod = %instance.pod, container = %instance.container, "following logs");
+        let mut logs = (self.tail)(instance.pod.clone(), instance.container.clone(), resume);
+        let end = instance.clone();
+        let tailed = async_stream::stream! {
+            let (mut resume, mut lines) = (resume, false);
+            while let Some(line) = logs.next().await {
+                if let Ok((_, Some(timestamp))) = &line {
+                    resume = Some(Resume::after(resume, *timestamp));
+                }
+                lines |= line.is_ok();
+                yield Tailed::Line(line.map(|(line, _)| line));
+            }
+            yield Tailed::End { instance: end, resume, lines };
+        };
+        let (tailed, handle) = stream::abortable(tailed.boxed());
+        self.tails.push(tailed);
+        self.attached.insert(instance, handle);
+    }
+
+    /// Attach to a container again after its logs ended, unless it was restarted or its pod deleted since
+    fn ended(&mut self, instance: Instance, resume: Option<Resume>, lines: bool) {
+        // Logs that are not followed end once they are read, and stay attached so they are not read again
+        if !self.follow || self.attached.remove(&instance).is_none() {
+            return;
+        }
+        if lines {
+            self.attach(instance, resume);
+        } else {
+            // The container may have stopped, so wait for its status rather than polling
+            self.ended.insert(instance, resume);
+        }
+    }
+
+    fn apply(&mut self, pod: &Pod) {
+        if pod.status.is_none() {
+            return;
+        }
+        let name = pod.name_any();
+        let status = pod.status.as_ref();
+        let statuses = status
+            .and_then(|s| s.init_container_statuses.as_ref())
+            .into_iter()
+            .chain(status.and_then(|s| s.container_statuses.as_ref()))
+            .flatten();
+        for status in statuses.filter(|status| has_started(status)) {
+            if self.container.as_ref().is_some_and(|c| *c != status.name) {
+                continue;
+            }
+            let instance = Instance {
+                pod: name.clone(),
+                container: status.name.clone(),
+                restarts: status.restart_count,
+            };
+            if self.attached.contains_key(&instance) {
+                continue;
+            }
+            if let Some(&resume) = self.ended.get(&instance) {
+                let running = status.state.as_ref().is_some_and(|state| state.running.is_some());
+                if running {
+                    self.ended.remove(&instance);
+                    self.attach(instance, resume);
+                }
+                continue;
+            }
+            // The logs of the previous instance end by themselves when it stops
+            let previous = |i: &Instance| i.pod == instance.pod && i.container == instance.container;
+            self.attached.retain(|i, _| !previous(i));
+            self.ended.retain(|i, _| !previous(i));
+            self.attach(instance, None);
+        }
+    }
+
+    fn delete(&mut self, pod: &str) {
+        self.ended.retain(|instance, _| instance.pod != pod);
+        self.attached.retain(|instance, handle| {
+            let keep = instance.pod != pod;
+            if !keep {
+                tracing::debug!(pod, container = %instance.container, "detaching from logs");
+                handle.abort();
+            }
+            keep
+        });
+    }
+
+    fn handle(&mut self, event: watcher::Event<Pod>) {
+        match event {
+            watcher::Event::Apply(pod) => self.apply(&pod),
+            watcher::Event::Delete(pod) => self.delete(&pod.name_any()),
+            watcher::Event::Init => self.listed.clear(),
+            watcher::Event::InitApply(pod) => {
+                self.listed.insert(pod.name_any());
+                self.apply(&pod);
+            }
+            watcher::Event::InitDone => {
+                let gone = self
+                    .attached
+                    .keys()
+                    .chain(self.ended.keys())
+                    .filter(|i| !self.listed.contains(&i.pod))
+                    .map(|i| i.pod.clone())
+                    .collect::<HashSet<_>>();
+                for pod in gone {
+                    self.delete(&pod);
+                }
+            }
+        }
+    }
+}
+
+/// Whether the logs of a container can be streamed
+fn has_started(status: &ContainerStatus) -> bool {
+    status
+        .state
+        .as_ref()
+        .is_some_and(|state| state.running.is_some() || state.terminated.is_some())
+}
+
+impl<F> Unpin for Aggregate<F> {}
+
+impl<F> Stream for Aggregate<F>
+where
+    F: FnMut(String, String, Option<Resume>) -> Tail,
+{
+    type Item = Result<LogLine, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        while let Some(pods) = this.pods.as_mut() {
+            match pods.poll_next_unpin(cx) {
+                Poll::Ready(Some(Ok(event))) => this.handle(event),
+                Poll::Ready(Some(Err(err))) => return Poll::Ready(Some(Err(Error::Watcher(err)))),
+                Poll::Ready(None) => this.pods = None,
+                Poll::Pending => break,
+            }
+        }
+        loop {
+            match this.tails.poll_next_unpin(cx) {
+                Poll::Ready(Some(Tailed::Line(line))) => return Poll::Ready(Some(line)),
+                Poll::Ready(Some(Tailed::End {
+                    instance,
+                    resume,
+                    lines,
+                })) => this.ended(instance, resume, lines),
+                // More containers can be attached to while pods are watched
+                Poll::Ready(None) if this.pods.is_some() => return Poll::Pending,
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::channel::mpsc;
+    use serde_json::json;
+
+    use super::*;
+
+    fn pod(name: &str, containers: &[(&str, i32)]) -> Pod {
+        let statuses = containers
+            .iter()
+            .map(|(container, restarts)| {
+                json!({
+                    "name": container,
+                    "image": "app",
+                    "imageID": "",
+                    "ready": true,
+                    "restartCount": restarts,
+                    "state": { "running": {} },
+                })
+            })
+            .collect::<Vec<_>>();
+        serde_json::from_value(json!({
+            "metadata": { "name": name },
+            "status": {
+                "containerStatuses": statuses,
+                "initContainerStatuses": [{
+                    "name": "init",
+                    "image": "init",
+                    "imageID": "",
+                    "ready": false,
+                    "restartCount": 0,
+                    "state": { "waiting": {} },
+                }],
+            },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn resumes_after_returned_lines() {
+        let mut timestamps = Timestamps {
+            keep: false,
+            skip: None,
+        };
+        let (line, timestamp) = timestamps.accept("2024-05-01T10:00:01.2Z b".into()).unwrap();
+        assert_eq!(line, "b");
+        let resume = Resume::after(Some(Resume::after(None, timestamp.unwrap())), timestamp.unwrap());
+        assert_eq!(resume.at_since, 2);
+        assert_eq!(
+            timestamps.accept("no timestamp".into()),
+            Some(("no timestamp".into(), None))
+        );
+
+        // Resuming from the start of the second repeats the lines before the last ones
+        let mut timestamps = Timestamps {
+            keep: true,
+            skip: Some(resume),
+        };
+        let accepted = [
+            "2024-05-01T10:00:01.1Z a",
+            "2024-05-01T10:00:01.2Z b",
+            "2024-05-01T10:00:01.2Z c",
+            "2024-05-01T10:00:01.2Z d",
+            "2024-05-01T10:00:02Z e",
+        ]
+        .into_iter()
+        .filter_map(|line| timestamps.accept(line.into()))
+        .map(|(line, _)| line)
+        .collect::<Vec<_>>();
+        assert_eq!(accepted, ["2024-05-01T10:00:01.2Z d", "2024-05-01T10:00:02Z e"]);
+    }
+
+    #[tokio::test]
+    async fn aggregates_logs_of_pods() {
+        let (events, pods) = mpsc::unbounded();
+        let mut tails = HashMap::new();
+        let (tails_tx, mut tails_rx) = mpsc::unbounded();
+        let mut logs = aggregate(pods.boxed(), None, true, move |pod, container, _resume| {
+            let (tx, rx) = mpsc::unbounded();
+            tails_tx
+                .unbounded_send((format!("{pod}/{container}"), tx))
+                .unwrap();
+            rx.boxed()
+        });
+        let line = |pod: &str, container: &str, line: &str| LogLine {
+            pod: pod.into(),
+            container: container.into(),
+            line: line.into(),
+        };
+        let send = |event| events.unbounded_send(Ok(event)).unwrap();
+
+        send(watcher::Event::Init);
+        send(watcher::Event::InitApply(pod("a", &[("app", 0), ("sidecar", 0)])));
+        send(watcher::Event::InitDone);
+        let _ = futures::poll!(logs.next());
+        while let Ok((name, tx)) = tails_rx.try_recv() {
+            tails.insert(name, tx);
+        }
+        assert_eq!(tails.len(), 2, "the waiting init container is not followed");
+        tails["a/app"]
+            .unbounded_send(Ok((line("a", "app", "hello"), None)))
+            .unwrap();
+        assert_eq!(logs.next().await.unwrap().unwrap().line, "hello");
+
+        // Restarted containers are followed again, deleted pods are detached from
+        send(watcher::Event::Apply(pod("a", &[("app", 1), ("sidecar", 0)])));
+        send(watcher::Event::Apply(pod("b", &[("app", 0)])));
+        let _ = futures::poll!(logs.next());
+        let mut attached = Vec::new();
+        while let Ok((name, tx)) = tails_rx.try_recv() {
+            attached.push(name.clone());
+            tails.insert(name, tx);
+        }
+        assert_eq!(attached, ["a/app", "b/app"]);
+        send(watcher::Event::Delete(pod("b", &[])));
+        let _ = futures::poll!(logs.next());
+        assert!(tails["b/app"]
+            .unbounded_send(Ok((line("b", "app", "lost"), None)))
+            .is_err());
+
+        // Pods missing from a relist are detached from
+        send(watcher::Event::Init);
+        send(watcher::Event::InitApply(pod("c", &[])));
+        send(watcher::Event::InitDone);
+        let _ = futures::poll!(logs.next());
+        assert!(tails["a/sidecar"]
+            .unbounded_send(Ok((line("a", "sidecar", "lost"), None)))
+            .is_err());
+
+        drop(events);
+        assert!(logs.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn attaches_again_to_ended_logs() {
+        use k8s_openapi::api::core::v1::{ContainerState, ContainerStateTerminated};
+
+        let (events, pods) = mpsc::unbounded();
+        let (tails_tx, mut tails_rx) = mpsc::unbounded();
+        let tail = move |_pod, _container, resume| {
+            let (tx, rx) = mpsc::unbounded();
+            tails_tx.unbounded_send((resume, tx)).unwrap();
+            rx.boxed()
+        };
+        let mut logs = aggregate(pods.boxed(), None, true, tail.clone());
+        let send = |event| events.unbounded_send(Ok(event)).unwrap();
+        let mut terminated = pod("a", &[("app", 0)]);
+        terminated
+            .status
+            .as_mut()
+            .unwrap()
+            .container_statuses
+            .as_mut()
+            .unwrap()[0]
+            .state = Some(ContainerState {
+            terminated: Some(ContainerStateTerminated::default()),
+            ..ContainerState::default()
+        });
+        let hello = LogLine {
+            pod: "a".into(),
+            container: "app".into(),
+            line: "hello".into(),
+        };
+        let timestamp = "2024-05-01T10:00:01Z".parse::<DateTime<Utc>>().unwrap();
+
+        send(watcher::Event::Apply(pod("a", &[("app", 0)])));
+        let _ = futures::poll!(logs.next());
+        let (resume, tx) = tails_rx.try_recv().unwrap();
+        assert_eq!(resume, None);
+        tx.unbounded_send(Ok((hello.clone(), Some(timestamp)))).unwrap();
+        assert_eq!(logs.next().await.unwrap().unwrap(), hello);
+
+        // Logs that ended after returning lines are resumed right away
+        drop(tx);
+        let _ = futures::poll!(logs.next());
+        let (resume, tx) = tails_rx.try_recv().unwrap();
+        let after_hello = Some(Resume {
+            since: timestamp,
+            at_since: 1,
+        });
+        assert_eq!(resume, after_hello);
+
+        // Logs that ended without lines wait for the container to be running
+        drop(tx);
+        let _ = futures::poll!(logs.next());
+        assert!(tails_rx.try_recv().is_err());
+        send(watcher::Event::Apply(terminated));
+        let _ = futures::poll!(logs.next());
+        assert!(tails_rx.try_recv().is_err());
+        send(watcher::Event::Apply(pod("a", &[("app", 0)])));
+        let _ = futures::poll!(logs.next());
+        let (resume, _tx) = tails_rx.try_recv().unwrap();
+        assert_eq!(resume, after_hello);
+
+        // Logs that are not followed are read once
+        let (events, pods) = mpsc::unbounded();
+        let mut logs = aggregate(pods.boxed(), None, false, tail);
+        events
+            .unbounded_send(Ok(watcher::Event::Apply(pod("b", &[("app", 0)]))))
+            .unwrap();
+        let _ = futures::poll!(logs.next());
+        let (_, tx) = tails_rx.try_recv().unwrap();
+        tx.unbounded_send(Ok((hello, Some(timestamp)))).unwrap();
+        drop(tx);
+        assert!(logs.next().await.unwrap().is_ok());
+        let _ = futures::poll!(logs.next());
+        assert!(tails_rx.try_recv().is_err());
+    }
+}